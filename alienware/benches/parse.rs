@@ -0,0 +1,31 @@
+//! The cost of reading the LED zones, using the fixture corpus in `tests/fixtures`
+
+use alienware::parse::{parse_hdmi_source, parse_rgb};
+use alienware::Alienware;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+fn get_rgb_zones(c: &mut Criterion) {
+    let aw = Alienware::with_platform(format!(
+        "{}/tests/fixtures/original/alienware-wmi",
+        env!("CARGO_MANIFEST_DIR")
+    ));
+    c.bench_function("get_rgb_zones", |b| {
+        b.iter(|| black_box(&aw).get_rgb_zones().unwrap())
+    });
+}
+
+fn parsers(c: &mut Criterion) {
+    c.bench_function("parse_rgb", |b| {
+        b.iter(|| parse_rgb(black_box("red: 0, green: 15, blue: 255\n")).unwrap())
+    });
+    c.bench_function("parse_rgb hex", |b| {
+        b.iter(|| parse_rgb(black_box("00ff0a")).unwrap())
+    });
+    c.bench_function("parse_hdmi_source", |b| {
+        b.iter(|| parse_hdmi_source(black_box("cable [gpu] unknown\n")).unwrap())
+    });
+}
+
+criterion_group!(benches, get_rgb_zones, parsers);
+criterion_main!(benches);
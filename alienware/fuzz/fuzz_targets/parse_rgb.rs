@@ -0,0 +1,11 @@
+#![no_main]
+
+use alienware::parse::{format_rgb, parse_rgb};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|contents: &str| {
+    if let Ok(colour) = parse_rgb(contents) {
+        // whatever was read must be written back as a value that reads as the same colour
+        assert_eq!(parse_rgb(&format_rgb(colour)), Ok(colour));
+    }
+});
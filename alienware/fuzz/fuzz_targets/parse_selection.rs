@@ -0,0 +1,14 @@
+#![no_main]
+
+use alienware::parse::{format_hdmi_source, parse_cable_state, parse_hdmi_source, parse_selection};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|contents: &str| {
+    let _ = parse_selection(contents);
+    let _ = parse_cable_state(contents);
+    if let Ok(source) = parse_hdmi_source(contents) {
+        // whatever was selected must be written back as a value that selects the same source
+        let written = format!("[{}]", format_hdmi_source(source));
+        assert_eq!(parse_hdmi_source(&written), Ok(source));
+    }
+});
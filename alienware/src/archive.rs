@@ -0,0 +1,185 @@
+use crate::brightness::BrightnessStore;
+use crate::calibration::Calibrations;
+use crate::{Alienware, Backend};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read_dir, read_to_string, symlink_metadata};
+use std::io::{Read, Write};
+use std::path::{Component, Path};
+use std::sync::Mutex;
+use tar::{Archive, Builder, EntryType, Header};
+
+/// The directory in the archive holding the files of the platform device
+const PLATFORM_DIR: &str = "alienware-wmi";
+
+/// The DMI ids that identify the machine, leaving out serial numbers and uuids
+const DMI_IDS: [&str; 9] = [
+    "sys_vendor",
+    "product_name",
+    "product_version",
+    "product_family",
+    "board_vendor",
+    "board_name",
+    "bios_vendor",
+    "bios_version",
+    "bios_date",
+];
+
+/// Write a gzipped tarball of the files of the platform device, the DMI ids of the machine, the parameters of the
+/// alienware_wmi kernel module and the kernel version, which can be loaded with [`Alienware::from_archive`]
+pub fn dump<W: Write>(aw: &Alienware, writer: W) -> std::io::Result<W> {
+    let mut builder = Builder::new(GzEncoder::new(writer, Compression::default()));
+    if aw.platform().is_dir() {
+        append_tree(&mut builder, aw.platform(), PLATFORM_DIR)?;
+    }
+    append_dir(&mut builder, Path::new("/sys/class/dmi/id"), "dmi")?;
+    for id in DMI_IDS {
+        append_file(
+            &mut builder,
+            &Path::new("/sys/class/dmi/id").join(id),
+            &format!("dmi/{id}"),
+        )?;
+    }
+    let module = Path::new("/sys/module/alienware_wmi/parameters");
+    if module.is_dir() {
+        append_tree(&mut builder, module, "module/parameters")?;
+    }
+    append_dir(&mut builder, Path::new("/proc/sys/kernel"), "kernel")?;
+    append_file(
+        &mut builder,
+        Path::new("/proc/sys/kernel/osrelease"),
+        "kernel/osrelease",
+    )?;
+    builder.into_inner()?.finish()
+}
+
+/// Add a directory entry to the archive
+fn append_dir<W: Write>(builder: &mut Builder<W>, path: &Path, name: &str) -> std::io::Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_size(0);
+    header.set_mtime(mtime(path));
+    builder.append_data(&mut header, name, std::io::empty())
+}
+
+/// Add a file to the archive if it can be read, since some sysfs files are write only or restricted to root
+fn append_file<W: Write>(builder: &mut Builder<W>, path: &Path, name: &str) -> std::io::Result<()> {
+    if let Ok(contents) = read_to_string(path) {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_size(contents.len() as u64);
+        header.set_mtime(mtime(path));
+        builder.append_data(&mut header, name, contents.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Add the files and directories under a directory to the archive, without following links out of the directory
+fn append_tree<W: Write>(builder: &mut Builder<W>, path: &Path, name: &str) -> std::io::Result<()> {
+    append_dir(builder, path, name)?;
+    let mut entries = read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let entry_name = format!("{name}/{}", entry.file_name().to_string_lossy());
+        match symlink_metadata(entry.path()) {
+            Ok(metadata) if metadata.is_dir() => append_tree(builder, &entry.path(), &entry_name)?,
+            Ok(metadata) if metadata.is_file() => append_file(builder, &entry.path(), &entry_name)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The modification time of a file, in seconds since the epoch
+fn mtime(path: &Path) -> u64 {
+    symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+impl Alienware {
+    /// Construct a read only instance of Alienware from an archive written by [`dump`], so that the settings of
+    /// another machine can be read back, e.g. to reproduce a problem.  Writing to it returns an error.
+    pub fn from_archive<R: Read>(reader: R) -> std::io::Result<Alienware> {
+        let mut files = BTreeMap::new();
+        let mut dirs = BTreeSet::new();
+        let mut archive = Archive::new(GzDecoder::new(reader));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let mut components = path.components().filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            });
+            if components.next().as_deref() != Some(PLATFORM_DIR) {
+                continue;
+            }
+            let name = components.collect::<Vec<String>>().join("/");
+            // record every parent directory, in case the archive does not have entries for them
+            let mut parent = name.as_str();
+            while let Some((dir, _)) = parent.rsplit_once('/') {
+                dirs.insert(dir.to_string());
+                parent = dir;
+            }
+            dirs.insert(String::new());
+            if entry.header().entry_type().is_dir() {
+                dirs.insert(name);
+            } else if entry.header().entry_type().is_file() {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                files.insert(name, contents);
+            }
+        }
+        Ok(Alienware {
+            platform: PLATFORM_DIR.to_string(),
+            backend: Backend::Memory {
+                files: Mutex::new(files),
+                dirs,
+                writable: false,
+            },
+            dry_run: None,
+            brightness: BrightnessStore::default(),
+            calibrations: Calibrations::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{setup_aw, setup_not_aw};
+    use crate::{dump, Alienware, Zone};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn dump_and_replay() {
+        let alienware = Alienware::test(setup_aw("dump_and_replay"));
+        let archive = dump(&alienware, Vec::new()).unwrap();
+        let replayed = Alienware::from_archive(archive.as_slice()).unwrap();
+        assert!(replayed.is_alienware());
+        assert_eq!(replayed.get_hdmi().unwrap(), alienware.get_hdmi().unwrap());
+        assert_eq!(
+            replayed.get_rgb_zones().unwrap(),
+            alienware.get_rgb_zones().unwrap()
+        );
+        let error = replayed.set_rgb_zone(Zone::Head, 15, 0, 0).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn dump_and_replay_not_alienware() {
+        let alienware = Alienware::test(setup_not_aw("dump_and_replay_not_alienware"));
+        let archive = dump(&alienware, Vec::new()).unwrap();
+        let replayed = Alienware::from_archive(archive.as_slice()).unwrap();
+        assert!(!replayed.is_alienware());
+        assert!(!replayed.get_rgb_zones().unwrap().exists);
+    }
+}
@@ -0,0 +1,534 @@
+use crate::shared::{flock, open_lock_file};
+use crate::{parse, Alienware, Colour, RGBZone, Zone};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{create_dir_all, metadata, read_to_string, rename, write};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// The file that the brightness and the colours the zones were set to are kept in between runs
+pub const DEFAULT_BRIGHTNESS_FILE: &str = "/var/lib/alienware-wmi/brightness";
+
+/// The brightest that a zone can be, as a percentage
+pub const MAX_BRIGHTNESS: u8 = 100;
+
+/// How brightly the zones are lit, as percentages of the colours they are set to.  Every colour written to a zone is
+/// scaled by both the global brightness and the brightness of the zone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Brightness {
+    pub global: u8,
+    /// The brightness of each zone that is not at full brightness
+    pub zones: HashMap<Zone, u8>,
+}
+
+impl Default for Brightness {
+    fn default() -> Self {
+        Self {
+            global: MAX_BRIGHTNESS,
+            zones: HashMap::new(),
+        }
+    }
+}
+
+impl Brightness {
+    /// The percentage of its colour that a zone is lit at, combining the global brightness with that of the zone
+    pub fn of_zone(&self, zone: Zone) -> u8 {
+        let zone = self.zones.get(&zone).copied().unwrap_or(MAX_BRIGHTNESS);
+        let combined =
+            u16::from(self.global.min(MAX_BRIGHTNESS)) * u16::from(zone.min(MAX_BRIGHTNESS));
+        ((combined + 50) / 100) as u8
+    }
+
+    /// The colour that is written to a zone to show a colour at this brightness
+    pub fn apply(&self, zone: Zone, colour: Colour) -> Colour {
+        colour.scale(f32::from(self.of_zone(zone)) / f32::from(MAX_BRIGHTNESS))
+    }
+}
+
+impl fmt::Display for Brightness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.global)?;
+        for zone in ZONES {
+            if let Some(percent) = self.zones.get(&zone) {
+                write!(f, ", {zone} {percent}%")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The zones in the order they are kept in the file
+const ZONES: [Zone; 3] = [Zone::Head, Zone::Left, Zone::Right];
+
+/// The brightness, with the colour that each zone was last set to and the colour that was written for it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Saved {
+    brightness: Brightness,
+    colours: HashMap<Zone, (Colour, Colour)>,
+    /// Whether it has been read from the file yet
+    loaded: bool,
+    /// The version of the file that was last read or written, to tell when another process has changed it
+    version: Option<(SystemTime, u64)>,
+    /// What has been changed since the file was last read or written
+    changed: Changed,
+}
+
+/// The parts of the brightness file that have been changed by this process and not yet written to it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Changed {
+    global: bool,
+    zones: HashSet<Zone>,
+    colours: HashSet<Zone>,
+}
+
+impl Changed {
+    fn is_empty(&self) -> bool {
+        !self.global && self.zones.is_empty() && self.colours.is_empty()
+    }
+}
+
+impl Saved {
+    /// Make the changes that have not been written yet to a newer copy of the file, so that the changes made by other
+    /// processes in the meantime are kept
+    fn merge_into(&self, mut newer: Saved) -> Saved {
+        if self.changed.global {
+            newer.brightness.global = self.brightness.global;
+        }
+        for zone in self.changed.zones.iter() {
+            match self.brightness.zones.get(zone) {
+                Some(percent) => newer.brightness.zones.insert(*zone, *percent),
+                None => newer.brightness.zones.remove(zone),
+            };
+        }
+        for zone in self.changed.colours.iter() {
+            match self.colours.get(zone) {
+                Some(colours) => newer.colours.insert(*zone, *colours),
+                None => newer.colours.remove(zone),
+            };
+        }
+        newer.loaded = true;
+        newer.changed = self.changed.clone();
+        newer
+    }
+}
+
+/// Receives the problems reading or saving the brightness file of an [`Alienware`], which do not stop the colours from
+/// being written
+pub type BrightnessWarnings = Box<dyn Fn(&Error) + Send + Sync>;
+
+/// The brightness of an [`Alienware`], which is read from its file when it is first needed and again whenever another
+/// process has changed it
+#[derive(Default)]
+pub(crate) struct BrightnessStore {
+    file: Option<PathBuf>,
+    saved: Mutex<Saved>,
+    warnings: Option<BrightnessWarnings>,
+}
+
+impl Alienware {
+    /// Keep the brightness, and the colours that the zones are set to, in a file so that they are shared between runs,
+    /// rather than only for the life of this instance.  [`DEFAULT_BRIGHTNESS_FILE`] is the usual place for it.
+    pub fn brightness_file<P: Into<PathBuf>>(mut self, path: P) -> Alienware {
+        self.brightness.file = Some(path.into());
+        self.brightness.saved = Mutex::new(Saved::default());
+        self
+    }
+
+    /// Pass the problems reading or saving the brightness file to a reporter.  A file that cannot be read is taken as
+    /// full brightness, and colours are still written when the file cannot be saved, so these are not errors.
+    pub fn brightness_warnings<F>(mut self, reporter: F) -> Alienware
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.brightness.warnings = Some(Box::new(reporter));
+        self
+    }
+
+    /// Get the global brightness and the brightness of each zone
+    pub fn get_brightness(&self) -> std::io::Result<Brightness> {
+        Ok(self.lock_brightness().brightness.clone())
+    }
+
+    /// Set the global brightness as a percentage, writing every zone again at the new brightness
+    pub fn set_brightness(&self, percent: u8) -> std::io::Result<()> {
+        check_percent(percent)?;
+        self.change_brightness(|saved| {
+            saved.brightness.global = percent;
+            saved.changed.global = true;
+        })
+    }
+
+    /// Set the brightness of a zone as a percentage, writing it again at the new brightness
+    pub fn set_zone_brightness(&self, zone: Zone, percent: u8) -> std::io::Result<()> {
+        check_percent(percent)?;
+        self.change_brightness(|saved| {
+            if percent == MAX_BRIGHTNESS {
+                saved.brightness.zones.remove(&zone);
+            } else {
+                saved.brightness.zones.insert(zone, percent);
+            }
+            saved.changed.zones.insert(zone);
+        })
+    }
+
+    /// Change the brightness and write the colour of every zone again, so that they show it.  The brightness is saved
+    /// before the zones are written, so that a file that cannot be written leaves the zones as they were.
+    fn change_brightness(&self, change: impl FnOnce(&mut Saved)) -> std::io::Result<()> {
+        let zones = self.get_zone_colours()?;
+        let before = self.lock_brightness().clone();
+        change(&mut self.lock_brightness());
+        if let Err(x) = self.save_brightness() {
+            *self.lock_brightness() = before;
+            return Err(x);
+        }
+        for (zone, colour) in zones {
+            self.set_rgb_zone(zone, colour.red, colour.green, colour.blue)?;
+        }
+        Ok(())
+    }
+
+    /// The colour to write to a zone so that it shows a colour at the brightness it is set to
+    pub(crate) fn effective_colour(&self, zone: Zone, colour: Colour) -> Colour {
+        self.lock_brightness().brightness.apply(zone, colour)
+    }
+
+    /// Keep the colour that a zone was set to along with the colour that was written for it, unless nothing was really
+    /// written as this is a dry run
+    pub(crate) fn record_colour(&self, zone: Zone, colour: Colour, effective: Colour) {
+        if self.is_dry_run() {
+            return;
+        }
+        let mut saved = self.lock_brightness();
+        if saved.colours.get(&zone) != Some(&(colour, effective)) {
+            saved.colours.insert(zone, (colour, effective));
+            saved.changed.colours.insert(zone);
+        }
+    }
+
+    /// The colour that a zone was set to, before its brightness and calibration were applied, from an [`RGBZone`]
+    /// read from it.  If the colour read is not the one that was written then it was set by something else, which is
+    /// taken as it is.
+    pub fn zone_colour(&self, rgb_zone: &RGBZone) -> Colour {
+        let lit = rgb_zone.colour();
+        match self.lock_brightness().colours.get(&rgb_zone.zone) {
+            Some((colour, written)) if *written == lit => *colour,
+            _ => lit,
+        }
+    }
+
+    /// Get the colours that the zones were set to, before their brightness and calibration were applied
+    pub fn get_zone_colours(&self) -> std::io::Result<HashMap<Zone, Colour>> {
+        Ok(self
+            .get_rgb_zones()?
+            .zones
+            .values()
+            .map(|rgb_zone| (rgb_zone.zone, self.zone_colour(rgb_zone)))
+            .collect())
+    }
+
+    /// Write any changes to the brightness or colours to the file, if there is one and this is not a dry run.  The file
+    /// is read again while holding a lock on it so that the changes made by other processes are kept, and is replaced
+    /// rather than written in place so that it is never left half written.
+    pub(crate) fn save_brightness(&self) -> std::io::Result<()> {
+        let path = match &self.brightness.file {
+            Some(path) if !self.is_dry_run() => path,
+            _ => return Ok(()),
+        };
+        let mut saved = self.lock_brightness();
+        if saved.changed.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        // the lock is released when the file is closed
+        let lock = open_lock_file(&path.with_extension("lock"))?;
+        flock(&lock, true)?;
+        let mut merged = saved.merge_into(self.load_saved(path));
+        let temporary = path.with_extension("tmp");
+        write(&temporary, format_saved(&merged))?;
+        rename(&temporary, path)?;
+        merged.version = version(path);
+        merged.changed = Changed::default();
+        *saved = merged;
+        Ok(())
+    }
+
+    /// Save the brightness file after writing colours, which have already been written whether or not it can be
+    /// saved, so a problem saving it is passed to the warnings reporter rather than returned
+    pub(crate) fn save_brightness_or_warn(&self) {
+        if let Err(x) = self.save_brightness() {
+            self.warn(&Error::new(
+                x.kind(),
+                format!("cannot save the brightness file: {x}"),
+            ));
+        }
+    }
+
+    /// Read the brightness file, where a file that does not exist yet is full brightness.  A file that cannot be read
+    /// is also taken as full brightness, and passed to the warnings reporter, rather than stopping the colours from
+    /// being written.
+    fn load_saved(&self, path: &Path) -> Saved {
+        // the version is taken before reading so that a change made while reading is read again next time
+        let version = version(path);
+        let saved = match read_to_string(path) {
+            Ok(contents) => parse_saved(contents.as_str()).map_err(|line| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid line '{line}' in {}", path.display()),
+                )
+            }),
+            Err(x) if x.kind() == ErrorKind::NotFound => Ok(Saved::default()),
+            Err(x) => Err(x),
+        };
+        let saved = saved.unwrap_or_else(|x| {
+            self.warn(&Error::new(
+                x.kind(),
+                format!("cannot read the brightness file, using full brightness: {x}"),
+            ));
+            Saved::default()
+        });
+        Saved { version, ..saved }
+    }
+
+    /// Pass a problem with the brightness file to the warnings reporter, if there is one
+    fn warn(&self, warning: &Error) {
+        if let Some(reporter) = &self.brightness.warnings {
+            reporter(warning);
+        }
+    }
+
+    /// Lock the brightness, reading it from the file the first time and again whenever another process has changed
+    /// it.  Nothing is left half changed by a panic while it is locked, so a poisoned lock can still be used.
+    fn lock_brightness(&self) -> MutexGuard<'_, Saved> {
+        let mut saved = self
+            .brightness
+            .saved
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(path) = &self.brightness.file {
+            if !saved.loaded || version(path) != saved.version {
+                *saved = saved.merge_into(self.load_saved(path));
+            }
+        }
+        saved.loaded = true;
+        saved
+    }
+}
+
+/// Check that a brightness is a percentage
+fn check_percent(percent: u8) -> std::io::Result<()> {
+    if percent > MAX_BRIGHTNESS {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("brightness must be from 0 to {MAX_BRIGHTNESS}%, not {percent}%"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The version of a file, or none if it does not exist.  Modification times can be too coarse to tell apart writes made
+/// close together, but as the file is replaced rather than written in place each version is a new file on unix.
+fn version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = metadata(path).ok()?;
+    #[cfg(unix)]
+    let file = std::os::unix::fs::MetadataExt::ino(&metadata);
+    #[cfg(not(unix))]
+    let file = metadata.len();
+    Some((metadata.modified().ok()?, file))
+}
+
+/// Parse the contents of a brightness file, returning the first line that cannot be read if there is one.  The global
+/// brightness is on a line of its own, and each zone has its brightness followed by the colour it was set to and the
+/// colour that was written for it, e.g. `head 50 0f0000 080000`.
+fn parse_saved(contents: &str) -> Result<Saved, &str> {
+    let mut saved = Saved::default();
+    for line in contents.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let percent = |word: &str| {
+            word.parse::<u8>()
+                .ok()
+                .filter(|percent| *percent <= MAX_BRIGHTNESS)
+        };
+        match words.as_slice() {
+            [] => {}
+            ["brightness", global] => saved.brightness.global = percent(global).ok_or(line)?,
+            [zone, brightness, colours @ ..] => {
+                let zone = zone.parse::<Zone>().map_err(|_| line)?;
+                let brightness = percent(brightness).ok_or(line)?;
+                if brightness != MAX_BRIGHTNESS {
+                    saved.brightness.zones.insert(zone, brightness);
+                }
+                match colours {
+                    [] => {}
+                    [colour, effective] => {
+                        let colour = parse::parse_rgb(colour).map_err(|_| line)?;
+                        let effective = parse::parse_rgb(effective).map_err(|_| line)?;
+                        saved.colours.insert(zone, (colour, effective));
+                    }
+                    _ => return Err(line),
+                }
+            }
+            _ => return Err(line),
+        }
+    }
+    Ok(saved)
+}
+
+/// Write the contents of a brightness file
+fn format_saved(saved: &Saved) -> String {
+    let mut contents = format!("brightness {}\n", saved.brightness.global);
+    for zone in ZONES {
+        let brightness = saved.brightness.zones.get(&zone);
+        let colours = saved.colours.get(&zone);
+        if brightness.is_none() && colours.is_none() {
+            continue;
+        }
+        contents
+            .push_str(format!("{zone} {}", brightness.copied().unwrap_or(MAX_BRIGHTNESS)).as_str());
+        if let Some((colour, effective)) = colours {
+            contents.push_str(
+                format!(
+                    " {} {}",
+                    parse::format_rgb(*colour),
+                    parse::format_rgb(*effective)
+                )
+                .as_str(),
+            );
+        }
+        contents.push('\n');
+    }
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_saved, parse_saved, Brightness};
+    use crate::{Alienware, Colour, Zone};
+    use std::fs::{read_to_string, write};
+    use std::sync::{Arc, Mutex};
+
+    const RED: Colour = Colour::new(15, 0, 0);
+
+    #[test]
+    fn scale() {
+        let mut brightness = Brightness::default();
+        assert_eq!(brightness.apply(Zone::Head, RED), RED);
+        brightness.global = 50;
+        brightness.zones.insert(Zone::Head, 50);
+        assert_eq!(brightness.of_zone(Zone::Head), 25);
+        assert_eq!(brightness.of_zone(Zone::Left), 50);
+        assert_eq!(brightness.apply(Zone::Head, RED), Colour::new(4, 0, 0));
+        assert_eq!(brightness.apply(Zone::Left, RED), Colour::new(8, 0, 0));
+        assert_eq!(brightness.to_string(), "50%, head 50%");
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let contents = "brightness 80\nhead 50 c80000 500000\nright 100 00000f 00000c\n";
+        let saved = parse_saved(contents).unwrap();
+        assert_eq!(saved.brightness.global, 80);
+        assert_eq!(saved.brightness.zones.len(), 1);
+        assert_eq!(
+            saved.colours[&Zone::Right],
+            (Colour::new(0, 0, 15), Colour::new(0, 0, 12))
+        );
+        assert_eq!(format_saved(&saved), contents);
+        for invalid in ["brightness 101", "middle 50", "head 50 c80000", "head"] {
+            assert_eq!(parse_saved(invalid), Err(invalid));
+        }
+    }
+
+    #[test]
+    fn brightness() {
+        let aw = Alienware::in_memory();
+        aw.set_rgb_zone(Zone::Head, RED.red, RED.green, RED.blue)
+            .unwrap();
+        aw.set_brightness(50).unwrap();
+        aw.set_zone_brightness(Zone::Head, 50).unwrap();
+        assert!(aw.set_brightness(101).is_err());
+        let head = aw.get_rgb_zones().unwrap().zones[&Zone::Head];
+        assert_eq!(aw.zone_colour(&head), RED);
+        assert_eq!(head.colour(), Colour::new(4, 0, 0));
+        assert_eq!(aw.get_brightness().unwrap().of_zone(Zone::Head), 25);
+
+        // back to full brightness
+        aw.set_brightness(100).unwrap();
+        aw.set_zone_brightness(Zone::Head, 100).unwrap();
+        let head = aw.get_rgb_zones().unwrap().zones[&Zone::Head];
+        assert_eq!(head.colour(), RED);
+        assert_eq!(aw.get_brightness().unwrap(), Brightness::default());
+    }
+
+    #[test]
+    fn brightness_file() {
+        let dir = std::env::temp_dir().join(format!("alienware-brightness-{}", std::process::id()));
+        let path = dir.join("brightness");
+        let _ = std::fs::remove_dir_all(&dir);
+        let aw = Alienware::in_memory().brightness_file(&path);
+        aw.set_brightness(50).unwrap();
+        aw.set_rgb_zone(Zone::Head, RED.red, RED.green, RED.blue)
+            .unwrap();
+        // the colour is saved when the Alienware is dropped
+        drop(aw);
+        let contents = read_to_string(&path).unwrap();
+        assert!(
+            contents.starts_with("brightness 50\nhead 100 0f0000 080000\n"),
+            "{contents}"
+        );
+
+        // another run reads the brightness back, and a zone changed by something else is taken as it is
+        let aw = Alienware::in_memory().brightness_file(&path);
+        aw.set_rgb_zone(Zone::Head, RED.red, RED.green, RED.blue)
+            .unwrap();
+        let zones = aw.get_rgb_zones().unwrap().zones;
+        assert_eq!(aw.zone_colour(&zones[&Zone::Head]), RED);
+        assert_eq!(zones[&Zone::Head].colour(), Colour::new(8, 0, 0));
+        assert_eq!(
+            aw.zone_colour(&zones[&Zone::Left]),
+            zones[&Zone::Left].colour()
+        );
+
+        // changes made by another process are read back and kept when this one saves its own
+        let other = Alienware::in_memory().brightness_file(&path);
+        other.set_zone_brightness(Zone::Left, 20).unwrap();
+        assert_eq!(aw.get_brightness().unwrap().zones[&Zone::Left], 20);
+        aw.set_rgb_zone(Zone::Right, RED.red, RED.green, RED.blue)
+            .unwrap();
+        drop(aw);
+        let contents = read_to_string(&path).unwrap();
+        assert!(contents.contains("left 20 "), "{contents}");
+        assert!(contents.contains("right 100 0f0000 080000"), "{contents}");
+
+        // a file that cannot be read is full brightness, with a warning
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let warned = warnings.clone();
+        let with_warnings = move |aw: Alienware| {
+            let warned = warned.clone();
+            aw.brightness_warnings(move |x| warned.lock().unwrap().push(x.to_string()))
+        };
+        write(&path, "brightness dim\n").unwrap();
+        let aw = with_warnings(Alienware::in_memory().brightness_file(&path));
+        assert_eq!(aw.get_brightness().unwrap(), Brightness::default());
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+        drop(aw);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // a file that cannot be saved does not stop the colour from being written
+        write(&dir, "").unwrap();
+        let aw = with_warnings(Alienware::in_memory().brightness_file(&path));
+        aw.set_rgb_zone(Zone::Head, RED.red, RED.green, RED.blue)
+            .unwrap();
+        assert_eq!(aw.get_rgb_zones().unwrap().zones[&Zone::Head].colour(), RED);
+        assert!(aw.set_brightness(50).is_err());
+        assert_eq!(aw.get_brightness().unwrap(), Brightness::default());
+        drop(aw);
+        let warnings = warnings.lock().unwrap();
+        assert!(warnings[1].starts_with("cannot read"), "{warnings:?}");
+        assert!(warnings[2].starts_with("cannot save"), "{warnings:?}");
+        std::fs::remove_file(&dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::effects::MAX_LEVEL;
+use crate::{Alienware, Colour, Zone};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// How the colours written to a zone are corrected so that it shows them the same as the other zones.  Each channel is
+/// put through the gamma curve, as a fraction of the brightest level of the named colours, and then scaled by its gain
+/// and by the white point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    /// How much of each of the red, green and blue channels is shown, from 0 to 1
+    pub gain: [f32; 3],
+    /// The curve that each channel is put through, where 1 is a straight line and more than 1 darkens the levels in
+    /// the middle
+    pub gamma: f32,
+    /// The colour that is written to show white, which sets the balance of the channels
+    pub white: Colour,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            gain: [1.0; 3],
+            gamma: 1.0,
+            white: Colour::new(MAX_LEVEL, MAX_LEVEL, MAX_LEVEL),
+        }
+    }
+}
+
+impl Calibration {
+    /// The colour to write so that a colour is shown as it should be
+    pub fn apply(&self, colour: Colour) -> Colour {
+        let full = f32::from(MAX_LEVEL);
+        let channel = |level: u8, gain: f32, white: u8| {
+            let curved = (f32::from(level) / full).powf(self.gamma);
+            let level = curved * gain.clamp(0.0, 1.0) * f32::from(white);
+            if level.is_nan() {
+                0
+            } else {
+                level.round().clamp(0.0, 255.0) as u8
+            }
+        };
+        Colour::new(
+            channel(colour.red, self.gain[0], self.white.red),
+            channel(colour.green, self.gain[1], self.white.green),
+            channel(colour.blue, self.gain[2], self.white.blue),
+        )
+    }
+
+    /// Whether this leaves every colour as it is
+    pub fn is_identity(&self) -> bool {
+        *self == Calibration::default()
+    }
+}
+
+/// The calibration of each zone of an [`Alienware`], where a zone without one is not corrected
+#[derive(Default)]
+pub(crate) struct Calibrations {
+    zones: Mutex<HashMap<Zone, Calibration>>,
+}
+
+impl Calibrations {
+    /// Nothing is left half changed by a panic while the calibrations are locked, so a poisoned lock can still be used
+    fn lock(&self) -> MutexGuard<'_, HashMap<Zone, Calibration>> {
+        self.zones
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Alienware {
+    /// Get the calibration of a zone
+    pub fn get_calibration(&self, zone: Zone) -> Calibration {
+        self.calibrations
+            .lock()
+            .get(&zone)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set the calibration of a zone, which corrects every colour written to it from then on
+    pub fn set_calibration(&self, zone: Zone, calibration: Calibration) {
+        let mut zones = self.calibrations.lock();
+        if calibration.is_identity() {
+            zones.remove(&zone);
+        } else {
+            zones.insert(zone, calibration);
+        }
+    }
+
+    /// The colour that is written to a zone to show a colour, scaled by the brightness of the zone and then calibrated
+    pub(crate) fn output_colour(&self, zone: Zone, colour: Colour) -> std::io::Result<Colour> {
+        let colour = self.effective_colour(zone, colour);
+        Ok(self.get_calibration(zone).apply(colour))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Calibration;
+    use crate::{Alienware, Colour, Zone};
+
+    const WHITE: Colour = Colour::new(15, 15, 15);
+
+    #[test]
+    fn calibrate() {
+        let identity = Calibration::default();
+        assert!(identity.is_identity());
+        for level in [0, 1, 8, 15, 16, 255] {
+            let colour = Colour::new(level, level, level);
+            assert_eq!(identity.apply(colour), colour);
+        }
+        let warm = Calibration {
+            gain: [1.0, 0.8, 0.5],
+            white: Colour::new(15, 15, 12),
+            ..Default::default()
+        };
+        assert_eq!(warm.apply(WHITE), Colour::new(15, 12, 6));
+        let gamma = Calibration {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(gamma.apply(Colour::new(15, 8, 3)), Colour::new(15, 4, 1));
+    }
+
+    #[test]
+    fn write_calibrated() {
+        let aw = Alienware::in_memory();
+        let calibration = Calibration {
+            gain: [1.0, 1.0, 0.6],
+            ..Default::default()
+        };
+        aw.set_calibration(Zone::Head, calibration);
+        aw.set_brightness(50).unwrap();
+        aw.set_rgb_zone(Zone::Head, 15, 15, 15).unwrap();
+        aw.set_rgb_zone(Zone::Left, 15, 15, 15).unwrap();
+        let zones = aw.get_rgb_zones().unwrap().zones;
+        assert_eq!(aw.zone_colour(&zones[&Zone::Head]), WHITE);
+        assert_eq!(zones[&Zone::Head].colour(), Colour::new(8, 8, 5));
+        assert_eq!(zones[&Zone::Left].colour(), Colour::new(8, 8, 8));
+        assert_eq!(aw.get_calibration(Zone::Head), calibration);
+        aw.set_calibration(Zone::Head, Calibration::default());
+        assert!(aw.get_calibration(Zone::Head).is_identity());
+    }
+}
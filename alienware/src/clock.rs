@@ -0,0 +1,247 @@
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// A source of time for animations, so that they can be run against a [`ManualClock`] in tests instead of real time
+pub trait Clock: Send + Sync {
+    /// The time since the clock started, which never goes backwards
+    fn now(&self) -> Duration;
+
+    /// Wait for a duration to pass
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real, monotonic, time
+#[derive(Clone, Copy, Debug)]
+pub struct MonotonicClock {
+    start: Instant,
+}
+
+impl MonotonicClock {
+    /// Construct a clock that starts now
+    pub fn new() -> MonotonicClock {
+        MonotonicClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        sleep(duration);
+    }
+}
+
+/// A clock that only moves when it is told to, for tests.  Sleeping moves it on by the time slept straight away.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Construct a clock that starts at zero
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    /// Move the clock on by a duration, such as to stand in for a frame that took a long time
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.now
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.lock()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// A frame that is due to be shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tick {
+    /// The number of the frame, counting from 0 at the start and including the frames that were dropped
+    pub frame: u64,
+    /// The time of the frame since the start, which is always a whole number of frame intervals
+    pub t: Duration,
+    /// How many frames were dropped before this one because they were not shown in time
+    pub dropped: u64,
+}
+
+/// Paces the frames of an animation.  Each frame is due a whole number of intervals after the start, so time lost to
+/// oversleeping or slow frames does not build up, and when frames are missed altogether they are dropped rather than
+/// rushed through to catch up.
+pub struct FrameScheduler<'a> {
+    clock: &'a dyn Clock,
+    interval: Duration,
+    start: Duration,
+    next: u64,
+    dropped: u64,
+}
+
+impl<'a> FrameScheduler<'a> {
+    /// Construct a scheduler for a number of frames a second, starting from the current time of a clock
+    pub fn new(clock: &'a dyn Clock, fps: u32) -> FrameScheduler<'a> {
+        FrameScheduler {
+            clock,
+            interval: Duration::from_secs(1) / fps.max(1),
+            start: clock.now(),
+            next: 0,
+            dropped: 0,
+        }
+    }
+
+    /// The time between frames
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// How many frames have been dropped since the start
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Wait until the next frame is due and return it, dropping any frames whose time has already passed
+    pub fn next_frame(&mut self) -> Tick {
+        let interval = self.interval.as_nanos();
+        let now = self.clock.now().saturating_sub(self.start);
+        let due = self.frame_time(self.next);
+        let frame = if now < due {
+            self.clock.sleep(due - now);
+            self.next
+        } else {
+            // the latest frame that is due, which is the next one unless some were missed
+            u64::try_from(now.as_nanos() / interval)
+                .unwrap_or(u64::MAX)
+                .max(self.next)
+        };
+        let dropped = frame - self.next;
+        self.dropped += dropped;
+        self.next = frame + 1;
+        Tick {
+            frame,
+            t: self.frame_time(frame),
+            dropped,
+        }
+    }
+
+    /// The time since the start that a frame is due
+    fn frame_time(&self, frame: u64) -> Duration {
+        let nanos = self.interval.as_nanos() * u128::from(frame);
+        Duration::new(
+            u64::try_from(nanos / 1_000_000_000).unwrap_or(u64::MAX),
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+}
+
+/// Parse a duration such as `500ms`, `2s`, `1m` or, like the shell `sleep`, a number of seconds such as `.5`
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    let (value, scale) = if let Some(value) = input.strip_suffix("ms") {
+        (value, 0.001)
+    } else if let Some(value) = input.strip_suffix('s') {
+        (value, 1.0)
+    } else if let Some(value) = input.strip_suffix('m') {
+        (value, 60.0)
+    } else {
+        (input.as_str(), 1.0)
+    };
+    let value = value.trim().parse::<f64>().ok()?;
+    if value.is_finite() && value >= 0.0 {
+        Duration::try_from_secs_f64(value * scale).ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_duration, Clock, FrameScheduler, ManualClock, MonotonicClock, Tick};
+    use std::time::Duration;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn manual_clock() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now(), Duration::ZERO);
+        clock.sleep(MS * 5);
+        clock.advance(MS);
+        assert_eq!(clock.now(), MS * 6);
+    }
+
+    #[test]
+    fn monotonic_clock() {
+        let clock = MonotonicClock::new();
+        let before = clock.now();
+        clock.sleep(MS);
+        assert!(clock.now() >= before + MS);
+    }
+
+    #[test]
+    fn schedule_frames() {
+        let clock = ManualClock::new();
+        clock.advance(MS * 1000);
+        let mut scheduler = FrameScheduler::new(&clock, 10);
+        assert_eq!(scheduler.interval(), MS * 100);
+        for frame in 0..3 {
+            let tick = scheduler.next_frame();
+            assert_eq!(
+                tick,
+                Tick {
+                    frame,
+                    t: MS * 100 * frame as u32,
+                    dropped: 0
+                }
+            );
+            assert_eq!(clock.now(), MS * 1000 + tick.t);
+        }
+        // a slow frame is made up for by sleeping less before the next one
+        clock.advance(MS * 30);
+        assert_eq!(scheduler.next_frame().t, MS * 300);
+        assert_eq!(clock.now(), MS * 1300);
+        // a frame that runs past the time of the one after drops the frames that were missed
+        clock.advance(MS * 250);
+        assert_eq!(
+            scheduler.next_frame(),
+            Tick {
+                frame: 5,
+                t: MS * 500,
+                dropped: 1
+            }
+        );
+        assert_eq!(clock.now(), MS * 1550);
+        assert_eq!(scheduler.next_frame().t, MS * 600);
+        assert_eq!(scheduler.dropped(), 1);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Some(MS * 500));
+        assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration(".5"), Some(MS * 500));
+        assert_eq!(parse_duration("1m"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+}
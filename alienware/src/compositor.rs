@@ -0,0 +1,389 @@
+use crate::effects::{Effect, Frame, MAX_LEVEL};
+use crate::{Colour, ParseError, Zone};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How the colour of a layer is combined with the colour of the layers below it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The colour of the layer covers the colours below it
+    #[default]
+    Normal,
+    /// The components are added together up to full brightness, so black leaves the colours below unchanged
+    Add,
+    /// The components are multiplied together as fractions of full brightness, which can only darken
+    Multiply,
+    /// The inverse of multiplying the inverses, which can only lighten
+    Screen,
+    /// The brighter of each of the components
+    Lighten,
+    /// The darker of each of the components
+    Darken,
+}
+
+impl BlendMode {
+    /// Combine the colour of a layer with the colour below it, before the opacity of the layer is taken into account.
+    /// The components are fractions of [`MAX_LEVEL`], which is the most that the blended components can be.
+    pub fn blend(self, below: Colour, layer: Colour) -> Colour {
+        let full = u16::from(MAX_LEVEL);
+        let blend = |below: u8, layer: u8| -> u8 {
+            let (below, layer) = (below.min(MAX_LEVEL), layer.min(MAX_LEVEL));
+            let (below_, layer_) = (u16::from(below), u16::from(layer));
+            match self {
+                BlendMode::Normal => layer,
+                BlendMode::Add => (below + layer).min(MAX_LEVEL),
+                BlendMode::Multiply => ((below_ * layer_ + full / 2) / full) as u8,
+                BlendMode::Screen => {
+                    (full - ((full - below_) * (full - layer_) + full / 2) / full) as u8
+                }
+                BlendMode::Lighten => below.max(layer),
+                BlendMode::Darken => below.min(layer),
+            }
+        };
+        Colour::new(
+            blend(below.red, layer.red),
+            blend(below.green, layer.green),
+            blend(below.blue, layer.blue),
+        )
+    }
+}
+
+impl fmt::Display for BlendMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlendMode::Normal => write!(f, "normal"),
+            BlendMode::Add => write!(f, "add"),
+            BlendMode::Multiply => write!(f, "multiply"),
+            BlendMode::Screen => write!(f, "screen"),
+            BlendMode::Lighten => write!(f, "lighten"),
+            BlendMode::Darken => write!(f, "darken"),
+        }
+    }
+}
+
+impl FromStr for BlendMode {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<BlendMode, ParseError> {
+        match input.trim().to_lowercase().as_str() {
+            "normal" => Ok(BlendMode::Normal),
+            "add" => Ok(BlendMode::Add),
+            "multiply" => Ok(BlendMode::Multiply),
+            "screen" => Ok(BlendMode::Screen),
+            "lighten" => Ok(BlendMode::Lighten),
+            "darken" => Ok(BlendMode::Darken),
+            _ => Err(ParseError::UnknownOption(input.to_string())),
+        }
+    }
+}
+
+/// What a layer shows
+enum Content {
+    Colours(Frame),
+    Effect(Box<dyn Effect>),
+}
+
+/// A layer of a [`Compositor`], which colours some or all of the zones.  Zones that a layer does not colour show the
+/// layers below through it.
+pub struct Layer {
+    name: String,
+    priority: i32,
+    blend: BlendMode,
+    opacity: f32,
+    expiry: Option<Duration>,
+    content: Content,
+    start: Option<Duration>,
+}
+
+impl Layer {
+    /// The priority of the theme that is shown when nothing else is
+    pub const BASE: i32 = 0;
+    /// The priority of an effect shown over the base theme
+    pub const EFFECT: i32 = 100;
+    /// The priority of a notification, such as a flash when a build fails, which is shown over everything else
+    pub const NOTIFICATION: i32 = 200;
+
+    /// Construct a layer that shows a colour on each of some zones
+    pub fn colours(name: &str, priority: i32, colours: Frame) -> Layer {
+        Layer::with_content(name, priority, Content::Colours(colours))
+    }
+
+    /// Construct a layer that shows an effect, which starts when the layer is first shown
+    pub fn effect(name: &str, priority: i32, effect: Box<dyn Effect>) -> Layer {
+        Layer::with_content(name, priority, Content::Effect(effect))
+    }
+
+    fn with_content(name: &str, priority: i32, content: Content) -> Layer {
+        Layer {
+            name: name.to_string(),
+            priority,
+            blend: BlendMode::default(),
+            opacity: 1.0,
+            expiry: None,
+            content,
+            start: None,
+        }
+    }
+
+    /// Set how the layer is combined with the layers below it, which is [`BlendMode::Normal`] by default
+    pub fn blend(mut self, blend: BlendMode) -> Layer {
+        self.blend = blend;
+        self
+    }
+
+    /// Set how much of the layer is shown over the layers below it, from 0 for none to 1 for all, which is the default
+    pub fn opacity(mut self, opacity: f32) -> Layer {
+        self.opacity = if opacity.is_nan() {
+            0.0
+        } else {
+            opacity.clamp(0.0, 1.0)
+        };
+        self
+    }
+
+    /// Remove the layer once it has been shown for a duration, so that the layers below show again
+    pub fn expire_after(mut self, duration: Duration) -> Layer {
+        self.expiry = Some(duration);
+        self
+    }
+
+    /// The name of the layer
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// The priority of the layer, where layers with a higher priority are shown over those with a lower one
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Whether the layer has been shown for as long as it is shown for by a time
+    fn has_expired(&self, t: Duration) -> bool {
+        match (self.start, self.expiry) {
+            (Some(start), Some(expiry)) => t >= start + expiry,
+            _ => false,
+        }
+    }
+
+    /// The colours of the layer at a time since the compositor started
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let start = *self.start.get_or_insert(t);
+        match &mut self.content {
+            Content::Colours(colours) => colours.clone(),
+            Content::Effect(effect) => effect.frame(t.saturating_sub(start), zones),
+        }
+    }
+}
+
+/// A stack of layers, such as a base theme with effects and notifications over it, that are combined into the colour
+/// of each zone.  It is an [`Effect`] itself, so it is shown with [`effects::run`](crate::effects::run) like any other,
+/// and layers that expire are removed as it is shown.
+#[derive(Default)]
+pub struct Compositor {
+    layers: Vec<Layer>,
+}
+
+impl Compositor {
+    /// Construct a compositor without any layers
+    pub fn new() -> Compositor {
+        Compositor::default()
+    }
+
+    /// Add a layer over the layers with the same or a lower priority, replacing any layer with the same name
+    pub fn add(&mut self, layer: Layer) {
+        self.remove(layer.name());
+        let index = self
+            .layers
+            .iter()
+            .position(|x| x.priority > layer.priority)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(index, layer);
+    }
+
+    /// Remove a layer by name, returning whether there was one
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.layers.len();
+        self.layers.retain(|layer| layer.name != name);
+        self.layers.len() != len
+    }
+
+    /// The layers from the bottom to the top
+    pub fn layers(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter()
+    }
+
+    /// Whether there are no layers
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+impl Effect for Compositor {
+    /// Combine the layers from the bottom up, removing any that have expired.  Zones that no layer colours are left
+    /// out, so they are not written.
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        self.layers.retain(|layer| !layer.has_expired(t));
+        let mut frame = Frame::new();
+        for layer in self.layers.iter_mut() {
+            let (blend, opacity) = (layer.blend, layer.opacity);
+            for (zone, colour) in layer.frame(t, zones) {
+                if !zones.contains(&zone) {
+                    continue;
+                }
+                let below = frame.get(&zone).copied().unwrap_or_default();
+                frame.insert(zone, below.mix(blend.blend(below, colour), opacity));
+            }
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlendMode, Compositor, Layer};
+    use crate::effects::{run_with, Effect, Frame, Strobe};
+    use crate::{Alienware, Colour, FrameScheduler, ManualClock, Zone};
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    const ZONES: [Zone; 3] = [Zone::Head, Zone::Left, Zone::Right];
+    const RED: Colour = Colour::new(15, 0, 0);
+    const BLUE: Colour = Colour::new(0, 0, 15);
+    const WHITE: Colour = Colour::new(15, 15, 15);
+
+    fn all(colour: Colour) -> Frame {
+        ZONES.iter().map(|zone| (*zone, colour)).collect()
+    }
+
+    #[test]
+    fn blend_modes() {
+        let below = Colour::new(12, 6, 0);
+        let layer = Colour::new(6, 15, 0);
+        assert_eq!(BlendMode::Normal.blend(below, layer), layer);
+        assert_eq!(BlendMode::Add.blend(below, layer), Colour::new(15, 15, 0));
+        assert_eq!(
+            BlendMode::Multiply.blend(below, layer),
+            Colour::new(5, 6, 0)
+        );
+        assert_eq!(
+            BlendMode::Screen.blend(below, layer),
+            Colour::new(13, 15, 0)
+        );
+        assert_eq!(
+            BlendMode::Lighten.blend(below, layer),
+            Colour::new(12, 15, 0)
+        );
+        assert_eq!(BlendMode::Darken.blend(below, layer), Colour::new(6, 6, 0));
+        // full brightness is the most that any of them can give
+        for mode in [BlendMode::Add, BlendMode::Multiply, BlendMode::Screen] {
+            assert_eq!(mode.blend(WHITE, WHITE), WHITE, "{mode}");
+        }
+        assert_eq!(BlendMode::Multiply.blend(WHITE, RED), RED);
+        for mode in ["normal", "add", "multiply", "screen", "lighten", "darken"] {
+            assert_eq!(mode.parse::<BlendMode>().unwrap().to_string(), mode);
+        }
+        assert!("overlay".parse::<BlendMode>().is_err());
+    }
+
+    #[test]
+    fn layers() {
+        let mut compositor = Compositor::new();
+        assert!(compositor.frame(Duration::ZERO, &ZONES).is_empty());
+        compositor.add(Layer::colours("base", Layer::BASE, all(BLUE)));
+        compositor.add(
+            Layer::colours(
+                "alert",
+                Layer::NOTIFICATION,
+                [(Zone::Head, RED)].into_iter().collect(),
+            )
+            .opacity(0.5),
+        );
+        compositor.add(Layer::colours(
+            "glow",
+            Layer::EFFECT,
+            [(Zone::Head, RED), (Zone::Left, RED)].into_iter().collect(),
+        ));
+        let names: Vec<&str> = compositor.layers().map(Layer::name).collect();
+        assert_eq!(names, ["base", "glow", "alert"]);
+        let frame = compositor.frame(Duration::ZERO, &ZONES);
+        assert_eq!(frame[&Zone::Head], RED);
+        assert_eq!(frame[&Zone::Left], RED);
+        assert_eq!(frame[&Zone::Right], BLUE);
+
+        // the notification is half over the base once the effect is gone
+        assert!(compositor.remove("glow"));
+        assert!(!compositor.remove("glow"));
+        let frame = compositor.frame(Duration::ZERO, &[Zone::Head]);
+        assert_eq!(frame.len(), 1);
+        assert_eq!(frame[&Zone::Head], Colour::new(8, 0, 8));
+
+        // a layer with the same name replaces the one that was there
+        compositor.add(Layer::colours("base", Layer::BASE, all(RED)).blend(BlendMode::Add));
+        assert_eq!(compositor.layers().count(), 2);
+        assert_eq!(compositor.frame(Duration::ZERO, &ZONES)[&Zone::Left], RED);
+    }
+
+    #[test]
+    fn expiry() {
+        let mut compositor = Compositor::new();
+        compositor.add(Layer::colours("base", Layer::BASE, all(BLUE)));
+        let strobe = Strobe {
+            colour: RED,
+            period: Duration::from_millis(200),
+            duty: 0.5,
+        };
+        compositor.add(
+            Layer::effect("flash", Layer::NOTIFICATION, Box::new(strobe))
+                .blend(BlendMode::Lighten)
+                .expire_after(Duration::from_millis(400)),
+        );
+        // the effect starts when the layer is first shown rather than when the compositor did
+        let head = |compositor: &mut Compositor, millis| {
+            compositor.frame(Duration::from_millis(millis), &ZONES)[&Zone::Head]
+        };
+        assert_eq!(head(&mut compositor, 1000), Colour::new(15, 0, 15));
+        assert_eq!(head(&mut compositor, 1150), BLUE);
+        assert_eq!(head(&mut compositor, 1200), Colour::new(15, 0, 15));
+        assert_eq!(head(&mut compositor, 1400), BLUE);
+        assert_eq!(compositor.layers().count(), 1);
+    }
+
+    #[test]
+    fn run_layers() {
+        let aw = Alienware::in_memory();
+        let before = aw.get_rgb_zones().unwrap().zones[&Zone::Left].colour();
+        let clock = ManualClock::new();
+        let mut compositor = Compositor::new();
+        compositor.add(Layer::colours("base", Layer::BASE, all(BLUE)));
+        compositor.add(
+            Layer::colours("alert", Layer::NOTIFICATION, all(RED))
+                .expire_after(Duration::from_millis(200)),
+        );
+        run_with(
+            &mut compositor,
+            &aw,
+            &[Zone::Head, Zone::Right],
+            &mut FrameScheduler::new(&clock, 10),
+            &AtomicBool::new(true),
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap();
+        let zones = aw.get_rgb_zones().unwrap().zones;
+        assert_eq!(zones[&Zone::Head].colour(), RED);
+        assert_eq!(zones[&Zone::Left].colour(), before);
+        run_with(
+            &mut compositor,
+            &aw,
+            &[Zone::Head, Zone::Right],
+            &mut FrameScheduler::new(&clock, 10),
+            &AtomicBool::new(true),
+            Some(Duration::from_millis(200)),
+        )
+        .unwrap();
+        assert_eq!(
+            aw.get_rgb_zones().unwrap().zones[&Zone::Head].colour(),
+            BLUE
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Reading JSON and TOML documents, such as light shows and state documents.  A TOML document is converted into the
+//! equivalent JSON so that both formats share the same reader.
+
+use crate::Colour;
+use jzon::JsonValue;
+
+/// The formats that a document can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Guess the format of a document from the extension of its file name
+    pub fn from_name(file: &str) -> Option<Format> {
+        if file.ends_with(".toml") {
+            Some(Format::Toml)
+        } else if file.ends_with(".json") {
+            Some(Format::Json)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse a document into JSON, trying JSON and then TOML if the format is not known
+pub fn parse(contents: &str, format: Option<Format>) -> Result<JsonValue, String> {
+    match format {
+        Some(Format::Json) => jzon::parse(contents).map_err(|x| x.to_string()),
+        Some(Format::Toml) => toml::from_str::<toml::Value>(contents)
+            .map(|value| toml_to_json(&value))
+            .map_err(|x| x.to_string()),
+        None => match jzon::parse(contents) {
+            Ok(value) => Ok(value),
+            Err(_) => toml::from_str::<toml::Value>(contents)
+                .map(|value| toml_to_json(&value))
+                .map_err(|_| "the document is neither JSON nor TOML".to_string()),
+        },
+    }
+}
+
+/// Convert a TOML value into the equivalent JSON
+pub fn toml_to_json(value: &toml::Value) -> JsonValue {
+    match value {
+        toml::Value::String(s) => s.as_str().into(),
+        toml::Value::Integer(i) => (*i).into(),
+        toml::Value::Float(f) => (*f).into(),
+        toml::Value::Boolean(b) => (*b).into(),
+        toml::Value::Datetime(d) => d.to_string().into(),
+        toml::Value::Array(a) => JsonValue::Array(a.iter().map(toml_to_json).collect()),
+        toml::Value::Table(t) => {
+            let mut object = JsonValue::new_object();
+            for (key, value) in t {
+                object[key.as_str()] = toml_to_json(value);
+            }
+            object
+        }
+    }
+}
+
+/// Read a colour, either as a colour string, a list of components or an object of components
+pub fn colour_from_json(value: &JsonValue) -> Result<Colour, String> {
+    if let Some(s) = value.as_str() {
+        return s.parse().map_err(|x| format!("invalid colour '{s}': {x}"));
+    }
+    let components: Option<Vec<u8>> = if value.is_array() {
+        value.members().map(JsonValue::as_u8).collect()
+    } else if value.is_object() {
+        ["red", "green", "blue"]
+            .iter()
+            .map(|name| value[*name].as_u8())
+            .collect()
+    } else {
+        None
+    };
+    match components.as_deref() {
+        Some([red, green, blue]) => Ok(Colour::new(*red, *green, *blue)),
+        _ => Err(format!("invalid colour {value}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{colour_from_json, parse, Format};
+    use crate::Colour;
+
+    #[test]
+    fn parse_either_format() {
+        let json = parse(r#"{"leds": {"head": "red"}}"#, None).unwrap();
+        let toml = parse("[leds]\nhead = \"red\"\n", None).unwrap();
+        assert_eq!(json, toml);
+        assert_eq!(
+            parse("[leds]\nhead = \"red\"\n", Some(Format::Toml)).unwrap(),
+            json
+        );
+        assert!(parse("[leds]\nhead = \"red\"\n", Some(Format::Json)).is_err());
+        assert!(parse("not a document", None).is_err());
+        assert_eq!(Format::from_name("show.toml"), Some(Format::Toml));
+        assert_eq!(Format::from_name("-"), None);
+    }
+
+    #[test]
+    fn colours() {
+        let colour = |json: &str| colour_from_json(&jzon::parse(json).unwrap());
+        assert_eq!(colour(r#""cyan""#), Ok(Colour::new(0, 15, 15)));
+        assert_eq!(colour("[1, 2, 3]"), Ok(Colour::new(1, 2, 3)));
+        assert_eq!(
+            colour(r#"{"red": 1, "green": 2, "blue": 3}"#),
+            Ok(Colour::new(1, 2, 3))
+        );
+        assert!(colour(r#"{"red": 300, "green": 0, "blue": 0}"#).is_err());
+        assert!(colour(r#"{"red": 1}"#).is_err());
+        assert!(colour("7").is_err());
+    }
+}
@@ -0,0 +1,450 @@
+//! Animated lighting effects, which give the colour of each zone at any point in time, and a loop that shows them.
+//!
+//! The built-in effects are registered in [`Registry::default`], and other crates can add their own by implementing
+//! [`Effect`] and registering a factory for it, along with the parameters it takes.
+
+use crate::{Alienware, Colour, FrameScheduler, MonotonicClock, Zone, ZoneWriter};
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+mod registry;
+
+pub use registry::{
+    EffectError, EffectFactory, EffectSpec, ParamKind, ParamSpec, ParamValue, Params, Registry,
+};
+
+/// The colour of each zone at a point in an effect
+pub type Frame = HashMap<Zone, Colour>;
+
+/// The brightest level of a colour component that the named colours use
+pub const MAX_LEVEL: u8 = 15;
+
+/// An animation of the LED zones
+pub trait Effect: Send {
+    /// The colour of each of the zones at a time since the start of the effect, which only ever moves forwards
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame;
+}
+
+/// Show an effect on some zones at a number of frames a second until `running` is cleared, such as by a Ctrl-C handler
+pub fn run(
+    effect: &mut dyn Effect,
+    aw: &Alienware,
+    zones: &[Zone],
+    fps: u32,
+    running: &AtomicBool,
+) -> std::io::Result<()> {
+    let clock = MonotonicClock::new();
+    run_with(
+        effect,
+        aw,
+        zones,
+        &mut FrameScheduler::new(&clock, fps),
+        running,
+        None,
+    )
+}
+
+/// Show an effect on the frames of a scheduler until `running` is cleared or, if there is one, the effect has run for
+/// its length, in which case the frame at the end is shown last.  The frames are given the time they were due rather
+/// than the time they were shown, so that an effect run against a [`ManualClock`](crate::ManualClock) is repeatable.
+pub fn run_with(
+    effect: &mut dyn Effect,
+    aw: &Alienware,
+    zones: &[Zone],
+    scheduler: &mut FrameScheduler<'_>,
+    running: &AtomicBool,
+    length: Option<Duration>,
+) -> std::io::Result<()> {
+    run_locked(effect, aw, zones, scheduler, running, length, || Ok(()))
+}
+
+/// Show an effect as [`run_with`] does, calling `lock` before writing each frame and holding what it returns until
+/// the frame has been written, so that other writers can get in between the frames
+pub(crate) fn run_locked<G>(
+    effect: &mut dyn Effect,
+    aw: &Alienware,
+    zones: &[Zone],
+    scheduler: &mut FrameScheduler<'_>,
+    running: &AtomicBool,
+    length: Option<Duration>,
+    mut lock: impl FnMut() -> std::io::Result<G>,
+) -> std::io::Result<()> {
+    // the scheduler already limits how often the zones are written
+    let mut writer = ZoneWriter::new(aw);
+    while running.load(Ordering::SeqCst) {
+        let t = scheduler.next_frame().t;
+        let t = length.map_or(t, |length| t.min(length));
+        let frame = effect.frame(t, zones);
+        let _guard = lock()?;
+        for (zone, colour) in frame {
+            writer.set(zone, colour)?;
+        }
+        if Some(t) == length {
+            break;
+        }
+    }
+    let _guard = lock()?;
+    writer.flush()
+}
+
+/// Fade a colour smoothly up from black and back down again every period
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breathe {
+    pub colour: Colour,
+    pub period: Duration,
+}
+
+impl Effect for Breathe {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let colour = self
+            .colour
+            .scale((1.0 - (TAU * phase(t, self.period)).cos()) / 2.0);
+        every_zone(zones, |_| colour)
+    }
+}
+
+/// Flash a colour on and let it fade away every period, like a heartbeat
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pulse {
+    pub colour: Colour,
+    pub period: Duration,
+}
+
+impl Effect for Pulse {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let phase = phase(t, self.period);
+        let level = if phase < PULSE_ATTACK {
+            phase / PULSE_ATTACK
+        } else {
+            let decay = 1.0 - (phase - PULSE_ATTACK) / (1.0 - PULSE_ATTACK);
+            decay * decay
+        };
+        let colour = self.colour.scale(level);
+        every_zone(zones, |_| colour)
+    }
+}
+
+/// Flash a colour on and off every period, lit for the `duty` fraction of it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strobe {
+    pub colour: Colour,
+    pub period: Duration,
+    pub duty: f32,
+}
+
+impl Effect for Strobe {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let colour = if phase(t, self.period) < self.duty {
+            self.colour
+        } else {
+            Colour::default()
+        };
+        every_zone(zones, |_| colour)
+    }
+}
+
+/// Move the zones around the colour wheel once every period, each zone offset from the last
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rainbow {
+    pub period: Duration,
+    pub brightness: u8,
+}
+
+impl Effect for Rainbow {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let phase = phase(t, self.period);
+        every_zone(zones, |index| {
+            hue(phase + index as f32 / zones.len() as f32, self.brightness)
+        })
+    }
+}
+
+/// Fade from each colour of a palette to the next, taking a period for each
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cycle {
+    pub palette: Vec<Colour>,
+    pub period: Duration,
+}
+
+impl Effect for Cycle {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let colour = if self.palette.is_empty() {
+            Colour::default()
+        } else {
+            let position = ticks(t, self.period);
+            let current = position.floor() as usize % self.palette.len();
+            let next = (current + 1) % self.palette.len();
+            self.palette[current].mix(self.palette[next], position.fract() as f32)
+        };
+        every_zone(zones, |_| colour)
+    }
+}
+
+/// Flicker a colour like a candle flame, with each zone drifting to a new random brightness every period
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candle {
+    pub colour: Colour,
+    pub period: Duration,
+    pub seed: u64,
+}
+
+impl Effect for Candle {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let position = ticks(t, self.period);
+        let tick = position.floor() as u64;
+        // ease between the random levels so the flame drifts rather than jumps
+        let t = position.fract() as f32;
+        let t = t * t * (3.0 - 2.0 * t);
+        every_zone(zones, |index| {
+            let from = noise(self.seed, tick, index);
+            let to = noise(self.seed, tick + 1, index);
+            self.colour
+                .scale(CANDLE_MIN + (1.0 - CANDLE_MIN) * (from + (to - from) * t))
+        })
+    }
+}
+
+/// The fraction of a pulse spent getting brighter
+const PULSE_ATTACK: f32 = 0.1;
+
+/// The dimmest that a candle flickers down to, as a fraction of its colour
+const CANDLE_MIN: f32 = 0.4;
+
+/// A frame with the colour of each zone given by its index
+fn every_zone(zones: &[Zone], colour: impl Fn(usize) -> Colour) -> Frame {
+    zones
+        .iter()
+        .enumerate()
+        .map(|(index, zone)| (*zone, colour(index)))
+        .collect()
+}
+
+/// How many periods of an effect have passed
+fn ticks(t: Duration, period: Duration) -> f64 {
+    t.as_secs_f64() / period.as_secs_f64().max(f64::EPSILON)
+}
+
+/// How far through the current period an effect is, from 0 up to 1
+fn phase(t: Duration, period: Duration) -> f32 {
+    if period.is_zero() {
+        0.0
+    } else {
+        ticks(t, period).fract() as f32
+    }
+}
+
+/// The fully saturated colour at a point on the colour wheel, where 0 and 1 are red
+fn hue(hue: f32, brightness: u8) -> Colour {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let rising = sector.fract();
+    let falling = 1.0 - rising;
+    let (red, green, blue) = match sector as u32 {
+        0 => (1.0, rising, 0.0),
+        1 => (falling, 1.0, 0.0),
+        2 => (0.0, 1.0, rising),
+        3 => (0.0, falling, 1.0),
+        4 => (rising, 0.0, 1.0),
+        _ => (1.0, 0.0, falling),
+    };
+    let level = |component: f32| (component * f32::from(brightness)).round() as u8;
+    Colour::new(level(red), level(green), level(blue))
+}
+
+/// A repeatable random number from 0 up to 1 for a zone at a tick, using the splitmix64 mixer
+fn noise(seed: u64, tick: u64, index: usize) -> f32 {
+    let mut x = seed
+        ^ tick.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (index as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::setup_aw;
+    use crate::{Clock, ManualClock};
+    use std::thread;
+
+    const ZONES: [Zone; 3] = [Zone::Head, Zone::Left, Zone::Right];
+    const RED: Colour = Colour::new(15, 0, 0);
+
+    fn head(effect: &mut dyn Effect, millis: u64) -> Colour {
+        effect.frame(Duration::from_millis(millis), &ZONES)[&Zone::Head]
+    }
+
+    #[test]
+    fn breathe_and_pulse() {
+        let mut breathe = Breathe {
+            colour: RED,
+            period: Duration::from_secs(2),
+        };
+        assert_eq!(head(&mut breathe, 0), Colour::default());
+        assert_eq!(head(&mut breathe, 1000), RED);
+        assert_eq!(head(&mut breathe, 2000), Colour::default());
+        let mut pulse = Pulse {
+            colour: RED,
+            period: Duration::from_secs(1),
+        };
+        assert_eq!(head(&mut pulse, 100), RED);
+        assert!(head(&mut pulse, 500).red < 15);
+        assert_eq!(head(&mut pulse, 999), Colour::default());
+    }
+
+    #[test]
+    fn strobe() {
+        let mut strobe = Strobe {
+            colour: RED,
+            period: Duration::from_millis(100),
+            duty: 0.2,
+        };
+        assert_eq!(head(&mut strobe, 10), RED);
+        assert_eq!(head(&mut strobe, 50), Colour::default());
+        assert_eq!(head(&mut strobe, 110), RED);
+    }
+
+    #[test]
+    fn rainbow() {
+        let mut rainbow = Rainbow {
+            period: Duration::from_secs(3),
+            brightness: MAX_LEVEL,
+        };
+        let frame = rainbow.frame(Duration::ZERO, &ZONES);
+        assert_eq!(frame[&Zone::Head], Colour::new(15, 0, 0));
+        assert_eq!(frame[&Zone::Left], Colour::new(0, 15, 0));
+        assert_eq!(frame[&Zone::Right], Colour::new(0, 0, 15));
+        assert_eq!(head(&mut rainbow, 1000), Colour::new(0, 15, 0));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut cycle = Cycle {
+            palette: vec![RED, Colour::new(0, 0, 15)],
+            period: Duration::from_secs(1),
+        };
+        assert_eq!(head(&mut cycle, 0), RED);
+        assert_eq!(head(&mut cycle, 500), Colour::new(8, 0, 8));
+        assert_eq!(head(&mut cycle, 1000), Colour::new(0, 0, 15));
+        assert_eq!(head(&mut cycle, 2000), RED);
+        let mut empty = Cycle {
+            palette: vec![],
+            period: Duration::from_secs(1),
+        };
+        assert_eq!(head(&mut empty, 0), Colour::default());
+    }
+
+    #[test]
+    fn candle() {
+        let mut candle = Candle {
+            colour: Colour::new(15, 6, 0),
+            period: Duration::from_millis(100),
+            seed: 7,
+        };
+        let mut levels = Vec::new();
+        for millis in (0..2000).step_by(50) {
+            let colour = head(&mut candle, millis);
+            assert!((6..=15).contains(&colour.red), "{colour}");
+            assert_eq!(colour, head(&mut candle, millis));
+            levels.push(colour.red);
+        }
+        levels.dedup();
+        assert!(levels.len() > 1);
+    }
+
+    #[test]
+    fn run_until_stopped() {
+        let platform = setup_aw("run_effect");
+        let aw = Alienware::test(platform.clone());
+        let running = AtomicBool::new(true);
+        let mut effect = Breathe {
+            colour: RED,
+            period: Duration::from_millis(200),
+        };
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(150));
+                running.store(false, Ordering::SeqCst);
+            });
+            run(&mut effect, &aw, &[Zone::Head], 50, &running).unwrap();
+        });
+        // the zone was written in the hex format rather than left as the kernel format from the setup
+        let zone00 = std::fs::read_to_string(format!("{platform}/rgb_zones/zone00")).unwrap();
+        assert_eq!(zone00.len(), 6);
+    }
+
+    /// Shows an effect while keeping the times of the frames, and takes a long time over the first one
+    struct Slow<'a> {
+        effect: Breathe,
+        clock: &'a ManualClock,
+        times: Vec<Duration>,
+    }
+
+    impl Effect for Slow<'_> {
+        fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+            if self.times.is_empty() {
+                self.clock.advance(Duration::from_millis(250));
+            }
+            self.times.push(t);
+            self.effect.frame(t, zones)
+        }
+    }
+
+    #[test]
+    fn run_frame_by_frame() {
+        let breathe = Breathe {
+            colour: RED,
+            period: Duration::from_millis(400),
+        };
+        let head = |aw: &Alienware| aw.get_rgb_zones().unwrap().zones[&Zone::Head].colour();
+        for (millis, expected) in [
+            (0, Colour::default()),
+            (200, RED),
+            (300, Colour::new(8, 0, 0)),
+        ] {
+            let aw = Alienware::in_memory();
+            let clock = ManualClock::new();
+            let mut scheduler = FrameScheduler::new(&clock, 10);
+            let length = Duration::from_millis(millis);
+            let mut effect = breathe.clone();
+            run_with(
+                &mut effect,
+                &aw,
+                &ZONES,
+                &mut scheduler,
+                &AtomicBool::new(true),
+                Some(length),
+            )
+            .unwrap();
+            assert_eq!(head(&aw), expected, "{millis}ms");
+            assert_eq!(clock.now(), length);
+        }
+
+        // a slow frame drops the frame after it rather than falling behind
+        let aw = Alienware::in_memory();
+        let clock = ManualClock::new();
+        let mut scheduler = FrameScheduler::new(&clock, 10);
+        let mut slow = Slow {
+            effect: breathe,
+            clock: &clock,
+            times: Vec::new(),
+        };
+        let length = Some(Duration::from_millis(400));
+        run_with(
+            &mut slow,
+            &aw,
+            &[Zone::Head],
+            &mut scheduler,
+            &AtomicBool::new(true),
+            length,
+        )
+        .unwrap();
+        let millis: Vec<u128> = slow.times.iter().map(Duration::as_millis).collect();
+        assert_eq!(millis, [0, 200, 300, 400]);
+        assert_eq!(scheduler.dropped(), 1);
+        assert_eq!(head(&aw), Colour::default());
+    }
+}
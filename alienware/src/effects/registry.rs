@@ -0,0 +1,637 @@
+use super::{Breathe, Candle, Cycle, Effect, Pulse, Rainbow, Strobe, MAX_LEVEL};
+use crate::{parse_duration, Colour};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+/// The type of value that an effect parameter takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParamKind {
+    Colour,
+    /// A list of colours
+    Palette,
+    /// A length of time that is more than zero
+    Duration,
+    /// A number from 0 to 1
+    Fraction,
+    /// A whole number from 0 up to a maximum
+    Integer {
+        max: u64,
+    },
+}
+
+impl ParamKind {
+    /// Parse a value of this kind, where a palette is a comma separated list of colours and a duration is written
+    /// like `500ms` or `2s`, without checking that it is in range
+    pub fn parse(&self, input: &str) -> Option<ParamValue> {
+        match self {
+            ParamKind::Colour => input.parse().ok().map(ParamValue::Colour),
+            ParamKind::Palette => input
+                .split(',')
+                .map(|colour| colour.parse().ok())
+                .collect::<Option<Vec<Colour>>>()
+                .map(ParamValue::Palette),
+            ParamKind::Duration => parse_duration(input).map(ParamValue::Duration),
+            ParamKind::Fraction => input.parse::<f32>().ok().map(ParamValue::Fraction),
+            ParamKind::Integer { .. } => input.parse::<u64>().ok().map(ParamValue::Integer),
+        }
+    }
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamKind::Colour => write!(f, "colour"),
+            ParamKind::Palette => write!(f, "list of colours"),
+            ParamKind::Duration => write!(f, "duration longer than zero"),
+            ParamKind::Fraction => write!(f, "fraction from 0 to 1"),
+            ParamKind::Integer { max } => write!(f, "number up to {max}"),
+        }
+    }
+}
+
+/// The value of an effect parameter
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    Colour(Colour),
+    Palette(Vec<Colour>),
+    Duration(Duration),
+    Fraction(f32),
+    Integer(u64),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Colour(colour) => write!(f, "{colour}"),
+            ParamValue::Palette(palette) => {
+                let colours: Vec<String> =
+                    palette.iter().map(|colour| colour.to_string()).collect();
+                write!(f, "{}", colours.join(","))
+            }
+            ParamValue::Duration(duration) if duration.subsec_nanos() == 0 => {
+                write!(f, "{}s", duration.as_secs())
+            }
+            ParamValue::Duration(duration) => write!(f, "{}ms", duration.as_millis()),
+            ParamValue::Fraction(fraction) => write!(f, "{fraction}"),
+            ParamValue::Integer(integer) => write!(f, "{integer}"),
+        }
+    }
+}
+
+/// A parameter that an effect takes
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ParamKind,
+    pub default: ParamValue,
+}
+
+impl ParamSpec {
+    /// Construct a parameter, with the value it takes if it is not given
+    pub fn new(
+        name: &'static str,
+        kind: ParamKind,
+        default: ParamValue,
+        description: &'static str,
+    ) -> ParamSpec {
+        ParamSpec {
+            name,
+            description,
+            kind,
+            default,
+        }
+    }
+
+    /// Check that a value is of the right type and in range for this parameter
+    pub fn check(&self, value: &ParamValue) -> Result<(), EffectError> {
+        let valid = match (self.kind, value) {
+            (ParamKind::Colour, ParamValue::Colour(_)) => true,
+            (ParamKind::Palette, ParamValue::Palette(palette)) => !palette.is_empty(),
+            (ParamKind::Duration, ParamValue::Duration(duration)) => !duration.is_zero(),
+            (ParamKind::Fraction, ParamValue::Fraction(fraction)) => (0.0..=1.0).contains(fraction),
+            (ParamKind::Integer { max }, ParamValue::Integer(integer)) => *integer <= max,
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(EffectError::InvalidValue {
+                param: self.name.to_string(),
+                kind: self.kind,
+            })
+        }
+    }
+}
+
+/// The values of the parameters of an effect, with the default for any that were not given
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    values: HashMap<&'static str, ParamValue>,
+}
+
+impl Params {
+    /// The value of a parameter
+    pub fn get(&self, name: &str) -> Option<&ParamValue> {
+        self.values.get(name)
+    }
+
+    /// The value of a colour parameter, or black if there is none
+    pub fn colour(&self, name: &str) -> Colour {
+        match self.get(name) {
+            Some(ParamValue::Colour(colour)) => *colour,
+            _ => Colour::default(),
+        }
+    }
+
+    /// The value of a palette parameter, or an empty palette if there is none
+    pub fn palette(&self, name: &str) -> Vec<Colour> {
+        match self.get(name) {
+            Some(ParamValue::Palette(palette)) => palette.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The value of a duration parameter, or zero if there is none
+    pub fn duration(&self, name: &str) -> Duration {
+        match self.get(name) {
+            Some(ParamValue::Duration(duration)) => *duration,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The value of a fraction parameter, or zero if there is none
+    pub fn fraction(&self, name: &str) -> f32 {
+        match self.get(name) {
+            Some(ParamValue::Fraction(fraction)) => *fraction,
+            _ => 0.0,
+        }
+    }
+
+    /// The value of an integer parameter, or zero if there is none
+    pub fn integer(&self, name: &str) -> u64 {
+        match self.get(name) {
+            Some(ParamValue::Integer(integer)) => *integer,
+            _ => 0,
+        }
+    }
+}
+
+/// Builds an effect from the values of its parameters, which have already been checked against its [`ParamSpec`]s
+pub type EffectFactory = Box<dyn Fn(&Params) -> Box<dyn Effect> + Send + Sync>;
+
+/// A named effect in a [`Registry`]
+pub struct EffectSpec {
+    pub name: String,
+    pub description: String,
+    pub params: Vec<ParamSpec>,
+    factory: EffectFactory,
+}
+
+impl EffectSpec {
+    /// The parameter with a name
+    pub fn param(&self, name: &str) -> Option<&ParamSpec> {
+        self.params.iter().find(|param| param.name == name)
+    }
+
+    /// Parse the values of some of its parameters, written as `name=value` with `color` taken as `colour`, checking
+    /// that each one is in range
+    pub fn parse_params<S: AsRef<str>>(
+        &self,
+        params: &[S],
+    ) -> Result<Vec<(String, ParamValue)>, EffectError> {
+        let mut values = Vec::new();
+        for param in params {
+            let param = param.as_ref();
+            let (name, value) = param
+                .split_once('=')
+                .ok_or(EffectError::NotAParam(param.to_string()))?;
+            let name = match name.trim().to_lowercase().as_str() {
+                "color" => "colour".to_string(),
+                name => name.to_string(),
+            };
+            let spec = self.param(name.as_str()).ok_or(EffectError::UnknownParam {
+                effect: self.name.clone(),
+                param: name.clone(),
+            })?;
+            let value = spec
+                .kind
+                .parse(value.trim())
+                .ok_or(EffectError::InvalidValue {
+                    param: name.clone(),
+                    kind: spec.kind,
+                })?;
+            spec.check(&value)?;
+            values.push((name, value));
+        }
+        Ok(values)
+    }
+
+    /// Build the effect from the values of some of its parameters, using the defaults for the rest
+    pub fn build(&self, values: Vec<(String, ParamValue)>) -> Result<Box<dyn Effect>, EffectError> {
+        let mut params = Params::default();
+        for param in self.params.iter() {
+            params.values.insert(param.name, param.default.clone());
+        }
+        for (name, value) in values {
+            let param = self.param(&name).ok_or(EffectError::UnknownParam {
+                effect: self.name.clone(),
+                param: name.clone(),
+            })?;
+            param.check(&value)?;
+            params.values.insert(param.name, value);
+        }
+        Ok((self.factory)(&params))
+    }
+}
+
+/// Reasons why an effect could not be built
+#[derive(Clone, Debug, PartialEq)]
+pub enum EffectError {
+    UnknownEffect(String),
+    /// A parameter that is not written as `name=value`
+    NotAParam(String),
+    UnknownParam {
+        effect: String,
+        param: String,
+    },
+    InvalidValue {
+        param: String,
+        kind: ParamKind,
+    },
+}
+
+impl fmt::Display for EffectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EffectError::UnknownEffect(name) => write!(f, "'{name}' is not an effect"),
+            EffectError::NotAParam(param) => {
+                write!(f, "'{param}' is not a parameter, e.g. period=2s")
+            }
+            EffectError::UnknownParam { effect, param } => {
+                write!(f, "{effect} does not take a '{param}' parameter")
+            }
+            EffectError::InvalidValue { param, kind } => {
+                write!(f, "{param} must be a {kind}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EffectError {}
+
+/// The effects that can be built by name.  [`Registry::default`] holds the built-in effects, and crates with effects
+/// of their own can provide a function that registers them, for applications to call.
+pub struct Registry {
+    effects: BTreeMap<String, EffectSpec>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        registry.register_builtins();
+        registry
+    }
+}
+
+impl Registry {
+    /// Construct a registry with no effects in it
+    pub fn empty() -> Registry {
+        Registry {
+            effects: BTreeMap::new(),
+        }
+    }
+
+    /// Add an effect, replacing any effect with the same name
+    pub fn register<F>(&mut self, name: &str, description: &str, params: Vec<ParamSpec>, factory: F)
+    where
+        F: Fn(&Params) -> Box<dyn Effect> + Send + Sync + 'static,
+    {
+        let name = name.to_lowercase();
+        self.effects.insert(
+            name.clone(),
+            EffectSpec {
+                name,
+                description: description.to_string(),
+                params,
+                factory: Box::new(factory),
+            },
+        );
+    }
+
+    /// The effect with a name
+    pub fn get(&self, name: &str) -> Option<&EffectSpec> {
+        self.effects.get(name.to_lowercase().as_str())
+    }
+
+    /// Every effect, in order of name
+    pub fn effects(&self) -> impl Iterator<Item = &EffectSpec> {
+        self.effects.values()
+    }
+
+    /// Build an effect by name from the values of some of its parameters, using the defaults for the rest
+    pub fn build(
+        &self,
+        name: &str,
+        values: Vec<(String, ParamValue)>,
+    ) -> Result<Box<dyn Effect>, EffectError> {
+        self.get(name)
+            .ok_or(EffectError::UnknownEffect(name.to_string()))?
+            .build(values)
+    }
+
+    /// Add the built-in effects
+    pub fn register_builtins(&mut self) {
+        let blue = ParamValue::Colour(Colour::new(0, 0, MAX_LEVEL));
+        let colour = |default: ParamValue| {
+            ParamSpec::new("colour", ParamKind::Colour, default, "the colour to show")
+        };
+        let period = |default: Duration, description: &'static str| {
+            ParamSpec::new(
+                "period",
+                ParamKind::Duration,
+                ParamValue::Duration(default),
+                description,
+            )
+        };
+        self.register(
+            "breathe",
+            "Fade a colour smoothly up from black and back down again",
+            vec![
+                colour(blue.clone()),
+                period(Duration::from_secs(4), "how long each breath takes"),
+            ],
+            |params| {
+                Box::new(Breathe {
+                    colour: params.colour("colour"),
+                    period: params.duration("period"),
+                })
+            },
+        );
+        self.register(
+            "pulse",
+            "Flash a colour on and let it fade away, like a heartbeat",
+            vec![
+                colour(blue),
+                period(Duration::from_secs(1), "the time between pulses"),
+            ],
+            |params| {
+                Box::new(Pulse {
+                    colour: params.colour("colour"),
+                    period: params.duration("period"),
+                })
+            },
+        );
+        self.register(
+            "strobe",
+            "Flash a colour on and off",
+            vec![
+                colour(ParamValue::Colour(Colour::new(
+                    MAX_LEVEL, MAX_LEVEL, MAX_LEVEL,
+                ))),
+                period(Duration::from_millis(200), "the time between flashes"),
+                ParamSpec::new(
+                    "duty",
+                    ParamKind::Fraction,
+                    ParamValue::Fraction(0.5),
+                    "the fraction of the time that the colour is on",
+                ),
+            ],
+            |params| {
+                Box::new(Strobe {
+                    colour: params.colour("colour"),
+                    period: params.duration("period"),
+                    duty: params.fraction("duty"),
+                })
+            },
+        );
+        self.register(
+            "rainbow",
+            "Move the zones around the colour wheel, each zone offset from the last",
+            vec![
+                period(
+                    Duration::from_secs(6),
+                    "how long each turn of the wheel takes",
+                ),
+                ParamSpec::new(
+                    "brightness",
+                    ParamKind::Integer {
+                        max: u64::from(MAX_LEVEL),
+                    },
+                    ParamValue::Integer(u64::from(MAX_LEVEL)),
+                    "the brightest level of each colour component",
+                ),
+            ],
+            |params| {
+                Box::new(Rainbow {
+                    period: params.duration("period"),
+                    brightness: params.integer("brightness").min(u64::from(MAX_LEVEL)) as u8,
+                })
+            },
+        );
+        self.register(
+            "cycle",
+            "Fade from each colour of a palette to the next",
+            vec![
+                ParamSpec::new(
+                    "palette",
+                    ParamKind::Palette,
+                    ParamValue::Palette(vec![
+                        Colour::new(MAX_LEVEL, 0, 0),
+                        Colour::new(MAX_LEVEL, MAX_LEVEL, 0),
+                        Colour::new(0, MAX_LEVEL, 0),
+                        Colour::new(0, MAX_LEVEL, MAX_LEVEL),
+                        Colour::new(0, 0, MAX_LEVEL),
+                        Colour::new(MAX_LEVEL, 0, MAX_LEVEL),
+                    ]),
+                    "the colours to go through",
+                ),
+                period(Duration::from_secs(1), "how long each colour takes"),
+            ],
+            |params| {
+                Box::new(Cycle {
+                    palette: params.palette("palette"),
+                    period: params.duration("period"),
+                })
+            },
+        );
+        self.register(
+            "candle",
+            "Flicker a colour like a candle flame",
+            vec![
+                colour(ParamValue::Colour(Colour::new(MAX_LEVEL, 6, 0))),
+                period(Duration::from_millis(120), "how often the flame changes"),
+                ParamSpec::new(
+                    "seed",
+                    ParamKind::Integer { max: u64::MAX },
+                    ParamValue::Integer(0),
+                    "the seed of the random flicker",
+                ),
+            ],
+            |params| {
+                Box::new(Candle {
+                    colour: params.colour("colour"),
+                    period: params.duration("period"),
+                    seed: params.integer("seed"),
+                })
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::Frame;
+    use crate::Zone;
+
+    /// An effect from another crate, which shows a colour on every other frame
+    struct Blink {
+        colour: Colour,
+        frames: u64,
+    }
+
+    impl Effect for Blink {
+        fn frame(&mut self, _t: Duration, zones: &[Zone]) -> Frame {
+            self.frames += 1;
+            let colour = if self.frames % 2 == 1 {
+                self.colour
+            } else {
+                Colour::default()
+            };
+            zones.iter().map(|zone| (*zone, colour)).collect()
+        }
+    }
+
+    #[test]
+    fn builtins() {
+        let registry = Registry::default();
+        let names: Vec<&str> = registry.effects().map(|spec| spec.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["breathe", "candle", "cycle", "pulse", "rainbow", "strobe"]
+        );
+        let mut effect = registry
+            .build(
+                "Strobe",
+                vec![("duty".to_string(), ParamValue::Fraction(1.0))],
+            )
+            .unwrap();
+        let frame = effect.frame(Duration::from_millis(150), &[Zone::Head]);
+        assert_eq!(frame[&Zone::Head], Colour::new(15, 15, 15));
+    }
+
+    #[test]
+    fn register() {
+        let mut registry = Registry::empty();
+        registry.register(
+            "blink",
+            "Blink a colour",
+            vec![ParamSpec::new(
+                "colour",
+                ParamKind::Colour,
+                ParamValue::Colour(Colour::new(15, 0, 0)),
+                "the colour to blink",
+            )],
+            |params| {
+                Box::new(Blink {
+                    colour: params.colour("colour"),
+                    frames: 0,
+                })
+            },
+        );
+        let mut blink = registry.build("blink", vec![]).unwrap();
+        assert_eq!(
+            blink.frame(Duration::ZERO, &[Zone::Left])[&Zone::Left],
+            Colour::new(15, 0, 0)
+        );
+        assert_eq!(
+            blink.frame(Duration::ZERO, &[Zone::Left])[&Zone::Left],
+            Colour::default()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let registry = Registry::default();
+        assert_eq!(
+            registry.build("sparkle", vec![]).err(),
+            Some(EffectError::UnknownEffect("sparkle".to_string()))
+        );
+        assert_eq!(
+            registry
+                .build(
+                    "rainbow",
+                    vec![("colour".to_string(), ParamValue::Colour(Colour::default()))]
+                )
+                .err(),
+            Some(EffectError::UnknownParam {
+                effect: "rainbow".to_string(),
+                param: "colour".to_string()
+            })
+        );
+        for (param, value) in [
+            ("period", ParamValue::Duration(Duration::ZERO)),
+            ("period", ParamValue::Integer(2)),
+            ("duty", ParamValue::Fraction(1.5)),
+        ] {
+            assert!(registry
+                .build("strobe", vec![(param.to_string(), value)])
+                .is_err());
+        }
+        assert!(registry
+            .build(
+                "rainbow",
+                vec![("brightness".to_string(), ParamValue::Integer(256))]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn parse_params() {
+        let registry = Registry::default();
+        let breathe = registry.get("Breathe").unwrap();
+        assert_eq!(
+            breathe.parse_params(&["color=red", "period = 2s"]),
+            Ok(vec![
+                (
+                    "colour".to_string(),
+                    ParamValue::Colour(Colour::new(15, 0, 0))
+                ),
+                (
+                    "period".to_string(),
+                    ParamValue::Duration(Duration::from_secs(2))
+                ),
+            ])
+        );
+        assert_eq!(
+            registry
+                .get("cycle")
+                .unwrap()
+                .parse_params(&["palette=red,#0000ff"]),
+            Ok(vec![(
+                "palette".to_string(),
+                ParamValue::Palette(vec![Colour::new(15, 0, 0), Colour::new(0, 0, 15)])
+            )])
+        );
+        let strobe = registry.get("strobe").unwrap();
+        assert_eq!(
+            strobe.parse_params(&["2s"]),
+            Err(EffectError::NotAParam("2s".to_string()))
+        );
+        assert_eq!(
+            strobe.parse_params(&["duty=half"]),
+            Err(EffectError::InvalidValue {
+                param: "duty".to_string(),
+                kind: ParamKind::Fraction
+            })
+        );
+        for params in [["duty=2"], ["period=0s"], ["sparkle=1"]] {
+            assert!(strobe.parse_params(&params).is_err(), "{params:?}");
+        }
+        let rainbow = registry.get("rainbow").unwrap();
+        assert!(rainbow.parse_params(&["brightness=15"]).is_ok());
+        assert!(rainbow.parse_params(&["brightness=16"]).is_err());
+    }
+}
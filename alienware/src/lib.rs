@@ -1,10 +1,47 @@
-use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(feature = "archive")]
+mod archive;
+mod brightness;
+mod calibration;
+mod clock;
+mod compositor;
+#[cfg(feature = "document")]
+pub mod document;
+pub mod effects;
+mod memory;
+pub mod parse;
+mod reconcile;
+mod shared;
+#[cfg(feature = "show")]
+pub mod show;
+mod state;
+mod transition;
+mod writer;
+
+#[cfg(feature = "archive")]
+pub use archive::dump;
+
+pub use brightness::{Brightness, BrightnessWarnings, DEFAULT_BRIGHTNESS_FILE, MAX_BRIGHTNESS};
+pub use calibration::Calibration;
+pub use clock::{parse_duration, Clock, FrameScheduler, ManualClock, MonotonicClock, Tick};
+pub use compositor::{BlendMode, Compositor, Layer};
+pub use parse::ParseError;
+pub use reconcile::{Drift, ReconcileEvent, Reconciler};
+pub use shared::{AlienwareGuard, SharedAlienware, DEFAULT_LOCK_FILE};
+pub use state::{Change, Colour, State, StateError};
+pub use transition::{ColourSpace, Easing, Transition};
+pub use writer::ZoneWriter;
+
+use brightness::BrightnessStore;
+use calibration::Calibrations;
 
 /// The possible sources of the HDMI output port
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -78,6 +115,20 @@ impl fmt::Display for Zone {
     }
 }
 
+impl FromStr for Zone {
+    type Err = ParseError;
+
+    /// Parse the name of a zone, ignoring case
+    fn from_str(input: &str) -> Result<Zone, ParseError> {
+        match input.trim().to_lowercase().as_str() {
+            "head" => Ok(Zone::Head),
+            "left" => Ok(Zone::Left),
+            "right" => Ok(Zone::Right),
+            _ => Err(ParseError::UnknownOption(input.to_string())),
+        }
+    }
+}
+
 /// State of the HDMI ports
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct HDMI {
@@ -105,6 +156,14 @@ pub struct RGBZone {
     pub blue: u8,
 }
 
+impl RGBZone {
+    /// The colour this LED is lit with, which is the colour it was set to scaled by its brightness and then calibrated.
+    /// [`Alienware::zone_colour`] gives the colour it was set to.
+    pub fn colour(&self) -> Colour {
+        Colour::new(self.red, self.green, self.blue)
+    }
+}
+
 /// Setup of all of the LEDs
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct RGBZones {
@@ -112,9 +171,50 @@ pub struct RGBZones {
     pub exists: bool,
 }
 
+/// A write to a sysfs file that was reported instead of being carried out
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedWrite {
+    pub path: PathBuf,
+    pub old: Option<String>,
+    pub new: String,
+}
+
+impl fmt::Display for PlannedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.old {
+            Some(old) => write!(f, "{}: {old} -> {}", self.path.display(), self.new),
+            None => write!(f, "{}: ? -> {}", self.path.display(), self.new),
+        }
+    }
+}
+
+/// Receives the writes that would have been made by an [`Alienware`] in dry run mode
+pub type DryRunReporter = Box<dyn Fn(&PlannedWrite) + Send + Sync>;
+
+/// The size of the buffer that sysfs files are read into, which is larger than any of the alienware attributes
+const READ_BUFFER_SIZE: usize = 128;
+
+/// Where the sysfs files are read from and written to
+enum Backend {
+    /// The files under the platform directory
+    Sysfs,
+    /// A copy of the files held in memory, keyed by their path relative to the platform directory, such as one loaded
+    /// from an archive made by [`dump`]
+    Memory {
+        files: Mutex<BTreeMap<String, String>>,
+        dirs: BTreeSet<String>,
+        /// Whether writes are taken in the way that the kernel module would take them, rather than refused
+        writable: bool,
+    },
+}
+
 /// Access to the settings for a Alienware server
 pub struct Alienware {
     platform: String,
+    backend: Backend,
+    dry_run: Option<DryRunReporter>,
+    brightness: BrightnessStore,
+    calibrations: Calibrations,
 }
 
 impl Default for Alienware {
@@ -123,23 +223,72 @@ impl Default for Alienware {
     }
 }
 
+impl Drop for Alienware {
+    /// Save the colours written since the brightness file was last saved
+    fn drop(&mut self) {
+        self.save_brightness_or_warn();
+    }
+}
+
 impl Alienware {
     /// Construct a new instance of Alienware
     pub fn new() -> Alienware {
         Alienware {
             platform: "/sys/devices/platform/alienware-wmi".to_string(),
+            backend: Backend::Sysfs,
+            dry_run: None,
+            brightness: BrightnessStore::default(),
+            calibrations: Calibrations::default(),
+        }
+    }
+
+    /// Switch to dry run mode, where settings are still read from the device but every write is passed to the
+    /// reporter instead of being made
+    pub fn dry_run<F>(mut self, reporter: F) -> Alienware
+    where
+        F: Fn(&PlannedWrite) + Send + Sync + 'static,
+    {
+        self.dry_run = Some(Box::new(reporter));
+        self
+    }
+
+    /// Checks whether writes are being reported rather than made
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.is_some()
+    }
+
+    /// Construct a new instance of Alienware that reads the sysfs files from another directory, such as a copy of the
+    /// platform directory of another machine
+    pub fn with_platform<P: Into<String>>(platform: P) -> Alienware {
+        Alienware {
+            platform: platform.into(),
+            backend: Backend::Sysfs,
+            dry_run: None,
+            brightness: BrightnessStore::default(),
+            calibrations: Calibrations::default(),
         }
     }
 
     /// Construct a new instance of Alienware used for testing that can change the root of the sysfs files
     #[allow(dead_code)]
     fn test(platform: String) -> Alienware {
-        Alienware { platform }
+        Alienware {
+            platform,
+            backend: Backend::Sysfs,
+            dry_run: None,
+            brightness: BrightnessStore::default(),
+            calibrations: Calibrations::default(),
+        }
+    }
+
+    /// The sysfs directory of the alienware platform device
+    pub fn platform(&self) -> &Path {
+        Path::new(&self.platform)
     }
 
     /// Check that this is an Alienware server (i.e. has the alienware platform settings in sysfs)
     pub fn is_alienware(&self) -> bool {
-        Path::new(&self.platform).exists()
+        self.sys_file_exists("")
     }
 
     /// Get the state of the HDMI ports
@@ -149,11 +298,7 @@ impl Alienware {
         let mut exists = false;
         if self.is_alienware() {
             exists = true;
-            let mut path_buf = PathBuf::new();
-            path_buf.push(&self.platform);
-            path_buf.push("hdmi");
-
-            if path_buf.exists() {
+            if self.sys_file_exists("hdmi") {
                 source = self.parse_source()?;
                 cable_state = self.parse_cable_state()?;
             }
@@ -165,50 +310,23 @@ impl Alienware {
         })
     }
 
-    /// Parse the state of the HDMI Output source
+    /// Parse the state of the HDMI Output source, where a source that cannot be recognised is unknown
     fn parse_source(&self) -> std::io::Result<HDMISource> {
-        match self.parse_sys_file("hdmi/source") {
-            Ok(Some(s)) => {
-                if s.eq("cable") {
-                    Ok(HDMISource::Cable)
-                } else if s.eq("gpu") {
-                    Ok(HDMISource::Gpu)
-                } else {
-                    Ok(HDMISource::Unknown)
-                }
-            }
-            Ok(None) => Ok(HDMISource::Unknown),
-            Err(x) => Err(x),
-        }
+        self.read_sys_file_with("hdmi/source", |contents| {
+            parse::parse_hdmi_source(contents).unwrap_or(HDMISource::Unknown)
+        })
     }
 
-    /// Parse the state of the HDMI input cable
+    /// Parse the state of the HDMI input cable, where a state that cannot be recognised is unknown
     fn parse_cable_state(&self) -> std::io::Result<HDMICableState> {
-        match self.parse_sys_file("hdmi/cable") {
-            Ok(Some(s)) => {
-                if s.eq("connected") {
-                    Ok(HDMICableState::Connected)
-                } else if s.eq("unconnected") {
-                    Ok(HDMICableState::Unconnected)
-                } else {
-                    Ok(HDMICableState::Unknown)
-                }
-            }
-            Ok(None) => Ok(HDMICableState::Unknown),
-            Err(x) => Err(x),
-        }
+        self.read_sys_file_with("hdmi/cable", |contents| {
+            parse::parse_cable_state(contents).unwrap_or(HDMICableState::Unknown)
+        })
     }
 
     /// Set the source for the HDMI Output port
-    pub fn set_hdmi_source(self, source: HDMISource) -> std::io::Result<()> {
-        self.write_sys_file(
-            "hdmi/source",
-            match source {
-                HDMISource::Cable => "cable",
-                HDMISource::Gpu => "gpu",
-                HDMISource::Unknown => "unknown",
-            },
-        )?;
+    pub fn set_hdmi_source(&self, source: HDMISource) -> std::io::Result<()> {
+        self.write_sys_file("hdmi/source", parse::format_hdmi_source(source))?;
         Ok(())
     }
 
@@ -218,30 +336,22 @@ impl Alienware {
         let mut exists = false;
         if self.is_alienware() {
             exists = true;
-            let mut path_buf = PathBuf::new();
-            path_buf.push(&self.platform);
-            path_buf.push("rgb_zones");
-            if path_buf.exists() {
-                path_buf.push("zone00");
-                if path_buf.exists() {
+            if self.sys_file_exists("rgb_zones") {
+                if self.sys_file_exists("rgb_zones/zone00") {
                     zones.insert(
                         Zone::Head,
                         self.parse_rgb_zone(Zone::Head, "rgb_zones/zone00")?,
                     );
                 }
 
-                path_buf.pop();
-                path_buf.push("zone01");
-                if path_buf.exists() {
+                if self.sys_file_exists("rgb_zones/zone01") {
                     zones.insert(
                         Zone::Left,
                         self.parse_rgb_zone(Zone::Left, "rgb_zones/zone01")?,
                     );
                 }
 
-                path_buf.pop();
-                path_buf.push("zone02");
-                if path_buf.exists() {
+                if self.sys_file_exists("rgb_zones/zone02") {
                     zones.insert(
                         Zone::Right,
                         self.parse_rgb_zone(Zone::Right, "rgb_zones/zone02")?,
@@ -252,35 +362,65 @@ impl Alienware {
         Ok(RGBZones { zones, exists })
     }
 
-    /// Set an LED colour
+    /// Set an LED colour, which is scaled by the brightness of the LED and calibrated before it is written.  The colour
+    /// is kept for the brightness file, which is saved when the brightness next changes, when a [`ZoneWriter`] is
+    /// flushed or when the `Alienware` is dropped, rather than on every write.
     pub fn set_rgb_zone(&self, zone: Zone, red: u8, green: u8, blue: u8) -> std::io::Result<()> {
-        let rgb = format!("{red:02x}{green:02x}{blue:02x}");
-        self.write_sys_file(
-            match zone {
-                Zone::Head => "rgb_zones/zone00",
-                Zone::Left => "rgb_zones/zone01",
-                Zone::Right => "rgb_zones/zone02",
-            },
-            rgb.as_str(),
-        )?;
+        let colour = Colour::new(red, green, blue);
+        let effective = self.output_colour(zone, colour)?;
+        self.write_sys_file(zone_file(zone), parse::format_rgb(effective).as_str())?;
+        self.record_colour(zone, colour, effective);
+        Ok(())
+    }
+
+    /// Set an LED colour through a handle to its sysfs file that is kept open between writes, opening it if there is
+    /// no handle yet.  Dry runs and replayed devices go through [`Alienware::set_rgb_zone`] instead.  The colour is
+    /// kept for the brightness file, which is written by [`Alienware::save_brightness`] rather than every time.
+    fn write_rgb_zone_with(
+        &self,
+        zone: Zone,
+        colour: Colour,
+        handle: &mut Option<File>,
+    ) -> std::io::Result<()> {
+        if self.dry_run.is_some() || !matches!(self.backend, Backend::Sysfs) {
+            return self.set_rgb_zone(zone, colour.red, colour.green, colour.blue);
+        }
+        let file = match handle {
+            Some(file) => file,
+            // truncating only matters for regular files, such as in tests, as every value written is the same length
+            None => handle.insert(
+                OpenOptions::new()
+                    .write(true)
+                    .truncate(true)
+                    .open(Path::new(&self.platform).join(zone_file(zone)))?,
+            ),
+        };
+        let effective = self.output_colour(zone, colour)?;
+        let result = file
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(parse::format_rgb(effective).as_bytes()));
+        if result.is_err() {
+            // reopen the file on the next write
+            *handle = None;
+        }
+        result?;
+        self.record_colour(zone, colour, effective);
         Ok(())
     }
 
     /// Parse the current colour of an LED
     fn parse_rgb_zone(&self, zone: Zone, file_name: &str) -> std::io::Result<RGBZone> {
-        match self.parse_sys_rgb_file(file_name) {
-            Ok((red, green, blue)) => Ok(RGBZone {
-                zone,
-                red,
-                green,
-                blue,
-            }),
-            Err(x) => Err(x),
-        }
+        let colour = self.read_sys_file_with(file_name, parse::parse_rgb)??;
+        Ok(RGBZone {
+            zone,
+            red: colour.red,
+            green: colour.green,
+            blue: colour.blue,
+        })
     }
 
     /// Checks whether the alienware HDMI setup is available
-    pub fn has_hdmi(self) -> bool {
+    pub fn has_hdmi(&self) -> bool {
         if let Ok(hdmi) = self.get_hdmi() {
             hdmi.exists
         } else {
@@ -288,8 +428,13 @@ impl Alienware {
         }
     }
 
+    /// Checks whether the HDMI output source can be read and set
+    fn has_hdmi_source(&self) -> bool {
+        self.sys_file_exists("hdmi/source")
+    }
+
     /// Checks whether the alienware LED setup is available
-    pub fn has_rgb_zones(self) -> bool {
+    pub fn has_rgb_zones(&self) -> bool {
         if let Ok(rgb_zones) = self.get_rgb_zones() {
             rgb_zones.exists
         } else {
@@ -297,45 +442,58 @@ impl Alienware {
         }
     }
 
-    /// Parses a single setting sysfs file
-    fn parse_sys_file(&self, file_name: &str) -> std::io::Result<Option<String>> {
-        static RE: OnceLock<Regex> = OnceLock::new();
-        let re = RE.get_or_init(|| Regex::new(r"\[([^)]+)\]").unwrap());
-        let mut path_buf = PathBuf::new();
-        path_buf.push(&self.platform);
-        path_buf.push(file_name);
-        let mut file = File::open(path_buf.as_path())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
-        let caps = re.captures(contents.as_str()).unwrap();
-        match caps.len() > 0 {
-            true => Ok(Some(caps[1].to_string())),
-            false => Ok(None),
+    /// Checks whether a sysfs file or directory exists, where an empty name is the platform directory itself
+    fn sys_file_exists(&self, file_name: &str) -> bool {
+        match &self.backend {
+            Backend::Sysfs => {
+                let mut path_buf = PathBuf::new();
+                path_buf.push(&self.platform);
+                path_buf.push(file_name);
+                path_buf.exists()
+            }
+            Backend::Memory { files, dirs, .. } => {
+                lock_files(files).contains_key(file_name) || dirs.contains(file_name)
+            }
         }
     }
 
-    /// Parses a sysfs file that holds an RGB setting
-    fn parse_sys_rgb_file(&self, file_name: &str) -> std::io::Result<(u8, u8, u8)> {
-        static RE: OnceLock<Regex> = OnceLock::new();
-        let re = RE.get_or_init(|| Regex::new(r"^red: (\d+), green: (\d+), blue: (\d+)").unwrap());
-        let mut path_buf = PathBuf::new();
-        path_buf.push(&self.platform);
-        path_buf.push(file_name);
-        let mut file = File::open(path_buf)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
-        match re.captures(contents.as_str()) {
-            Some(caps) if caps.len() == 4 => {
-                let red = &caps[1];
-                let green = &caps[2];
-                let blue = &caps[3];
-                Ok((
-                    red.parse::<u8>().unwrap(),
-                    green.parse::<u8>().unwrap(),
-                    blue.parse::<u8>().unwrap(),
-                ))
+    /// Read the contents of a sysfs file
+    fn read_sys_file(&self, file_name: &str) -> std::io::Result<String> {
+        self.read_sys_file_with(file_name, str::to_string)
+    }
+
+    /// Read the contents of a sysfs file and pass them to a parser.  The attribute files are small enough to be read
+    /// into a buffer on the stack, so that reading them does not allocate.
+    fn read_sys_file_with<T>(
+        &self,
+        file_name: &str,
+        parse: impl FnOnce(&str) -> T,
+    ) -> std::io::Result<T> {
+        match &self.backend {
+            Backend::Sysfs => {
+                let mut file = File::open(Path::new(&self.platform).join(file_name))?;
+                let mut buffer = [0u8; READ_BUFFER_SIZE];
+                let mut len = 0;
+                while len < buffer.len() {
+                    match file.read(&mut buffer[len..]) {
+                        Ok(0) => return Ok(parse(utf8(&buffer[..len])?)),
+                        Ok(read) => len += read,
+                        Err(x) if x.kind() == std::io::ErrorKind::Interrupted => {}
+                        Err(x) => return Err(x),
+                    }
+                }
+                // too big for the buffer, so read the rest of it onto the heap
+                let mut contents = buffer.to_vec();
+                file.read_to_end(&mut contents)?;
+                Ok(parse(utf8(&contents)?))
             }
-            _ => Ok((0u8, 0u8, 0u8)),
+            Backend::Memory { files, .. } => match lock_files(files).get(file_name) {
+                Some(contents) => Ok(parse(contents)),
+                None => Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    file_name.to_string(),
+                )),
+            },
         }
     }
 
@@ -344,18 +502,67 @@ impl Alienware {
         let mut path_buf = PathBuf::new();
         path_buf.push(&self.platform);
         path_buf.push(file_name);
+        if let Some(reporter) = &self.dry_run {
+            let old = self
+                .read_sys_file(file_name)
+                .ok()
+                .map(|old| old.trim().to_string());
+            reporter(&PlannedWrite {
+                path: path_buf,
+                old,
+                new: value.to_string(),
+            });
+            return Ok(());
+        }
+        if let Backend::Memory {
+            files, writable, ..
+        } = &self.backend
+        {
+            return if *writable {
+                memory::write(&mut lock_files(files), file_name, value)
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "the replayed device is read only",
+                ))
+            };
+        }
         let mut sys_file = File::create(path_buf)?;
         sys_file.write_all(value.as_bytes())?;
         Ok(())
     }
 }
 
+/// The sysfs file holding the colour of an LED zone, relative to the platform directory
+fn zone_file(zone: Zone) -> &'static str {
+    match zone {
+        Zone::Head => "rgb_zones/zone00",
+        Zone::Left => "rgb_zones/zone01",
+        Zone::Right => "rgb_zones/zone02",
+    }
+}
+
+/// Lock the files of a device held in memory.  Nothing is left half written by a panic while they are locked, so a
+/// poisoned lock can still be used.
+fn lock_files(files: &Mutex<BTreeMap<String, String>>) -> MutexGuard<'_, BTreeMap<String, String>> {
+    files
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Check that the contents of a sysfs file are text
+fn utf8(contents: &[u8]) -> std::io::Result<&str> {
+    std::str::from_utf8(contents)
+        .map_err(|x| std::io::Error::new(std::io::ErrorKind::InvalidData, x))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{HDMISource, Zone};
     use std::fs::{create_dir_all, metadata, remove_dir_all, File};
     use std::io::prelude::*;
     use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn is_alienware() {
@@ -461,9 +668,41 @@ mod tests {
         }
     }
 
-    const TEST_PATH: &str = "/tmp/alienware_wmi_test";
+    #[test]
+    fn dry_run() {
+        let platform = setup_aw("dry_run");
+        let planned = Arc::new(Mutex::new(Vec::new()));
+        let reported = planned.clone();
+        let alienware = crate::Alienware::test(platform.clone())
+            .dry_run(move |write| reported.lock().unwrap().push(write.clone()));
+        assert!(alienware.is_dry_run());
+        alienware.set_rgb_zone(Zone::Head, 15, 0, 0).unwrap();
+        alienware.set_hdmi_source(HDMISource::Cable).unwrap();
+        let planned = planned.lock().unwrap();
+        assert_eq!(
+            *planned,
+            vec![
+                crate::PlannedWrite {
+                    path: PathBuf::from(format!("{platform}/rgb_zones/zone00")),
+                    old: Some("red: 0, green: 0, blue: 15".to_string()),
+                    new: "0f0000".to_string(),
+                },
+                crate::PlannedWrite {
+                    path: PathBuf::from(format!("{platform}/hdmi/source")),
+                    old: Some("cable [gpu] unknown,".to_string()),
+                    new: "cable".to_string(),
+                },
+            ]
+        );
+        let mut file = File::open(format!("{platform}/rgb_zones/zone00")).unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).unwrap();
+        assert_eq!("red: 0, green: 0, blue: 15", contents);
+    }
+
+    pub(crate) const TEST_PATH: &str = "/tmp/alienware_wmi_test";
 
-    fn setup_not_aw(test: &str) -> String {
+    pub(crate) fn setup_not_aw(test: &str) -> String {
         let mut path_buf = PathBuf::new();
         path_buf.push(TEST_PATH);
         path_buf.push(test);
@@ -479,7 +718,7 @@ mod tests {
         platform
     }
 
-    fn setup_aw(test: &str) -> String {
+    pub(crate) fn setup_aw(test: &str) -> String {
         let mut path_buf = PathBuf::new();
         path_buf.push(TEST_PATH);
         path_buf.push(test);
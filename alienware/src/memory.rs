@@ -0,0 +1,130 @@
+use crate::brightness::BrightnessStore;
+use crate::calibration::Calibrations;
+use crate::{Alienware, Backend};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Error, ErrorKind};
+use std::sync::Mutex;
+
+/// The directory that a device held in memory appears to be in, as reported by dry runs
+const PLATFORM_DIR: &str = "alienware-wmi";
+
+impl Alienware {
+    /// Construct an Alpha R2 held in memory, so that code that drives the lights can be tested without the hardware.
+    /// Writes are taken and shown when read back in the same way as by the kernel module.  It starts with the head
+    /// LEDs blue, the left green and the right red, and the HDMI output connected to the GPU.
+    pub fn in_memory() -> Alienware {
+        let files = [
+            ("hdmi/cable", "[unconnected] connected unknown\n"),
+            ("hdmi/source", "input [gpu] unknown\n"),
+            ("rgb_zones/zone00", "red: 0, green: 0, blue: 15\n"),
+            ("rgb_zones/zone01", "red: 0, green: 15, blue: 0\n"),
+            ("rgb_zones/zone02", "red: 15, green: 0, blue: 0\n"),
+        ]
+        .into_iter()
+        .map(|(name, contents)| (name.to_string(), contents.to_string()))
+        .collect();
+        let dirs = ["", "hdmi", "rgb_zones"]
+            .into_iter()
+            .map(str::to_string)
+            .collect::<BTreeSet<String>>();
+        Alienware {
+            platform: PLATFORM_DIR.to_string(),
+            backend: Backend::Memory {
+                files: Mutex::new(files),
+                dirs,
+                writable: true,
+            },
+            dry_run: None,
+            brightness: BrightnessStore::default(),
+            calibrations: Calibrations::default(),
+        }
+    }
+}
+
+/// Take a write to a file of a device held in memory as the kernel module would, keeping what it shows when the file
+/// is read back
+pub(crate) fn write(
+    files: &mut BTreeMap<String, String>,
+    file_name: &str,
+    value: &str,
+) -> std::io::Result<()> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid value '{value}' for {file_name}"),
+        )
+    };
+    let contents = files
+        .get_mut(file_name)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, file_name.to_string()))?;
+    let value = value.trim();
+    if file_name.starts_with("rgb_zones/zone") {
+        // the colour is only taken as hex, and shown as its components with only the low 4 bits of each kept, as the
+        // kernel module masks the value with 0x0f0f0f0f
+        if value.len() != 6 || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let component = |i: usize| {
+            u8::from_str_radix(&value[i..i + 2], 16)
+                .map(|component| component & 0x0f)
+                .map_err(|_| invalid())
+        };
+        *contents = format!(
+            "red: {}, green: {}, blue: {}\n",
+            component(0)?,
+            component(2)?,
+            component(4)?
+        );
+    } else if file_name == "hdmi/source" {
+        *contents = match value {
+            "cable" | "input" => "[input] gpu unknown\n",
+            "gpu" => "input [gpu] unknown\n",
+            _ => return Err(invalid()),
+        }
+        .to_string();
+    } else if file_name == "hdmi/cable" {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{file_name} is read only"),
+        ));
+    } else {
+        *contents = value.to_string();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Alienware, Colour, HDMICableState, HDMISource, Zone};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn in_memory() {
+        let aw = Alienware::in_memory();
+        assert!(aw.is_alienware());
+        assert!(aw.has_hdmi());
+        assert!(aw.has_rgb_zones());
+        let hdmi = aw.get_hdmi().unwrap();
+        assert_eq!(hdmi.source, HDMISource::Gpu);
+        assert_eq!(hdmi.cable_state, HDMICableState::Unconnected);
+        assert_eq!(aw.get_rgb_zones().unwrap().zones.len(), 3);
+
+        aw.set_rgb_zone(Zone::Left, 1, 2, 15).unwrap();
+        aw.set_hdmi_source(HDMISource::Cable).unwrap();
+        assert_eq!(
+            aw.get_rgb_zones().unwrap().zones[&Zone::Left].colour(),
+            Colour::new(1, 2, 15)
+        );
+        // only the low 4 bits of each component are kept, as by the kernel module
+        aw.set_rgb_zone(Zone::Left, 16, 31, 255).unwrap();
+        assert_eq!(
+            aw.get_rgb_zones().unwrap().zones[&Zone::Left].colour(),
+            Colour::new(0, 15, 15)
+        );
+        assert_eq!(aw.get_hdmi().unwrap().source, HDMISource::Cable);
+        assert_eq!(
+            aw.set_hdmi_source(HDMISource::Unknown).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Parsers for the contents of the sysfs files of the platform device, and formatters for the values that are written
+//! to them.  None of the parsers panic, whatever they are given.
+
+use crate::{Colour, HDMICableState, HDMISource};
+use std::fmt;
+
+/// Reasons why the contents of a sysfs file could not be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// None of the options are selected with square brackets
+    NoSelection,
+    /// The selected option is not one that is known
+    UnknownOption(String),
+    /// The contents are not in any of the known RGB formats
+    UnknownFormat,
+    /// A colour component does not fit in a byte
+    OutOfRange {
+        component: &'static str,
+        value: String,
+    },
+    /// A colour temperature is outside of the range that can be converted to a colour
+    KelvinOutOfRange(u32),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::NoSelection => write!(f, "no option is selected"),
+            ParseError::UnknownOption(option) => write!(f, "unknown option '{option}'"),
+            ParseError::UnknownFormat => write!(f, "not in a known RGB format"),
+            ParseError::OutOfRange { component, value } => {
+                write!(f, "{component} value {value} is larger than 255")
+            }
+            ParseError::KelvinOutOfRange(kelvin) => write!(
+                f,
+                "colour temperature {kelvin}K is not between {}K and {}K",
+                Colour::MIN_KELVIN,
+                Colour::MAX_KELVIN
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for std::io::Error {
+    fn from(error: ParseError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Parse the option that is selected with square brackets in a file listing the choices, e.g. `cable [gpu] unknown`
+pub fn parse_selection(contents: &str) -> Result<&str, ParseError> {
+    let mut rest = contents;
+    while let Some(open) = rest.find('[') {
+        rest = &rest[open + 1..];
+        match rest.find(['[', ']']) {
+            // another opening bracket before this one was closed, so start again from there
+            Some(close) if rest.as_bytes()[close] == b'[' => rest = &rest[close..],
+            Some(close) => {
+                let selection = rest[..close].trim();
+                return if selection.is_empty() {
+                    Err(ParseError::NoSelection)
+                } else {
+                    Ok(selection)
+                };
+            }
+            None => break,
+        }
+    }
+    Err(ParseError::NoSelection)
+}
+
+/// Parse the contents of the `hdmi/source` file
+pub fn parse_hdmi_source(contents: &str) -> Result<HDMISource, ParseError> {
+    let selection = parse_selection(contents)?;
+    // some kernels call the cable source "input"
+    if selection.eq_ignore_ascii_case("cable") || selection.eq_ignore_ascii_case("input") {
+        Ok(HDMISource::Cable)
+    } else if selection.eq_ignore_ascii_case("gpu") {
+        Ok(HDMISource::Gpu)
+    } else if selection.eq_ignore_ascii_case("unknown") {
+        Ok(HDMISource::Unknown)
+    } else {
+        Err(ParseError::UnknownOption(selection.to_string()))
+    }
+}
+
+/// Parse the contents of the `hdmi/cable` file
+pub fn parse_cable_state(contents: &str) -> Result<HDMICableState, ParseError> {
+    let selection = parse_selection(contents)?;
+    if selection.eq_ignore_ascii_case("connected") {
+        Ok(HDMICableState::Connected)
+    } else if selection.eq_ignore_ascii_case("unconnected") {
+        Ok(HDMICableState::Unconnected)
+    } else if selection.eq_ignore_ascii_case("unknown") {
+        Ok(HDMICableState::Unknown)
+    } else {
+        Err(ParseError::UnknownOption(selection.to_string()))
+    }
+}
+
+/// Parse the contents of an `rgb_zones/zoneNN` file, either as `red: 0, green: 0, blue: 15` or as the hex value that
+/// is written to it, e.g. `00000f`
+pub fn parse_rgb(contents: &str) -> Result<Colour, ParseError> {
+    // the labelled components can be surrounded by other text, so try each place that "red" appears
+    let mut start = 0;
+    while let Some(found) = find_ignore_ascii_case(&contents[start..], "red") {
+        let mut cursor = Cursor(&contents[start + found..]);
+        if let Some(colour) = labelled_components(&mut cursor) {
+            return colour;
+        }
+        start += found + "red".len();
+    }
+    hex_components(contents).ok_or(ParseError::UnknownFormat)
+}
+
+/// Parse `red: 0, green: 0, blue: 15`, with optional commas and any amount of whitespace, returning `None` if the
+/// text does not match
+fn labelled_components(cursor: &mut Cursor) -> Option<Result<Colour, ParseError>> {
+    let mut components = [0u8; 3];
+    for (i, name) in ["red", "green", "blue"].into_iter().enumerate() {
+        if i > 0 {
+            cursor.skip_whitespace();
+            cursor.eat(",");
+        }
+        cursor.skip_whitespace();
+        if !cursor.eat_ignore_ascii_case(name) {
+            return None;
+        }
+        cursor.skip_whitespace();
+        if !cursor.eat(":") {
+            return None;
+        }
+        cursor.skip_whitespace();
+        let digits = cursor.digits()?;
+        match digits.parse::<u8>() {
+            Ok(value) => components[i] = value,
+            Err(_) => {
+                return Some(Err(ParseError::OutOfRange {
+                    component: name,
+                    value: digits.to_string(),
+                }))
+            }
+        }
+    }
+    Some(Ok(Colour::new(components[0], components[1], components[2])))
+}
+
+/// Parse a hex value such as `00000f`, `#00000f` or `0x00000f`, surrounded by nothing but whitespace
+fn hex_components(contents: &str) -> Option<Colour> {
+    let trimmed = contents.trim();
+    let hex = trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix('#'))
+        .unwrap_or(trimmed)
+        .as_bytes();
+    if hex.len() != 6 {
+        return None;
+    }
+    let byte = |i: usize| Some(hex_digit(hex[i])? << 4 | hex_digit(hex[i + 1])?);
+    Some(Colour::new(byte(0)?, byte(2)?, byte(4)?))
+}
+
+/// The value of a single hex digit
+fn hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// The byte offset of the first match of an ASCII needle, ignoring case
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// The unparsed remainder of some text
+struct Cursor<'a>(&'a str);
+
+impl<'a> Cursor<'a> {
+    fn skip_whitespace(&mut self) {
+        self.0 = self.0.trim_start();
+    }
+
+    /// Consume some text if it comes next
+    fn eat(&mut self, text: &str) -> bool {
+        match self.0.strip_prefix(text) {
+            Some(rest) => {
+                self.0 = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Consume some ASCII text if it comes next, ignoring case
+    fn eat_ignore_ascii_case(&mut self, text: &str) -> bool {
+        match self.0.as_bytes().get(..text.len()) {
+            Some(next) if next.eq_ignore_ascii_case(text.as_bytes()) => {
+                self.0 = &self.0[text.len()..];
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consume one or more ASCII digits
+    fn digits(&mut self) -> Option<&'a str> {
+        let end = self
+            .0
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.0.len());
+        if end == 0 {
+            return None;
+        }
+        let (digits, rest) = self.0.split_at(end);
+        self.0 = rest;
+        Some(digits)
+    }
+}
+
+/// The value written to the `hdmi/source` file to select a source
+pub fn format_hdmi_source(source: HDMISource) -> &'static str {
+    match source {
+        HDMISource::Cable => "cable",
+        HDMISource::Gpu => "gpu",
+        HDMISource::Unknown => "unknown",
+    }
+}
+
+/// The value written to an `rgb_zones/zoneNN` file to set its colour
+pub fn format_rgb(colour: Colour) -> String {
+    format!("{:02x}{:02x}{:02x}", colour.red, colour.green, colour.blue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn colour() -> impl Strategy<Value = Colour> {
+        any::<(u8, u8, u8)>().prop_map(Colour::from)
+    }
+
+    fn hdmi_source() -> impl Strategy<Value = HDMISource> {
+        prop_oneof![
+            Just(HDMISource::Cable),
+            Just(HDMISource::Gpu),
+            Just(HDMISource::Unknown),
+        ]
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse_selection("cable gpu unknown"),
+            Err(ParseError::NoSelection)
+        );
+        assert_eq!(
+            parse_selection("cable [] unknown"),
+            Err(ParseError::NoSelection)
+        );
+        assert_eq!(
+            parse_hdmi_source("[hdmi2]"),
+            Err(ParseError::UnknownOption("hdmi2".to_string()))
+        );
+        assert_eq!(parse_rgb(""), Err(ParseError::UnknownFormat));
+        assert_eq!(
+            parse_rgb("red: 256, green: 0, blue: 0"),
+            Err(ParseError::OutOfRange {
+                component: "red",
+                value: "256".to_string()
+            })
+        );
+        assert_eq!(
+            parse_rgb("red: ٣, green: 0, blue: 0"),
+            Err(ParseError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(parse_selection("cable [gpu] unknown,"), Ok("gpu"));
+        assert_eq!(parse_selection("[ [ input ] gpu"), Ok("input"));
+        assert_eq!(parse_selection("[unknown"), Err(ParseError::NoSelection));
+        assert_eq!(
+            parse_rgb("zone: red: 1 green:2,BLUE : 3"),
+            Ok(Colour::new(1, 2, 3))
+        );
+        assert_eq!(
+            parse_rgb("red red: 4, green: 5, blue: 6"),
+            Ok(Colour::new(4, 5, 6))
+        );
+        assert_eq!(parse_rgb(" 0x0A0b0c\n"), Ok(Colour::new(10, 11, 12)));
+        assert_eq!(parse_rgb("0a0b0"), Err(ParseError::UnknownFormat));
+        assert_eq!(parse_rgb("+a0b0c"), Err(ParseError::UnknownFormat));
+    }
+
+    proptest! {
+        #[test]
+        fn parsers_never_panic(contents in any::<String>()) {
+            let _ = parse_selection(&contents);
+            let _ = parse_hdmi_source(&contents);
+            let _ = parse_cable_state(&contents);
+            let _ = parse_rgb(&contents);
+        }
+
+        #[test]
+        fn rgb_parser_never_panics_on_numbers(red in "[0-9]{1,40}", green in "[0-9]{1,40}", blue in "[0-9]{1,40}") {
+            let _ = parse_rgb(&format!("red: {red}, green: {green}, blue: {blue}"));
+        }
+
+        #[test]
+        fn rgb_round_trip(colour in colour()) {
+            prop_assert_eq!(parse_rgb(&format_rgb(colour)), Ok(colour));
+            let kernel = format!("red: {}, green: {}, blue: {}\n", colour.red, colour.green, colour.blue);
+            prop_assert_eq!(parse_rgb(&kernel), Ok(colour));
+        }
+
+        #[test]
+        fn hdmi_source_round_trip(source in hdmi_source()) {
+            // the kernel lists every option, with the selected one in square brackets
+            let contents = [HDMISource::Cable, HDMISource::Gpu, HDMISource::Unknown]
+                .iter()
+                .map(|option| {
+                    if *option == source {
+                        format!("[{}]", format_hdmi_source(*option))
+                    } else {
+                        format_hdmi_source(*option).to_string()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            prop_assert_eq!(parse_hdmi_source(&contents), Ok(source));
+        }
+    }
+}
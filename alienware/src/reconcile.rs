@@ -0,0 +1,222 @@
+use crate::{Alienware, Change, Clock, MonotonicClock, SharedAlienware, State};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How often a waiting reconciler checks whether it has been stopped
+const STOP_CHECK: Duration = Duration::from_millis(50);
+
+/// A setting that was found to differ from the desired state and was put back
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Drift {
+    /// The time since the reconciler started that the setting was found to differ
+    pub t: Duration,
+    pub change: Change,
+}
+
+/// Something that happened in a pass of a running [`Reconciler`]
+#[derive(Debug)]
+pub enum ReconcileEvent {
+    /// A setting was found to differ from the desired state and was put back
+    Drift(Drift),
+    /// The machine could not be read or written, which is tried again at the next interval
+    Error {
+        /// The time since the reconciler started that the pass failed
+        t: Duration,
+        error: std::io::Error,
+    },
+}
+
+/// Keeps a machine in a desired [`State`], such as when the firmware resets the colours after a suspend.  Each pass
+/// reads the LEDs and HDMI source and writes back only the settings that differ from the desired state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reconciler {
+    desired: State,
+    interval: Duration,
+}
+
+impl Reconciler {
+    /// How long a reconciler waits between passes unless it is given an interval
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Construct a reconciler for a desired state
+    pub fn new(desired: State) -> Reconciler {
+        Reconciler {
+            desired,
+            interval: Reconciler::DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Set how long to wait from the start of one pass to the start of the next
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The state that the machine is kept in
+    pub fn desired(&self) -> &State {
+        &self.desired
+    }
+
+    /// Compare the machine with the desired state once and put back the settings that differ, returning the changes
+    /// that were made
+    pub fn reconcile(&self, aw: &Alienware) -> std::io::Result<Vec<Change>> {
+        let changes = aw.diff_state(&self.desired)?;
+        aw.apply_changes(&changes)?;
+        Ok(changes)
+    }
+
+    /// Reconcile every interval until `running` is cleared, such as by a Ctrl-C handler, passing each setting that is
+    /// put back to `event`.  Each pass takes the lock of the [`SharedAlienware`], which is let go while waiting for the
+    /// next.  A pass that cannot read or write the machine is passed to `event` as an error, and does not stop the
+    /// passes after it, so that a device that goes away for a while, such as over a suspend, is put back when it
+    /// returns.
+    pub fn run(
+        &self,
+        aw: &SharedAlienware,
+        running: &AtomicBool,
+        event: impl FnMut(ReconcileEvent),
+    ) {
+        self.run_with(aw, &MonotonicClock::new(), running, event)
+    }
+
+    /// Reconcile every interval of a clock until `running` is cleared, passing each setting that is put back and each
+    /// pass that fails to `event`
+    pub fn run_with(
+        &self,
+        aw: &SharedAlienware,
+        clock: &dyn Clock,
+        running: &AtomicBool,
+        mut event: impl FnMut(ReconcileEvent),
+    ) {
+        let start = clock.now();
+        let mut due = start;
+        while running.load(Ordering::SeqCst) {
+            let t = clock.now();
+            if t < due {
+                clock.sleep((due - t).min(STOP_CHECK));
+                continue;
+            }
+            match aw.lock().and_then(|aw| self.reconcile(&aw)) {
+                Ok(changes) => {
+                    for change in changes {
+                        event(ReconcileEvent::Drift(Drift {
+                            t: t - start,
+                            change,
+                        }));
+                    }
+                }
+                Err(error) => event(ReconcileEvent::Error {
+                    t: t - start,
+                    error,
+                }),
+            }
+            due = t + self.interval;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReconcileEvent, Reconciler};
+    use crate::tests::setup_aw;
+    use crate::{Alienware, Change, Colour, ManualClock, SharedAlienware, State, Zone};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    const RED: Colour = Colour::new(15, 0, 0);
+    const BLUE: Colour = Colour::new(0, 0, 15);
+
+    fn desired() -> State {
+        State {
+            zones: HashMap::from([(Zone::Head, RED), (Zone::Left, RED)]),
+            hdmi_source: None,
+        }
+    }
+
+    #[test]
+    fn reconcile_once() {
+        let aw = Alienware::in_memory();
+        let reconciler = Reconciler::new(desired());
+        assert_eq!(
+            reconciler.reconcile(&aw).unwrap(),
+            vec![
+                Change::Zone {
+                    zone: Zone::Head,
+                    from: Some(BLUE),
+                    to: RED
+                },
+                Change::Zone {
+                    zone: Zone::Left,
+                    from: Some(Colour::new(0, 15, 0)),
+                    to: RED
+                }
+            ]
+        );
+        assert_eq!(reconciler.reconcile(&aw).unwrap(), vec![]);
+        aw.set_rgb_zone(Zone::Left, 0, 0, 0).unwrap();
+        assert_eq!(reconciler.reconcile(&aw).unwrap().len(), 1);
+        assert_eq!(aw.get_rgb_zones().unwrap().zones[&Zone::Left].colour(), RED);
+    }
+
+    #[test]
+    fn reconcile_every_interval() {
+        let aw = SharedAlienware::new(Alienware::in_memory());
+        aw.set_rgb_zone(Zone::Left, 15, 0, 0).unwrap();
+        let reconciler = Reconciler::new(desired()).interval(Duration::from_secs(2));
+        let clock = ManualClock::new();
+        let running = AtomicBool::new(true);
+        let mut drifts = Vec::new();
+        reconciler.run_with(&aw, &clock, &running, |event| {
+            match event {
+                ReconcileEvent::Drift(drift) => drifts.push(drift),
+                ReconcileEvent::Error { error, .. } => panic!("{error}"),
+            }
+            if drifts.len() < 3 {
+                // drift again before the next pass
+                aw.set_rgb_zone(Zone::Head, 0, 0, 15).unwrap();
+            } else {
+                running.store(false, Ordering::SeqCst);
+            }
+        });
+        let times: Vec<Duration> = drifts.iter().map(|drift| drift.t).collect();
+        assert_eq!(
+            times,
+            vec![
+                Duration::ZERO,
+                Duration::from_secs(2),
+                Duration::from_secs(4)
+            ]
+        );
+        assert!(drifts.iter().all(|drift| matches!(
+            drift.change,
+            Change::Zone {
+                zone: Zone::Head,
+                ..
+            }
+        )));
+        assert_eq!(aw.get_rgb_zones().unwrap().zones[&Zone::Head].colour(), RED);
+    }
+
+    #[test]
+    fn retry_after_error() {
+        // the lock cannot be taken as the directory of its file does not exist
+        let platform = setup_aw("retry_after_error");
+        let lock_file = PathBuf::from(&platform).join("missing/alienware-wmi.lock");
+        let aw = SharedAlienware::with_lock_file(Alienware::test(platform), Some(lock_file));
+        let reconciler = Reconciler::new(desired()).interval(Duration::from_secs(2));
+        let clock = ManualClock::new();
+        let running = AtomicBool::new(true);
+        let mut errors = Vec::new();
+        reconciler.run_with(&aw, &clock, &running, |event| {
+            if let ReconcileEvent::Error { t, .. } = event {
+                errors.push(t);
+            }
+            if errors.len() == 2 {
+                running.store(false, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(errors, vec![Duration::ZERO, Duration::from_secs(2)]);
+    }
+}
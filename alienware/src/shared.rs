@@ -0,0 +1,422 @@
+use crate::effects::{run_locked, Effect};
+use crate::{
+    Alienware, Backend, Brightness, Calibration, Change, Colour, FrameScheduler, HDMISource,
+    RGBZone, RGBZones, State, StateError, Transition, Zone, HDMI,
+};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// The lock file that is shared by every process writing to the alienware platform device
+pub const DEFAULT_LOCK_FILE: &str = "/run/lock/alienware-wmi.lock";
+
+/// An [`Alienware`] that can be cloned and shared between threads.  Writes are serialised, both between the threads of
+/// this process with a mutex and between processes with an advisory `flock` on a lock file, so that an update made of
+/// several writes, such as applying a [`State`], is never interleaved with another one.  Reads are not locked.
+#[derive(Clone)]
+pub struct SharedAlienware {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    aw: Alienware,
+    lock_file: Option<PathBuf>,
+    /// The open lock file, which is opened by the first write
+    lock: Mutex<Option<File>>,
+}
+
+/// Exclusive access for writing to a [`SharedAlienware`], which is released when it is dropped
+pub struct AlienwareGuard<'a> {
+    aw: &'a Alienware,
+    lock: MutexGuard<'a, Option<File>>,
+}
+
+impl SharedAlienware {
+    /// Share an [`Alienware`], locking the [`DEFAULT_LOCK_FILE`] for writes
+    pub fn new(aw: Alienware) -> SharedAlienware {
+        SharedAlienware::with_lock_file(aw, Some(PathBuf::from(DEFAULT_LOCK_FILE)))
+    }
+
+    /// Share an [`Alienware`], locking the given lock file for writes, or only locking between threads if there is
+    /// none.  There is no lock file for dry runs or replayed devices as they never write to the device.
+    pub fn with_lock_file(aw: Alienware, lock_file: Option<PathBuf>) -> SharedAlienware {
+        let lock_file = match aw.backend {
+            Backend::Sysfs if !aw.is_dry_run() => lock_file,
+            _ => None,
+        };
+        SharedAlienware {
+            inner: Arc::new(Inner {
+                aw,
+                lock_file,
+                lock: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// The lock file that is locked for writes
+    pub fn lock_file(&self) -> Option<&Path> {
+        self.inner.lock_file.as_deref()
+    }
+
+    /// Wait for exclusive access to write to the device, for updates that are made of several calls
+    pub fn lock(&self) -> std::io::Result<AlienwareGuard<'_>> {
+        // nothing is left half done by a panic while holding the lock, so a poisoned lock can still be used
+        let mut lock = self
+            .inner
+            .lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(path) = &self.inner.lock_file {
+            if lock.is_none() {
+                *lock = Some(open_lock_file(path)?);
+            }
+        }
+        if let Some(file) = lock.as_ref() {
+            flock(file, true)?;
+        }
+        Ok(AlienwareGuard {
+            aw: &self.inner.aw,
+            lock,
+        })
+    }
+
+    /// The shared [`Alienware`], for reading without waiting for the lock.  Anything written through it is not
+    /// serialised with other writers.
+    pub fn unlocked(&self) -> &Alienware {
+        &self.inner.aw
+    }
+
+    /// Check that this is an Alienware server (i.e. has the alienware platform settings in sysfs)
+    pub fn is_alienware(&self) -> bool {
+        self.inner.aw.is_alienware()
+    }
+
+    /// Get the state of the HDMI ports
+    pub fn get_hdmi(&self) -> std::io::Result<HDMI> {
+        self.inner.aw.get_hdmi()
+    }
+
+    /// Set the source for the HDMI Output port
+    pub fn set_hdmi_source(&self, source: HDMISource) -> std::io::Result<()> {
+        self.lock()?.set_hdmi_source(source)
+    }
+
+    /// Get the state of the various LEDs
+    pub fn get_rgb_zones(&self) -> std::io::Result<RGBZones> {
+        self.inner.aw.get_rgb_zones()
+    }
+
+    /// Set an LED colour
+    pub fn set_rgb_zone(&self, zone: Zone, red: u8, green: u8, blue: u8) -> std::io::Result<()> {
+        self.lock()?.set_rgb_zone(zone, red, green, blue)
+    }
+
+    /// Get the colour that a zone was set to, before its brightness and calibration were applied, from an [`RGBZone`]
+    /// read from it
+    pub fn zone_colour(&self, rgb_zone: &RGBZone) -> Colour {
+        self.inner.aw.zone_colour(rgb_zone)
+    }
+
+    /// Get the colours that the zones were set to, before their brightness and calibration were applied
+    pub fn get_zone_colours(&self) -> std::io::Result<HashMap<Zone, Colour>> {
+        self.inner.aw.get_zone_colours()
+    }
+
+    /// Get the global brightness and the brightness of each zone
+    pub fn get_brightness(&self) -> std::io::Result<Brightness> {
+        self.inner.aw.get_brightness()
+    }
+
+    /// Set the global brightness as a percentage
+    pub fn set_brightness(&self, percent: u8) -> std::io::Result<()> {
+        self.lock()?.set_brightness(percent)
+    }
+
+    /// Set the brightness of a zone as a percentage
+    pub fn set_zone_brightness(&self, zone: Zone, percent: u8) -> std::io::Result<()> {
+        self.lock()?.set_zone_brightness(zone, percent)
+    }
+
+    /// Get the calibration of a zone
+    pub fn get_calibration(&self, zone: Zone) -> Calibration {
+        self.inner.aw.get_calibration(zone)
+    }
+
+    /// Set the calibration of a zone, waiting for any update in progress so that it is not part drawn with each
+    pub fn set_calibration(&self, zone: Zone, calibration: Calibration) -> std::io::Result<()> {
+        self.lock()?.set_calibration(zone, calibration);
+        Ok(())
+    }
+
+    /// Checks whether the alienware HDMI setup is available
+    pub fn has_hdmi(&self) -> bool {
+        self.inner.aw.has_hdmi()
+    }
+
+    /// Checks whether the alienware LED setup is available
+    pub fn has_rgb_zones(&self) -> bool {
+        self.inner.aw.has_rgb_zones()
+    }
+
+    /// Read the current settings of the machine
+    pub fn get_state(&self) -> std::io::Result<State> {
+        self.inner.aw.get_state()
+    }
+
+    /// Check whether a state can be applied to this machine, returning the reasons why not
+    pub fn validate_state(&self, state: &State) -> std::io::Result<Vec<StateError>> {
+        self.inner.aw.validate_state(state)
+    }
+
+    /// The changes that applying a state would make
+    pub fn diff_state(&self, state: &State) -> std::io::Result<Vec<Change>> {
+        self.inner.aw.diff_state(state)
+    }
+
+    /// Make a set of changes as a single update
+    pub fn apply_changes(&self, changes: &[Change]) -> std::io::Result<()> {
+        self.lock()?.apply_changes(changes)
+    }
+
+    /// Apply a state as a single update, returning the changes that were made
+    pub fn apply_state(&self, state: &State) -> std::io::Result<Vec<Change>> {
+        self.lock()?.apply_state(state)
+    }
+
+    /// Fade a zone to a colour, holding the lock until it is done
+    pub fn transition(
+        &self,
+        zone: Zone,
+        to: Colour,
+        transition: Transition,
+    ) -> std::io::Result<()> {
+        self.lock()?.transition(zone, to, transition)
+    }
+
+    /// Make a set of changes, fading the zones to their new colours, holding the lock until it is done
+    pub fn transition_changes(
+        &self,
+        changes: &[Change],
+        transition: Transition,
+    ) -> std::io::Result<()> {
+        self.lock()?.transition_changes(changes, transition)
+    }
+
+    /// Make a set of changes, fading the zones to their new colours until it is done or `running` is cleared, holding
+    /// the lock until then
+    pub fn transition_changes_until(
+        &self,
+        changes: &[Change],
+        transition: Transition,
+        running: &AtomicBool,
+    ) -> std::io::Result<()> {
+        self.lock()?
+            .transition_changes_until(changes, transition, running)
+    }
+
+    /// Show an effect as [`effects::run_with`](crate::effects::run_with) does, taking the lock for each frame rather
+    /// than for the whole effect so that other writers can change the zones in between
+    pub fn run_effect(
+        &self,
+        effect: &mut dyn Effect,
+        zones: &[Zone],
+        scheduler: &mut FrameScheduler<'_>,
+        running: &AtomicBool,
+        length: Option<Duration>,
+    ) -> std::io::Result<()> {
+        run_locked(
+            effect,
+            &self.inner.aw,
+            zones,
+            scheduler,
+            running,
+            length,
+            || self.lock(),
+        )
+    }
+
+    /// Play a show as [`show::play`](crate::show::play) does, taking the lock for each frame
+    #[cfg(feature = "show")]
+    pub fn play(
+        &self,
+        show: &crate::show::Show,
+        fps: u32,
+        running: &AtomicBool,
+    ) -> std::io::Result<()> {
+        let mut player = crate::show::player(show, &self.inner.aw)?;
+        let clock = crate::MonotonicClock::new();
+        self.run_effect(
+            &mut player,
+            &show.zones(),
+            &mut FrameScheduler::new(&clock, fps),
+            running,
+            show.length(),
+        )
+    }
+}
+
+impl From<Alienware> for SharedAlienware {
+    fn from(aw: Alienware) -> Self {
+        SharedAlienware::new(aw)
+    }
+}
+
+impl Deref for AlienwareGuard<'_> {
+    type Target = Alienware;
+
+    fn deref(&self) -> &Alienware {
+        self.aw
+    }
+}
+
+impl Drop for AlienwareGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(file) = self.lock.as_ref() {
+            // closing the file would also release the lock, so there is nothing more to do if this fails
+            let _ = flock(file, false);
+        }
+    }
+}
+
+/// Open the lock file, creating it so that any user can lock it
+pub(crate) fn open_lock_file(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(false);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o666);
+    options.open(path).map_err(|x| {
+        std::io::Error::new(
+            x.kind(),
+            format!("cannot open lock file {}: {x}", path.display()),
+        )
+    })
+}
+
+/// Take or release an exclusive advisory lock on a file, waiting for other processes to release it
+#[cfg(unix)]
+pub(crate) fn flock(file: &File, lock: bool) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let operation = if lock { libc::LOCK_EX } else { libc::LOCK_UN };
+    loop {
+        // SAFETY: the file descriptor is open for as long as `file` is borrowed
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Advisory locks are only taken on unix, elsewhere writes are only serialised within a process
+#[cfg(not(unix))]
+pub(crate) fn flock(_file: &File, _lock: bool) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedAlienware;
+    use crate::effects::{Effect, Frame};
+    use crate::tests::setup_aw;
+    use crate::{Alienware, Colour, FrameScheduler, ManualClock, Zone};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::Duration;
+
+    fn is_send_sync<T: Clone + Send + Sync>() {}
+
+    #[test]
+    fn share_between_threads() {
+        is_send_sync::<SharedAlienware>();
+        let platform = setup_aw("share_between_threads");
+        let lock_file = PathBuf::from(format!("{platform}.lock"));
+        let shared =
+            SharedAlienware::with_lock_file(Alienware::test(platform), Some(lock_file.clone()));
+        assert_eq!(shared.lock_file(), Some(lock_file.as_path()));
+        let threads: Vec<_> = [Zone::Head, Zone::Left, Zone::Right]
+            .into_iter()
+            .map(|zone| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for level in 0..16 {
+                        shared.set_rgb_zone(zone, level, 0, 0).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let zones = shared.get_rgb_zones().unwrap();
+        for zone in [Zone::Head, Zone::Left, Zone::Right] {
+            assert_eq!(zones.zones[&zone].colour(), Colour::new(15, 0, 0));
+        }
+    }
+
+    /// An effect that writes to the right zone itself while working out each frame
+    struct Interrupted(SharedAlienware);
+
+    impl Effect for Interrupted {
+        fn frame(&mut self, t: Duration, _zones: &[Zone]) -> Frame {
+            let level = t.as_secs() as u8;
+            self.0.set_rgb_zone(Zone::Right, 0, level, 0).unwrap();
+            HashMap::from([(Zone::Head, Colour::new(level, 0, 0))])
+        }
+    }
+
+    #[test]
+    fn lock_for_each_frame() {
+        let platform = setup_aw("lock_for_each_frame");
+        let lock_file = PathBuf::from(format!("{platform}.lock"));
+        let shared = SharedAlienware::with_lock_file(Alienware::test(platform), Some(lock_file));
+        let clock = ManualClock::new();
+        // this would deadlock if the lock were held for the whole effect
+        shared
+            .run_effect(
+                &mut Interrupted(shared.clone()),
+                &[Zone::Head],
+                &mut FrameScheduler::new(&clock, 1),
+                &AtomicBool::new(true),
+                Some(Duration::from_secs(3)),
+            )
+            .unwrap();
+        let zones = shared.get_rgb_zones().unwrap().zones;
+        assert_eq!(zones[&Zone::Head].colour(), Colour::new(3, 0, 0));
+        assert_eq!(zones[&Zone::Right].colour(), Colour::new(0, 3, 0));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn lock_between_processes() {
+        use std::fs::OpenOptions;
+        use std::os::unix::io::AsRawFd;
+        let platform = setup_aw("lock_between_processes");
+        let lock_file = PathBuf::from(format!("{platform}.lock"));
+        let shared =
+            SharedAlienware::with_lock_file(Alienware::test(platform), Some(lock_file.clone()));
+        assert!(!lock_file.exists());
+        let guard = shared.lock().unwrap();
+        // another open file description stands in for another process
+        let other = OpenOptions::new().read(true).open(&lock_file).unwrap();
+        let try_lock = || unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        assert_ne!(try_lock(), 0);
+        drop(guard);
+        assert_eq!(try_lock(), 0);
+    }
+
+    #[test]
+    fn no_lock_file_for_dry_run() {
+        let shared =
+            SharedAlienware::new(Alienware::test(setup_aw("no_lock_file")).dry_run(|_| {}));
+        assert_eq!(shared.lock_file(), None);
+        shared.set_rgb_zone(Zone::Head, 15, 0, 0).unwrap();
+    }
+}
@@ -0,0 +1,739 @@
+//! Light shows described as a timeline of keyframes for each zone, which are read from TOML or JSON documents and
+//! played on a machine.
+//!
+//! A show is a list of tracks, each taking some zones through a list of keyframes.  Each keyframe gives a colour and
+//! how long the zones take to get there from the keyframe before, with an easing curve for the fade.  Fades follow
+//! `ease-in-out` through OKLab, as they do everywhere else, unless the keyframe or its track gives another easing or
+//! colour space.  The `step` easing changes to the colour straight away and holds it for the duration.  Tracks, and the
+//! show as a whole, can be repeated a number of times or, with `repeat = 0`, for ever:
+//!
+//! ```toml
+//! repeat = 2
+//!
+//! [[tracks]]
+//! zones = ["left", "right"]
+//! easing = "step"
+//! keyframes = [
+//!     { colour = "red", duration = "1s" },
+//!     { colour = "blue", duration = "2s", easing = "linear", space = "rgb" },
+//! ]
+//! ```
+//!
+//! A fade between two keyframes that are both given as colour temperatures, such as `6500K` and `2700K`, moves through
+//! the temperatures in between rather than through a colour space, so that the light warms up as daylight does:
+//!
+//! ```toml
+//! [[tracks]]
+//! zones = ["head", "left", "right"]
+//! keyframes = [
+//!     { colour = "6500K", duration = "0s" },
+//!     { colour = "2700K", duration = "30m", easing = "linear" },
+//! ]
+//! ```
+
+use crate::document::{self, colour_from_json, Format};
+use crate::effects::{run_with, Effect, Frame};
+use crate::state::parse_kelvin;
+use crate::{
+    parse_duration, Alienware, Colour, ColourSpace, Easing, FrameScheduler, MonotonicClock, Zone,
+};
+use jzon::JsonValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// A light show of tracks that are played at the same time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Show {
+    pub name: Option<String>,
+    /// How many times the show is played, where 0 plays it for ever
+    pub repeat: u32,
+    pub tracks: Vec<Track>,
+}
+
+/// The keyframes that some zones go through
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub zones: Vec<Zone>,
+    /// How many times the keyframes are played in each play of the show, where 0 plays them for ever
+    pub repeat: u32,
+    pub keyframes: Vec<Keyframe>,
+}
+
+/// A colour that the zones of a track reach after a duration, fading from the keyframe before along an easing curve
+/// through a colour space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub colour: Colour,
+    pub duration: Duration,
+    pub easing: Easing,
+    pub space: ColourSpace,
+    /// The colour temperature that the colour was given as, if it was, so that a fade from another colour temperature
+    /// moves through the temperatures in between
+    pub kelvin: Option<u32>,
+}
+
+/// Reasons why a document is not a show that can be played, where tracks are numbered from 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShowError {
+    /// The document is not JSON or TOML, or is not laid out as a show
+    Invalid(String),
+    NoTracks,
+    NoZones {
+        track: usize,
+    },
+    NoKeyframes {
+        track: usize,
+    },
+    /// A zone is in more than one track, so it would be given two colours at once
+    ZoneInTwoTracks(Zone),
+    /// A track repeats for ever but takes no time
+    EndlessTrack {
+        track: usize,
+    },
+    /// The show repeats for ever but takes no time
+    EndlessShow,
+    /// The zone is not on this machine
+    MissingZone(Zone),
+}
+
+impl fmt::Display for ShowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShowError::Invalid(reason) => write!(f, "{reason}"),
+            ShowError::NoTracks => write!(f, "the show has no tracks"),
+            ShowError::NoZones { track } => write!(f, "track {track} has no zones"),
+            ShowError::NoKeyframes { track } => write!(f, "track {track} has no keyframes"),
+            ShowError::ZoneInTwoTracks(zone) => write!(f, "{zone} is in more than one track"),
+            ShowError::EndlessTrack { track } => {
+                write!(f, "track {track} repeats for ever but takes no time")
+            }
+            ShowError::EndlessShow => write!(f, "the show repeats for ever but takes no time"),
+            ShowError::MissingZone(zone) => write!(f, "there are no {zone} LEDs on this machine"),
+        }
+    }
+}
+
+impl std::error::Error for ShowError {}
+
+impl Show {
+    /// Read a show from a JSON document
+    pub fn from_json(contents: &str) -> Result<Show, ShowError> {
+        Show::from_document(contents, Some(Format::Json))
+    }
+
+    /// Read a show from a TOML document
+    pub fn from_toml(contents: &str) -> Result<Show, ShowError> {
+        Show::from_document(contents, Some(Format::Toml))
+    }
+
+    /// Read a show from a document that is either JSON or TOML
+    pub fn parse(contents: &str) -> Result<Show, ShowError> {
+        Show::from_document(contents, None)
+    }
+
+    /// Read a show from a document in a format, or either format if it is not known
+    pub fn from_document(contents: &str, format: Option<Format>) -> Result<Show, ShowError> {
+        show_from_json(&document::parse(contents, format).map_err(ShowError::Invalid)?)
+    }
+
+    /// Check that the show can be played, returning the reasons why not
+    pub fn validate(&self) -> Vec<ShowError> {
+        let mut errors = Vec::new();
+        if self.tracks.is_empty() {
+            errors.push(ShowError::NoTracks);
+        }
+        let mut seen = Vec::new();
+        for (index, track) in self.tracks.iter().enumerate() {
+            let number = index + 1;
+            if track.zones.is_empty() {
+                errors.push(ShowError::NoZones { track: number });
+            }
+            if track.keyframes.is_empty() {
+                errors.push(ShowError::NoKeyframes { track: number });
+            } else if track.repeat == 0 && track.cycle_length().is_zero() {
+                errors.push(ShowError::EndlessTrack { track: number });
+            }
+            for zone in track.zones.iter() {
+                if seen.contains(zone) {
+                    if !errors.contains(&ShowError::ZoneInTwoTracks(*zone)) {
+                        errors.push(ShowError::ZoneInTwoTracks(*zone));
+                    }
+                } else {
+                    seen.push(*zone);
+                }
+            }
+        }
+        if self.repeat == 0 && self.pass_length().is_some_and(|length| length.is_zero()) {
+            errors.push(ShowError::EndlessShow);
+        }
+        errors
+    }
+
+    /// The zones that the show changes
+    pub fn zones(&self) -> Vec<Zone> {
+        let mut zones: Vec<Zone> = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.zones.iter().copied())
+            .collect();
+        zones.sort_by_key(|zone| *zone as u8);
+        zones.dedup();
+        zones
+    }
+
+    /// How long the show plays for, or `None` if it plays for ever
+    pub fn length(&self) -> Option<Duration> {
+        if self.repeat == 0 {
+            None
+        } else {
+            self.pass_length().map(|length| length * self.repeat)
+        }
+    }
+
+    /// How long a single play of the show takes, or `None` if one of its tracks repeats for ever
+    fn pass_length(&self) -> Option<Duration> {
+        self.tracks
+            .iter()
+            .map(Track::length)
+            .try_fold(Duration::ZERO, |longest, length| {
+                length.map(|length| longest.max(length))
+            })
+    }
+}
+
+impl Track {
+    /// How long the track plays for, or `None` if it repeats for ever
+    pub fn length(&self) -> Option<Duration> {
+        if self.repeat == 0 {
+            None
+        } else {
+            Some(self.cycle_length() * self.repeat)
+        }
+    }
+
+    /// How long a single play of the keyframes takes
+    fn cycle_length(&self) -> Duration {
+        self.keyframes
+            .iter()
+            .map(|keyframe| keyframe.duration)
+            .sum()
+    }
+
+    /// The colour that the track ends on
+    fn last_colour(&self) -> Option<Colour> {
+        self.keyframes.last().map(|keyframe| keyframe.colour)
+    }
+
+    /// The colour temperature that the track ends on, if it ends on one
+    fn last_kelvin(&self) -> Option<u32> {
+        self.keyframes.last().and_then(|keyframe| keyframe.kelvin)
+    }
+
+    /// The colour of the track at a time since it started, fading to the first keyframe from a starting colour
+    fn colour(&self, t: Duration, start: Colour) -> Colour {
+        let (pass, t) = split_passes(t, self.cycle_length(), self.repeat);
+        let (mut from, mut from_kelvin) = match self.last_colour() {
+            Some(last) if pass > 0 => (last, self.last_kelvin()),
+            _ => (start, None),
+        };
+        let mut at = Duration::ZERO;
+        for keyframe in self.keyframes.iter() {
+            if t < at + keyframe.duration {
+                let amount = (t - at).as_secs_f32() / keyframe.duration.as_secs_f32();
+                let amount = keyframe.easing.apply(amount);
+                return match (from_kelvin, keyframe.kelvin) {
+                    (Some(from_kelvin), Some(to_kelvin)) => {
+                        mix_kelvin(from_kelvin, to_kelvin, amount).unwrap_or(keyframe.colour)
+                    }
+                    _ => keyframe.space.mix(from, keyframe.colour, amount),
+                };
+            }
+            at += keyframe.duration;
+            from = keyframe.colour;
+            from_kelvin = keyframe.kelvin;
+        }
+        from
+    }
+}
+
+/// Plays a [`Show`] as an [`Effect`], fading each zone to the first keyframe of its track from a starting colour
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    show: Show,
+    start: HashMap<Zone, Colour>,
+}
+
+impl Player {
+    /// Construct a player for a show, where every zone starts from black
+    pub fn new(show: Show) -> Player {
+        Player {
+            show,
+            start: HashMap::new(),
+        }
+    }
+
+    /// Start the zones from their colours, such as the ones they show before the show is played
+    pub fn starting_from(mut self, colours: HashMap<Zone, Colour>) -> Player {
+        self.start = colours;
+        self
+    }
+
+    /// The show that is played
+    pub fn show(&self) -> &Show {
+        &self.show
+    }
+
+    /// The colour of every zone in the show at a time since it started
+    pub fn colours(&self, t: Duration) -> Frame {
+        let (pass, t) = match self.show.pass_length() {
+            Some(length) => split_passes(t, length, self.show.repeat),
+            None => (0, t),
+        };
+        let mut frame = Frame::new();
+        for track in self.show.tracks.iter() {
+            for zone in track.zones.iter() {
+                let start = match track.last_colour() {
+                    Some(last) if pass > 0 => last,
+                    _ => self.start.get(zone).copied().unwrap_or_default(),
+                };
+                frame.insert(*zone, track.colour(t, start));
+            }
+        }
+        frame
+    }
+}
+
+impl Effect for Player {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        let mut frame = self.colours(t);
+        frame.retain(|zone, _| zones.contains(zone));
+        frame
+    }
+}
+
+impl Alienware {
+    /// Check that a show can be played on this machine, returning the reasons why not
+    pub fn validate_show(&self, show: &Show) -> std::io::Result<Vec<ShowError>> {
+        let mut errors = show.validate();
+        let rgb_zones = self.get_rgb_zones()?;
+        for zone in show.zones() {
+            if !rgb_zones.zones.contains_key(&zone) {
+                errors.push(ShowError::MissingZone(zone));
+            }
+        }
+        Ok(errors)
+    }
+}
+
+/// Play a show at a number of frames a second, starting each zone from the colour it shows now, until it ends or
+/// `running` is cleared, such as by a Ctrl-C handler
+pub fn play(show: &Show, aw: &Alienware, fps: u32, running: &AtomicBool) -> std::io::Result<()> {
+    let mut player = player(show, aw)?;
+    let clock = MonotonicClock::new();
+    run_with(
+        &mut player,
+        aw,
+        &show.zones(),
+        &mut FrameScheduler::new(&clock, fps),
+        running,
+        show.length(),
+    )
+}
+
+/// A player for a show that starts from the colours that the zones have now
+pub(crate) fn player(show: &Show, aw: &Alienware) -> std::io::Result<Player> {
+    let start = aw.get_zone_colours()?;
+    Ok(Player::new(show.clone()).starting_from(start))
+}
+
+/// The colour of a colour temperature part way between two others, where an amount of 0 is the first and 1 is the
+/// second
+fn mix_kelvin(from: u32, to: u32, amount: f32) -> Option<Colour> {
+    let amount = f64::from(amount.clamp(0.0, 1.0));
+    let kelvin = f64::from(from) + (f64::from(to) - f64::from(from)) * amount;
+    Colour::from_kelvin(kelvin.round() as u32)
+}
+
+/// Split a time into the number of whole plays of something that repeats, and the time into the current play.  Once
+/// the last play is over the time stays at the end of it.
+fn split_passes(t: Duration, length: Duration, repeat: u32) -> (u32, Duration) {
+    if length.is_zero() {
+        return (0, t);
+    }
+    let pass = t.as_nanos() / length.as_nanos();
+    if repeat > 0 && pass >= u128::from(repeat) {
+        (repeat - 1, length)
+    } else {
+        let into = Duration::from_nanos((t.as_nanos() % length.as_nanos()) as u64);
+        (u32::try_from(pass).unwrap_or(u32::MAX), into)
+    }
+}
+
+/// Read a show from the JSON layout that both formats share
+fn show_from_json(value: &JsonValue) -> Result<Show, ShowError> {
+    let invalid = |reason: String| ShowError::Invalid(reason);
+    if !value.is_object() {
+        return Err(invalid("the document must be an object".to_string()));
+    }
+    let mut show = Show {
+        name: None,
+        repeat: 1,
+        tracks: Vec::new(),
+    };
+    for (key, value) in value.entries() {
+        match key {
+            "name" => {
+                show.name = Some(
+                    value
+                        .as_str()
+                        .ok_or(invalid("name must be a string".to_string()))?
+                        .to_string(),
+                )
+            }
+            "repeat" => show.repeat = repeat_from_json("the show", value).map_err(invalid)?,
+            "tracks" => {
+                if !value.is_array() {
+                    return Err(invalid("tracks must be a list".to_string()));
+                }
+                for (index, value) in value.members().enumerate() {
+                    show.tracks.push(
+                        track_from_json(index + 1, value)
+                            .map_err(|x| invalid(format!("track {}: {x}", index + 1)))?,
+                    );
+                }
+            }
+            _ => return Err(invalid(format!("unknown setting '{key}'"))),
+        }
+    }
+    Ok(show)
+}
+
+fn track_from_json(number: usize, value: &JsonValue) -> Result<Track, String> {
+    if !value.is_object() {
+        return Err("a track must be an object".to_string());
+    }
+    let mut track = Track {
+        zones: Vec::new(),
+        repeat: 1,
+        keyframes: Vec::new(),
+    };
+    // the easing and colour space of a track are the defaults for its keyframes, wherever they are in the track
+    let easing = match value["easing"].is_null() {
+        true => Easing::default(),
+        false => easing_from_json(&value["easing"])?,
+    };
+    let space = match value["space"].is_null() {
+        true => ColourSpace::default(),
+        false => space_from_json(&value["space"])?,
+    };
+    for (key, value) in value.entries() {
+        match key {
+            "zones" | "zone" => {
+                let names: Vec<&JsonValue> = if value.is_array() {
+                    value.members().collect()
+                } else {
+                    vec![value]
+                };
+                for name in names {
+                    let zone = name
+                        .as_str()
+                        .and_then(|name| name.parse::<Zone>().ok())
+                        .ok_or(format!("unknown LED zone {name}"))?;
+                    track.zones.push(zone);
+                }
+            }
+            "repeat" => track.repeat = repeat_from_json(&format!("track {number}"), value)?,
+            "easing" | "space" => {}
+            "keyframes" => {
+                if !value.is_array() {
+                    return Err("keyframes must be a list".to_string());
+                }
+                for (index, value) in value.members().enumerate() {
+                    track.keyframes.push(
+                        keyframe_from_json(value, easing, space)
+                            .map_err(|x| format!("keyframe {}: {x}", index + 1))?,
+                    );
+                }
+            }
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+    }
+    Ok(track)
+}
+
+fn keyframe_from_json(
+    value: &JsonValue,
+    easing: Easing,
+    space: ColourSpace,
+) -> Result<Keyframe, String> {
+    if !value.is_object() {
+        return Err("a keyframe must be an object".to_string());
+    }
+    let mut colour = None;
+    let mut keyframe = Keyframe {
+        colour: Colour::default(),
+        duration: Duration::ZERO,
+        easing,
+        space,
+        kelvin: None,
+    };
+    for (key, value) in value.entries() {
+        match key {
+            "colour" | "color" => {
+                colour = Some(colour_from_json(value)?);
+                keyframe.kelvin = value.as_str().and_then(parse_kelvin);
+            }
+            "duration" => keyframe.duration = duration_from_json(value)?,
+            "easing" => keyframe.easing = easing_from_json(value)?,
+            "space" => keyframe.space = space_from_json(value)?,
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+    }
+    keyframe.colour = colour.ok_or("a keyframe must have a colour".to_string())?;
+    Ok(keyframe)
+}
+
+fn easing_from_json(value: &JsonValue) -> Result<Easing, String> {
+    value
+        .as_str()
+        .and_then(|easing| easing.parse().ok())
+        .ok_or(format!(
+            "invalid easing {value}, try step, linear, ease-in, ease-out or ease-in-out"
+        ))
+}
+
+fn space_from_json(value: &JsonValue) -> Result<ColourSpace, String> {
+    value
+        .as_str()
+        .and_then(|space| space.parse().ok())
+        .ok_or(format!(
+            "invalid colour space {value}, try rgb, hsv or oklab"
+        ))
+}
+
+fn repeat_from_json(what: &str, value: &JsonValue) -> Result<u32, String> {
+    value.as_u32().ok_or(format!(
+        "the repeat of {what} must be a number of times, or 0 for ever"
+    ))
+}
+
+/// Read a duration, either as a number of seconds or a string such as `500ms`, `2s` or `1m`
+fn duration_from_json(value: &JsonValue) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {value}, e.g. 500ms, 2s or 1m");
+    let duration = match value.as_str() {
+        Some(s) => parse_duration(s),
+        None => value
+            .as_f64()
+            .filter(|number| number.is_finite() && *number >= 0.0)
+            .and_then(|number| Duration::try_from_secs_f64(number).ok()),
+    };
+    duration.ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::setup_aw;
+    use std::fs::read_to_string;
+
+    const RED: Colour = Colour::new(15, 0, 0);
+    const BLUE: Colour = Colour::new(0, 0, 15);
+
+    fn at(player: &Player, millis: u64, zone: Zone) -> Colour {
+        player.colours(Duration::from_millis(millis))[&zone]
+    }
+
+    #[test]
+    fn parse_toml_and_json() {
+        let toml = Show::from_toml(
+            r#"
+name = "test"
+repeat = 2
+
+[[tracks]]
+zones = ["left", "right"]
+keyframes = [
+    { colour = "red", duration = "1s" },
+    { color = [0, 0, 15], duration = 2, easing = "ease-in-out", space = "hsv" },
+]
+"#,
+        )
+        .unwrap();
+        let json = Show::parse(
+            r##"{"name": "test", "repeat": 2, "tracks": [{"zones": ["left", "right"], "keyframes": [
+                {"colour": "#ff0000", "duration": "1000ms"},
+                {"colour": {"red": 0, "green": 0, "blue": 15}, "duration": 2.0, "easing": "ease-in-out", "space": "HSV"}
+            ]}]}"##,
+        )
+        .unwrap();
+        assert_eq!(toml, json);
+        assert_eq!(toml.name.as_deref(), Some("test"));
+        assert_eq!(toml.zones(), vec![Zone::Left, Zone::Right]);
+        assert_eq!(toml.length(), Some(Duration::from_secs(6)));
+        assert_eq!(toml.tracks[0].keyframes[0].easing, Easing::EaseInOut);
+        assert_eq!(toml.tracks[0].keyframes[0].space, ColourSpace::Oklab);
+        assert_eq!(
+            toml.tracks[0].keyframes[1],
+            Keyframe {
+                colour: BLUE,
+                duration: Duration::from_secs(2),
+                easing: Easing::EaseInOut,
+                space: ColourSpace::Hsv,
+                kelvin: None,
+            }
+        );
+        assert!(toml.validate().is_empty());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for document in [
+            "not a show",
+            r#"{"tracks": {}}"#,
+            r#"{"tracks": [{"zones": "middle", "keyframes": []}]}"#,
+            r#"{"tracks": [{"zones": "head", "keyframes": [{"duration": "1s"}]}]}"#,
+            r#"{"tracks": [{"zones": "head", "keyframes": [{"colour": "purple"}]}]}"#,
+            r#"{"tracks": [{"zones": "head", "keyframes": [{"colour": "red", "duration": "-1s"}]}]}"#,
+            r#"{"tracks": [{"zones": "head", "keyframes": [{"colour": "red", "easing": "bounce"}]}]}"#,
+            r#"{"tracks": [{"zones": "head", "keyframes": [{"colour": "red", "space": "lab"}]}]}"#,
+            r#"{"tracks": [{"zones": "head", "easing": "bounce", "keyframes": []}]}"#,
+            r#"{"tracks": [{"zones": "head", "repeat": -1, "keyframes": []}]}"#,
+            r#"{"tracks": [], "speed": 2}"#,
+        ] {
+            assert!(Show::parse(document).is_err(), "{document}");
+        }
+        assert_eq!(
+            Show::parse(r#"{"tracks": [{"zones": "head", "keyframes": [{}]}]}"#),
+            Err(ShowError::Invalid(
+                "track 1: keyframe 1: a keyframe must have a colour".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(
+            Show::parse("{}").unwrap().validate(),
+            vec![ShowError::NoTracks]
+        );
+        let show = Show::parse(
+            r#"{"repeat": 0, "tracks": [
+                {"zones": "head", "keyframes": []},
+                {"zones": ["head", "left"], "repeat": 0, "keyframes": [{"colour": "red"}]},
+                {"zones": [], "keyframes": [{"colour": "red", "duration": "1s"}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            show.validate(),
+            vec![
+                ShowError::NoKeyframes { track: 1 },
+                ShowError::EndlessTrack { track: 2 },
+                ShowError::ZoneInTwoTracks(Zone::Head),
+                ShowError::NoZones { track: 3 },
+            ]
+        );
+        let show = Show::parse(
+            r#"{"repeat": 0, "tracks": [{"zones": "head", "keyframes": [{"colour": "red"}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(show.validate(), vec![ShowError::EndlessShow]);
+        assert_eq!(show.length(), None);
+    }
+
+    #[test]
+    fn player() {
+        let show = Show::from_toml(
+            r#"
+[[tracks]]
+zones = "head"
+repeat = 2
+easing = "step"
+space = "rgb"
+keyframes = [
+    { colour = "red", duration = "1s" },
+    { colour = "blue", duration = "1s", easing = "linear" },
+]
+
+[[tracks]]
+zone = "left"
+space = "rgb"
+keyframes = [{ colour = "blue", duration = "2s", easing = "linear" }]
+"#,
+        )
+        .unwrap();
+        assert_eq!(show.length(), Some(Duration::from_secs(4)));
+        let player = Player::new(show).starting_from(HashMap::from([(Zone::Left, RED)]));
+        assert_eq!(at(&player, 0, Zone::Head), RED);
+        assert_eq!(at(&player, 1500, Zone::Head), Colour::new(8, 0, 8));
+        assert_eq!(at(&player, 2000, Zone::Head), RED);
+        assert_eq!(at(&player, 3999, Zone::Head), BLUE);
+        assert_eq!(at(&player, 10000, Zone::Head), BLUE);
+        assert_eq!(at(&player, 0, Zone::Left), RED);
+        assert_eq!(at(&player, 1000, Zone::Left), Colour::new(8, 0, 8));
+        assert_eq!(at(&player, 3000, Zone::Left), BLUE);
+        let mut player = player;
+        let frame = player.frame(Duration::ZERO, &[Zone::Head, Zone::Right]);
+        assert_eq!(frame.len(), 1);
+    }
+
+    #[test]
+    fn kelvin_ramp() {
+        let show = Show::from_toml(
+            r#"
+[[tracks]]
+zones = "head"
+space = "rgb"
+keyframes = [
+    { colour = "6500K", duration = "0s" },
+    { colour = "2700K", duration = "4s", easing = "linear" },
+    { colour = "red", duration = "2s", easing = "linear" },
+]
+"#,
+        )
+        .unwrap();
+        assert_eq!(show.tracks[0].keyframes[1].kelvin, Some(2700));
+        let player = Player::new(show);
+        assert_eq!(at(&player, 0, Zone::Head), Colour::new(15, 15, 15));
+        // half way through the fade is 4600K, which is bluer than half way between the colours
+        assert_eq!(at(&player, 2000, Zone::Head), Colour::new(15, 13, 11));
+        assert_eq!(at(&player, 4000, Zone::Head), Colour::new(15, 10, 5));
+        assert_eq!(at(&player, 5000, Zone::Head), Colour::new(15, 5, 3));
+    }
+
+    #[test]
+    fn repeat_show() {
+        let show = Show::parse(
+            r#"{"repeat": 0, "tracks": [{"zones": "right", "easing": "step", "space": "rgb", "keyframes": [
+                {"colour": "red", "duration": "1s"},
+                {"colour": "blue", "duration": "1s", "easing": "linear"}
+            ]}]}"#,
+        )
+        .unwrap();
+        let player = Player::new(show);
+        assert_eq!(at(&player, 500, Zone::Right), RED);
+        assert_eq!(at(&player, 60_500, Zone::Right), RED);
+        assert_eq!(at(&player, 61_500, Zone::Right), Colour::new(8, 0, 8));
+    }
+
+    #[test]
+    fn play_to_the_end() {
+        let platform = setup_aw("play_show");
+        let aw = Alienware::test(platform.clone());
+        let show = Show::parse(
+            r#"{"tracks": [{"zones": ["head", "left"], "keyframes": [
+                {"colour": "blue", "duration": "50ms"},
+                {"colour": "red", "duration": "50ms", "easing": "linear"}
+            ]}]}"#,
+        )
+        .unwrap();
+        assert!(aw.validate_show(&show).unwrap().is_empty());
+        play(&show, &aw, 50, &AtomicBool::new(true)).unwrap();
+        for zone in ["zone00", "zone01"] {
+            let path = format!("{platform}/rgb_zones/{zone}");
+            assert_eq!(read_to_string(path).unwrap(), "0f0000");
+        }
+    }
+}
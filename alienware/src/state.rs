@@ -0,0 +1,454 @@
+use crate::effects::MAX_LEVEL;
+use crate::{Alienware, HDMISource, ParseError, Zone};
+use std::collections::HashMap;
+use std::fmt;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+
+/// An RGB colour that can be written to an LED zone
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Colour {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Colour {
+    /// Construct a new colour from its red, green and blue components
+    pub const fn new(red: u8, green: u8, blue: u8) -> Colour {
+        Colour { red, green, blue }
+    }
+
+    /// The lowest colour temperature, in Kelvin, that can be converted to a colour
+    pub const MIN_KELVIN: u32 = 1000;
+    /// The highest colour temperature, in Kelvin, that can be converted to a colour
+    pub const MAX_KELVIN: u32 = 40000;
+
+    /// The colour of light at a colour temperature in Kelvin, on the same 0-15 scale as the named colours, or `None` if
+    /// it is outside of `MIN_KELVIN` to `MAX_KELVIN`.  Candle light is about 1900K, a warm white bulb about 2700K and
+    /// daylight about 6500K, which is white, with higher temperatures getting bluer.
+    pub fn from_kelvin(kelvin: u32) -> Option<Colour> {
+        if !(Colour::MIN_KELVIN..=Colour::MAX_KELVIN).contains(&kelvin) {
+            return None;
+        }
+        // Tanner Helland's curve fit to the colour of a black body, which is empirical rather than taken from the colour
+        // matching functions, on a 0-255 scale
+        let t = kelvin as f64 / 100.0;
+        let (red, green, blue) = if t <= 66.0 {
+            (
+                255.0,
+                99.470_802_586_1 * t.ln() - 161.119_568_166_1,
+                if t <= 19.0 {
+                    0.0
+                } else {
+                    138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+                },
+            )
+        } else {
+            (
+                329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2),
+                288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2),
+                255.0,
+            )
+        };
+        let level = |value: f64| (value.clamp(0.0, 255.0) * 15.0 / 255.0).round() as u8;
+        Some(Colour::new(level(red), level(green), level(blue)))
+    }
+
+    /// This colour with every component multiplied by a factor, which is clamped to between 0 and 1
+    pub fn scale(self, factor: f32) -> Colour {
+        let factor = if factor.is_nan() {
+            0.0
+        } else {
+            factor.clamp(0.0, 1.0)
+        };
+        let scale = |component: u8| (f32::from(component) * factor).round() as u8;
+        Colour::new(scale(self.red), scale(self.green), scale(self.blue))
+    }
+
+    /// A colour part way between this colour and another, where an amount of 0 is this colour and 1 is the other
+    pub fn mix(self, other: Colour, amount: f32) -> Colour {
+        let amount = if amount.is_nan() {
+            0.0
+        } else {
+            amount.clamp(0.0, 1.0)
+        };
+        let mix = |from: u8, to: u8| {
+            (f32::from(from) + (f32::from(to) - f32::from(from)) * amount).round() as u8
+        };
+        Colour::new(
+            mix(self.red, other.red),
+            mix(self.green, other.green),
+            mix(self.blue, other.blue),
+        )
+    }
+}
+
+impl From<(u8, u8, u8)> for Colour {
+    fn from((red, green, blue): (u8, u8, u8)) -> Self {
+        Colour { red, green, blue }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = ParseError;
+
+    /// Parse a named colour, `#rrggbb` hex, three numbers separated by spaces or a colour temperature in Kelvin, e.g.
+    /// `cyan`, `#00ffff`, `0 15 15` or `2700K`.  Hex components are scaled from 0-255 down to the 0-15 scale of the
+    /// named colours, so `#00ffff` is the same as `cyan`.
+    fn from_str(input: &str) -> Result<Colour, ParseError> {
+        if let Some(kelvin) = parse_kelvin(input) {
+            return Colour::from_kelvin(kelvin).ok_or(ParseError::KelvinOutOfRange(kelvin));
+        }
+        let input = input.trim().to_lowercase();
+        match input.as_str() {
+            "black" => Ok(Colour::new(0, 0, 0)),
+            "white" => Ok(Colour::new(15, 15, 15)),
+            "red" => Ok(Colour::new(15, 0, 0)),
+            "yellow" => Ok(Colour::new(15, 15, 0)),
+            "green" => Ok(Colour::new(0, 15, 0)),
+            "cyan" => Ok(Colour::new(0, 15, 15)),
+            "blue" => Ok(Colour::new(0, 0, 15)),
+            "magenta" => Ok(Colour::new(15, 0, 15)),
+            hex if hex.starts_with('#') => {
+                let hex = &hex[1..];
+                if hex.len() != 6 || !hex.is_ascii() {
+                    return Err(ParseError::UnknownFormat);
+                }
+                let component = |i: usize| {
+                    u8::from_str_radix(&hex[i..i + 2], 16)
+                        .map(|value| ((u16::from(value) * u16::from(MAX_LEVEL) + 127) / 255) as u8)
+                        .map_err(|_| ParseError::UnknownFormat)
+                };
+                Ok(Colour::new(component(0)?, component(2)?, component(4)?))
+            }
+            _ => {
+                let values: Vec<&str> = input.split_whitespace().collect();
+                let (red, green, blue) = match values[..] {
+                    [red, green, blue] => (red, green, blue),
+                    _ => return Err(ParseError::UnknownFormat),
+                };
+                let component = |component: &'static str, value: &str| {
+                    value.parse::<u8>().map_err(|x| match x.kind() {
+                        IntErrorKind::PosOverflow => ParseError::OutOfRange {
+                            component,
+                            value: value.to_string(),
+                        },
+                        _ => ParseError::UnknownFormat,
+                    })
+                };
+                Ok(Colour::new(
+                    component("red", red)?,
+                    component("green", green)?,
+                    component("blue", blue)?,
+                ))
+            }
+        }
+    }
+}
+
+/// The temperature of a colour given in Kelvin, such as `2700K`
+pub(crate) fn parse_kelvin(input: &str) -> Option<u32> {
+    let input = input.trim();
+    let digits = input
+        .strip_suffix('K')
+        .or_else(|| input.strip_suffix('k'))?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(digits.parse().unwrap_or(u32::MAX))
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.red, self.green, self.blue)
+    }
+}
+
+/// A complete or partial description of the settings of an Alienware machine.  Any setting that is not present is
+/// left untouched when the state is applied.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    pub zones: HashMap<Zone, Colour>,
+    pub hdmi_source: Option<HDMISource>,
+}
+
+/// A single difference between the current settings of a machine and a desired [`State`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Zone {
+        zone: Zone,
+        from: Option<Colour>,
+        to: Colour,
+    },
+    HdmiSource {
+        from: HDMISource,
+        to: HDMISource,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Zone { zone, from, to } => match from {
+                Some(from) => write!(f, "{zone}: {from} -> {to}"),
+                None => write!(f, "{zone}: ? -> {to}"),
+            },
+            Change::HdmiSource { from, to } => write!(f, "hdmi source: {from} -> {to}"),
+        }
+    }
+}
+
+/// Reasons why a [`State`] cannot be applied to this machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    NotAlienware,
+    MissingZone(Zone),
+    NoHdmi,
+    UnknownHdmiSource,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::NotAlienware => write!(f, "this machine has no alienware platform device"),
+            StateError::MissingZone(zone) => write!(f, "there are no {zone} LEDs on this machine"),
+            StateError::NoHdmi => write!(f, "there is no HDMI passthrough on this machine"),
+            StateError::UnknownHdmiSource => write!(f, "the HDMI source cannot be set to unknown"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl Alienware {
+    /// Get the current settings as a [`State`]
+    pub fn get_state(&self) -> std::io::Result<State> {
+        let zones = self.get_zone_colours()?;
+        let hdmi = self.get_hdmi()?;
+        Ok(State {
+            zones,
+            hdmi_source: if self.has_hdmi_source() {
+                Some(hdmi.source)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Check that every setting in a [`State`] is supported by this machine
+    pub fn validate_state(&self, state: &State) -> std::io::Result<Vec<StateError>> {
+        let mut errors = Vec::new();
+        if !self.is_alienware() {
+            errors.push(StateError::NotAlienware);
+            return Ok(errors);
+        }
+        let rgb_zones = self.get_rgb_zones()?;
+        let mut zones: Vec<&Zone> = state.zones.keys().collect();
+        zones.sort_by_key(|zone| **zone as u8);
+        for zone in zones {
+            if !rgb_zones.zones.contains_key(zone) {
+                errors.push(StateError::MissingZone(*zone));
+            }
+        }
+        match state.hdmi_source {
+            Some(HDMISource::Unknown) => errors.push(StateError::UnknownHdmiSource),
+            Some(_) if !self.has_hdmi_source() => errors.push(StateError::NoHdmi),
+            _ => {}
+        }
+        Ok(errors)
+    }
+
+    /// List the changes that would be needed to bring this machine to the desired [`State`]
+    pub fn diff_state(&self, state: &State) -> std::io::Result<Vec<Change>> {
+        let current = self.get_state()?;
+        let mut changes = Vec::new();
+        for zone in [Zone::Head, Zone::Left, Zone::Right] {
+            if let Some(to) = state.zones.get(&zone) {
+                let from = current.zones.get(&zone).copied();
+                if from != Some(*to) {
+                    changes.push(Change::Zone {
+                        zone,
+                        from,
+                        to: *to,
+                    });
+                }
+            }
+        }
+        if let Some(to) = state.hdmi_source {
+            let from = current.hdmi_source.unwrap_or(HDMISource::Unknown);
+            if from != to {
+                changes.push(Change::HdmiSource { from, to });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Apply a set of changes, as returned by [`Alienware::diff_state`]
+    pub fn apply_changes(&self, changes: &[Change]) -> std::io::Result<()> {
+        for change in changes {
+            match change {
+                Change::Zone { zone, to, .. } => {
+                    self.set_rgb_zone(*zone, to.red, to.green, to.blue)?
+                }
+                Change::HdmiSource { to, .. } => self.set_hdmi_source(*to)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Bring this machine to the desired [`State`], only writing the settings that differ, and return the changes made
+    pub fn apply_state(&self, state: &State) -> std::io::Result<Vec<Change>> {
+        let changes = self.diff_state(state)?;
+        self.apply_changes(&changes)?;
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::effects::MAX_LEVEL;
+    use crate::tests::{setup_aw, setup_not_aw};
+    use crate::{Alienware, Change, Colour, HDMISource, ParseError, State, StateError, Zone};
+    use std::fs::{read_to_string, remove_file};
+
+    #[test]
+    fn scale_and_mix() {
+        let colour = Colour::new(15, 8, 0);
+        assert_eq!(colour.scale(0.5), Colour::new(8, 4, 0));
+        assert_eq!(colour.scale(2.0), colour);
+        assert_eq!(colour.scale(f32::NAN), Colour::new(0, 0, 0));
+        assert_eq!(colour.mix(Colour::new(0, 0, 15), 0.5), Colour::new(8, 4, 8));
+        assert_eq!(colour.mix(Colour::new(0, 0, 15), -1.0), colour);
+    }
+
+    #[test]
+    fn colour_from_str() {
+        assert_eq!("Cyan".parse(), Ok(Colour::new(0, 15, 15)));
+        assert_eq!(" 0 15 15 ".parse(), Ok(Colour::new(0, 15, 15)));
+        assert_eq!("#00ff0a".parse(), Ok(Colour::new(0, 15, 1)));
+        assert_eq!("#00FFFF".parse(), "cyan".parse::<Colour>());
+        assert_eq!("#808080".parse(), Ok(Colour::new(8, 8, 8)));
+        assert_eq!("#00ff0".parse::<Colour>(), Err(ParseError::UnknownFormat));
+        assert_eq!("1 2".parse::<Colour>(), Err(ParseError::UnknownFormat));
+        assert_eq!("1 -2 3".parse::<Colour>(), Err(ParseError::UnknownFormat));
+        assert_eq!(
+            "256 0 0".parse::<Colour>(),
+            Err(ParseError::OutOfRange {
+                component: "red",
+                value: "256".to_string()
+            })
+        );
+        assert_eq!("2700K".parse(), Ok(Colour::new(15, 10, 5)));
+        assert_eq!(" 6500k ".parse(), Ok(Colour::new(15, 15, 15)));
+        assert_eq!(
+            "500K".parse::<Colour>(),
+            Err(ParseError::KelvinOutOfRange(500))
+        );
+        assert_eq!(
+            "99999999999K".parse::<Colour>(),
+            Err(ParseError::KelvinOutOfRange(u32::MAX))
+        );
+        assert_eq!("K".parse::<Colour>(), Err(ParseError::UnknownFormat));
+        assert_eq!("-2700K".parse::<Colour>(), Err(ParseError::UnknownFormat));
+    }
+
+    #[test]
+    fn kelvin() {
+        assert_eq!(Colour::from_kelvin(1000), Some(Colour::new(15, 4, 0)));
+        assert_eq!(Colour::from_kelvin(2700), Some(Colour::new(15, 10, 5)));
+        assert_eq!(Colour::from_kelvin(6500), Some(Colour::new(15, 15, 15)));
+        assert_eq!(Colour::from_kelvin(40000), Some(Colour::new(9, 11, 15)));
+        assert_eq!(Colour::from_kelvin(999), None);
+        assert_eq!(Colour::from_kelvin(40001), None);
+        // warmer light has less blue
+        let blue = |kelvin| Colour::from_kelvin(kelvin).unwrap().blue;
+        assert!((1000..6600)
+            .step_by(100)
+            .all(|kelvin| blue(kelvin) <= blue(kelvin + 100)));
+        // both ends of the range stay on the 0-15 scale
+        for kelvin in [Colour::MIN_KELVIN, Colour::MAX_KELVIN] {
+            let colour = Colour::from_kelvin(kelvin).unwrap();
+            assert!(
+                [colour.red, colour.green, colour.blue]
+                    .iter()
+                    .all(|component| *component <= MAX_LEVEL),
+                "{kelvin}K gives {colour:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn get_state() {
+        let alienware = Alienware::test(setup_aw("get_state"));
+        let state = alienware.get_state().unwrap();
+        assert_eq!(state.zones.len(), 3);
+        assert_eq!(state.zones[&Zone::Head], Colour::new(0, 0, 15));
+        assert_eq!(state.hdmi_source, Some(HDMISource::Gpu));
+    }
+
+    #[test]
+    fn validate_state() {
+        let platform = setup_aw("validate_state");
+        remove_file(format!("{platform}/rgb_zones/zone02")).unwrap();
+        let alienware = Alienware::test(platform);
+        let mut state = State::default();
+        state.zones.insert(Zone::Head, Colour::new(15, 0, 0));
+        assert!(alienware.validate_state(&state).unwrap().is_empty());
+        state.zones.insert(Zone::Right, Colour::new(15, 0, 0));
+        state.hdmi_source = Some(HDMISource::Unknown);
+        assert_eq!(
+            alienware.validate_state(&state).unwrap(),
+            vec![
+                StateError::MissingZone(Zone::Right),
+                StateError::UnknownHdmiSource
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_state_not_alienware() {
+        let alienware = Alienware::test(setup_not_aw("validate_state_not_alienware"));
+        assert_eq!(
+            alienware.validate_state(&State::default()).unwrap(),
+            vec![StateError::NotAlienware]
+        );
+    }
+
+    #[test]
+    fn apply_state() {
+        let platform = setup_aw("apply_state");
+        let alienware = Alienware::test(platform.clone());
+        let mut state = State::default();
+        state.zones.insert(Zone::Head, Colour::new(0, 0, 15));
+        state.zones.insert(Zone::Left, Colour::new(15, 7, 0));
+        state.hdmi_source = Some(HDMISource::Cable);
+        let changes = alienware.apply_state(&state).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Zone {
+                    zone: Zone::Left,
+                    from: Some(Colour::new(0, 15, 0)),
+                    to: Colour::new(15, 7, 0)
+                },
+                Change::HdmiSource {
+                    from: HDMISource::Gpu,
+                    to: HDMISource::Cable
+                }
+            ]
+        );
+        assert_eq!(
+            read_to_string(format!("{platform}/rgb_zones/zone01")).unwrap(),
+            "0f0700"
+        );
+        assert_eq!(
+            read_to_string(format!("{platform}/rgb_zones/zone00")).unwrap(),
+            "red: 0, green: 0, blue: 15"
+        );
+        assert_eq!(
+            read_to_string(format!("{platform}/hdmi/source")).unwrap(),
+            "cable"
+        );
+    }
+}
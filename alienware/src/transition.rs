@@ -0,0 +1,496 @@
+use crate::effects::{run_with, Effect, Frame};
+use crate::{Alienware, Change, Colour, FrameScheduler, MonotonicClock, ParseError, Zone};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// The number of times a second that the zones are written during a transition
+const TRANSITION_FPS: u32 = 50;
+
+/// The curve that a fade between two colours follows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    /// Change to the new colour at the start and hold it
+    Step,
+    Linear,
+    EaseIn,
+    EaseOut,
+    /// Start and finish slowly, which is the default for a fade
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// How far a fade has got to the new colour at a fraction of its duration, both from 0 to 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        match self {
+            Easing::Step => 1.0,
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+impl fmt::Display for Easing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Easing::Step => write!(f, "step"),
+            Easing::Linear => write!(f, "linear"),
+            Easing::EaseIn => write!(f, "ease-in"),
+            Easing::EaseOut => write!(f, "ease-out"),
+            Easing::EaseInOut => write!(f, "ease-in-out"),
+        }
+    }
+}
+
+impl FromStr for Easing {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Easing, ParseError> {
+        match input.trim().to_lowercase().as_str() {
+            "step" => Ok(Easing::Step),
+            "linear" => Ok(Easing::Linear),
+            "ease-in" => Ok(Easing::EaseIn),
+            "ease-out" => Ok(Easing::EaseOut),
+            "ease-in-out" => Ok(Easing::EaseInOut),
+            _ => Err(ParseError::UnknownOption(input.to_string())),
+        }
+    }
+}
+
+/// The colour space that a fade between two colours moves through.  The components are treated as linear light, as
+/// they set how brightly the LEDs are driven, so no gamma curve is applied before converting them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColourSpace {
+    /// A straight line between the red, green and blue components
+    Rgb,
+    /// The shorter way round the colour wheel, so that the colours part way through stay saturated
+    Hsv,
+    /// The OKLab perceptual colour space, so that the lightness and hue change evenly to the eye, which is the default
+    #[default]
+    Oklab,
+}
+
+impl ColourSpace {
+    /// A colour part way between two colours, where an amount of 0 is the first colour and 1 is the second
+    pub fn mix(self, from: Colour, to: Colour, amount: f32) -> Colour {
+        let amount = if amount.is_nan() {
+            0.0
+        } else {
+            amount.clamp(0.0, 1.0)
+        };
+        // the ends are exact, whatever rounding the conversions make
+        if amount == 0.0 {
+            return from;
+        }
+        if amount == 1.0 {
+            return to;
+        }
+        match self {
+            ColourSpace::Rgb => from.mix(to, amount),
+            ColourSpace::Hsv => mix_hsv(from, to, f64::from(amount)),
+            ColourSpace::Oklab => {
+                let (from, to) = (to_oklab(from), to_oklab(to));
+                let mix = |i: usize| from[i] + (to[i] - from[i]) * f64::from(amount);
+                from_oklab([mix(0), mix(1), mix(2)])
+            }
+        }
+    }
+}
+
+impl fmt::Display for ColourSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColourSpace::Rgb => write!(f, "rgb"),
+            ColourSpace::Hsv => write!(f, "hsv"),
+            ColourSpace::Oklab => write!(f, "oklab"),
+        }
+    }
+}
+
+impl FromStr for ColourSpace {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<ColourSpace, ParseError> {
+        match input.trim().to_lowercase().as_str() {
+            "rgb" => Ok(ColourSpace::Rgb),
+            "hsv" => Ok(ColourSpace::Hsv),
+            "oklab" => Ok(ColourSpace::Oklab),
+            _ => Err(ParseError::UnknownOption(input.to_string())),
+        }
+    }
+}
+
+/// How a zone fades from one colour to another
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub duration: Duration,
+    pub easing: Easing,
+    pub space: ColourSpace,
+}
+
+impl Transition {
+    /// Construct a fade that takes a duration, following the default [`Easing`] through the default [`ColourSpace`]
+    pub fn new(duration: Duration) -> Transition {
+        Transition {
+            duration,
+            easing: Easing::default(),
+            space: ColourSpace::default(),
+        }
+    }
+
+    /// Set the curve that the fade follows
+    pub fn easing(mut self, easing: Easing) -> Transition {
+        self.easing = easing;
+        self
+    }
+
+    /// Set the colour space that the fade moves through
+    pub fn space(mut self, space: ColourSpace) -> Transition {
+        self.space = space;
+        self
+    }
+
+    /// The colour at a time since the fade started
+    pub fn colour_at(&self, from: Colour, to: Colour, t: Duration) -> Colour {
+        let amount = if self.duration.is_zero() {
+            1.0
+        } else {
+            t.as_secs_f32() / self.duration.as_secs_f32()
+        };
+        self.space.mix(from, to, self.easing.apply(amount))
+    }
+}
+
+/// A fade of some zones from one colour to another, shown as an effect
+struct Fade {
+    transition: Transition,
+    zones: HashMap<Zone, (Colour, Colour)>,
+}
+
+impl Effect for Fade {
+    fn frame(&mut self, t: Duration, zones: &[Zone]) -> Frame {
+        zones
+            .iter()
+            .filter_map(|zone| {
+                let (from, to) = self.zones.get(zone)?;
+                Some((*zone, self.transition.colour_at(*from, *to, t)))
+            })
+            .collect()
+    }
+}
+
+impl Alienware {
+    /// Fade a zone from the colour it shows now to another, returning once it is done
+    pub fn transition(
+        &self,
+        zone: Zone,
+        to: Colour,
+        transition: Transition,
+    ) -> std::io::Result<()> {
+        let from = self.get_zone_colours()?.get(&zone).copied();
+        self.transition_changes(&[Change::Zone { zone, from, to }], transition)
+    }
+
+    /// Make a set of changes, as returned by [`Alienware::diff_state`], fading every zone to its new colour at the same
+    /// time and returning once they are done.  Zones whose colour is not known change straight away.
+    pub fn transition_changes(
+        &self,
+        changes: &[Change],
+        transition: Transition,
+    ) -> std::io::Result<()> {
+        self.transition_changes_until(changes, transition, &AtomicBool::new(true))
+    }
+
+    /// Make a set of changes as [`Alienware::transition_changes`] does, stopping part way through the fade if `running`
+    /// is cleared, such as by a Ctrl-C handler
+    pub fn transition_changes_until(
+        &self,
+        changes: &[Change],
+        transition: Transition,
+        running: &AtomicBool,
+    ) -> std::io::Result<()> {
+        let mut fade = Fade {
+            transition,
+            zones: HashMap::new(),
+        };
+        for change in changes {
+            match change {
+                Change::Zone { zone, from, to } => {
+                    fade.zones.insert(*zone, (from.unwrap_or(*to), *to));
+                }
+                Change::HdmiSource { to, .. } => self.set_hdmi_source(*to)?,
+            }
+        }
+        if fade.zones.is_empty() {
+            return Ok(());
+        }
+        let zones: Vec<Zone> = fade.zones.keys().copied().collect();
+        let clock = MonotonicClock::new();
+        run_with(
+            &mut fade,
+            self,
+            &zones,
+            &mut FrameScheduler::new(&clock, TRANSITION_FPS),
+            running,
+            Some(transition.duration),
+        )
+    }
+}
+
+/// Mix two colours round the colour wheel, where a black or grey colour takes the hue of the other one
+fn mix_hsv(from: Colour, to: Colour, amount: f64) -> Colour {
+    let (from_hue, from_saturation, from_value) = to_hsv(from);
+    let (to_hue, to_saturation, to_value) = to_hsv(to);
+    let (from_hue, from_saturation) = if from_value == 0.0 {
+        (to_hue, to_saturation)
+    } else if from_saturation == 0.0 {
+        (to_hue, from_saturation)
+    } else {
+        (from_hue, from_saturation)
+    };
+    let (to_hue, to_saturation) = if to_value == 0.0 {
+        (from_hue, from_saturation)
+    } else if to_saturation == 0.0 {
+        (from_hue, to_saturation)
+    } else {
+        (to_hue, to_saturation)
+    };
+    let mut turn = to_hue - from_hue;
+    if turn > 0.5 {
+        turn -= 1.0;
+    } else if turn < -0.5 {
+        turn += 1.0;
+    }
+    from_hsv(
+        from_hue + turn * amount,
+        from_saturation + (to_saturation - from_saturation) * amount,
+        from_value + (to_value - from_value) * amount,
+    )
+}
+
+/// The hue from 0 up to 1, the saturation from 0 to 1 and the value, on the same scale as the components, of a colour
+fn to_hsv(colour: Colour) -> (f64, f64, f64) {
+    let (red, green, blue) = components(colour);
+    let max = red.max(green).max(blue);
+    let delta = max - red.min(green).min(blue);
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        ((green - blue) / delta).rem_euclid(6.0) / 6.0
+    } else if max == green {
+        ((blue - red) / delta + 2.0) / 6.0
+    } else {
+        ((red - green) / delta + 4.0) / 6.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+fn from_hsv(hue: f64, saturation: f64, value: f64) -> Colour {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let f = sector.fract();
+    let p = value * (1.0 - saturation);
+    let q = value * (1.0 - saturation * f);
+    let t = value * (1.0 - saturation * (1.0 - f));
+    let (red, green, blue) = match sector as u32 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+    from_components(red, green, blue)
+}
+
+/// Convert a colour to OKLab, treating the components as linear light where 255 is full brightness
+fn to_oklab(colour: Colour) -> [f64; 3] {
+    let (red, green, blue) = components(colour);
+    let (red, green, blue) = (red / 255.0, green / 255.0, blue / 255.0);
+    let l = (0.412_221_470_8 * red + 0.536_332_536_3 * green + 0.051_445_992_9 * blue).cbrt();
+    let m = (0.211_903_498_2 * red + 0.680_699_545_1 * green + 0.107_396_956_6 * blue).cbrt();
+    let s = (0.088_302_461_9 * red + 0.281_718_837_6 * green + 0.629_978_700_5 * blue).cbrt();
+    [
+        0.210_454_255_3 * l + 0.793_617_785_0 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205_0 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766_0 * s,
+    ]
+}
+
+fn from_oklab([lightness, a, b]: [f64; 3]) -> Colour {
+    let l = (lightness + 0.396_337_777_4 * a + 0.215_803_757_3 * b).powi(3);
+    let m = (lightness - 0.105_561_345_8 * a - 0.063_854_172_8 * b).powi(3);
+    let s = (lightness - 0.089_484_177_5 * a - 1.291_485_548_0 * b).powi(3);
+    from_components(
+        (4.076_741_662_1 * l - 3.307_711_591_3 * m + 0.230_969_929_2 * s) * 255.0,
+        (-1.268_438_004_6 * l + 2.609_757_401_1 * m - 0.341_319_396_5 * s) * 255.0,
+        (-0.004_196_086_3 * l - 0.703_418_614_7 * m + 1.707_614_701_0 * s) * 255.0,
+    )
+}
+
+fn components(colour: Colour) -> (f64, f64, f64) {
+    (
+        f64::from(colour.red),
+        f64::from(colour.green),
+        f64::from(colour.blue),
+    )
+}
+
+fn from_components(red: f64, green: f64, blue: f64) -> Colour {
+    let component = |value: f64| value.round().clamp(0.0, 255.0) as u8;
+    Colour::new(component(red), component(green), component(blue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColourSpace, Easing, Transition};
+    use crate::tests::setup_aw;
+    use crate::{Alienware, Change, Colour, HDMISource, Zone};
+    use std::fs::read_to_string;
+    use std::sync::atomic::AtomicBool;
+    use std::time::{Duration, Instant};
+
+    const RED: Colour = Colour::new(15, 0, 0);
+    const BLUE: Colour = Colour::new(0, 0, 15);
+    const WHITE: Colour = Colour::new(15, 15, 15);
+    const BLACK: Colour = Colour::new(0, 0, 0);
+
+    #[test]
+    fn easing() {
+        for easing in [
+            Easing::Step,
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.to_string().parse(), Ok(easing));
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+        assert_eq!(Easing::Step.apply(0.0), 1.0);
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::Linear.apply(f32::NAN), 0.0);
+    }
+
+    #[test]
+    fn colour_spaces() {
+        for space in [ColourSpace::Rgb, ColourSpace::Hsv, ColourSpace::Oklab] {
+            assert_eq!(space.to_string().parse(), Ok(space));
+            for (from, to) in [(RED, BLUE), (WHITE, BLACK), (Colour::new(3, 200, 17), RED)] {
+                assert_eq!(space.mix(from, to, 0.0), from, "{space}");
+                assert_eq!(space.mix(from, to, 1.0), to, "{space}");
+                assert_eq!(space.mix(from, to, 0.001), from, "{space}");
+            }
+        }
+        assert_eq!(ColourSpace::Rgb.mix(RED, BLUE, 0.5), Colour::new(8, 0, 8));
+        // round the wheel the short way, through magenta rather than green
+        assert_eq!(ColourSpace::Hsv.mix(RED, BLUE, 0.5), Colour::new(15, 0, 15));
+        assert_eq!(ColourSpace::Hsv.mix(RED, BLACK, 0.5), Colour::new(8, 0, 0));
+        assert_eq!(ColourSpace::Hsv.mix(WHITE, RED, 0.5), Colour::new(15, 8, 8));
+        // half way in perceived lightness is much less than half the light
+        assert_eq!(
+            ColourSpace::Oklab.mix(WHITE, BLACK, 0.5),
+            Colour::new(2, 2, 2)
+        );
+        assert_eq!(ColourSpace::Rgb.mix(WHITE, BLACK, f32::NAN), WHITE);
+        assert!("lab".parse::<ColourSpace>().is_err());
+    }
+
+    #[test]
+    fn colour_at() {
+        let transition = Transition::new(Duration::from_secs(2)).space(ColourSpace::Hsv);
+        assert_eq!(transition.colour_at(RED, BLUE, Duration::ZERO), RED);
+        assert_eq!(
+            transition.colour_at(RED, BLUE, Duration::from_secs(1)),
+            Colour::new(15, 0, 15)
+        );
+        assert_eq!(
+            transition.colour_at(RED, BLUE, Duration::from_secs(3)),
+            BLUE
+        );
+        assert_eq!(
+            Transition::new(Duration::ZERO),
+            Transition {
+                duration: Duration::ZERO,
+                easing: Easing::EaseInOut,
+                space: ColourSpace::Oklab,
+            }
+        );
+        let instant = Transition::new(Duration::ZERO);
+        assert_eq!(instant.colour_at(RED, BLUE, Duration::ZERO), BLUE);
+        let step = Transition::new(Duration::from_secs(1)).easing(Easing::Step);
+        assert_eq!(step.colour_at(RED, BLUE, Duration::ZERO), BLUE);
+    }
+
+    #[test]
+    fn transition() {
+        let platform = setup_aw("transition");
+        let aw = Alienware::test(platform.clone());
+        let start = Instant::now();
+        aw.transition(
+            Zone::Head,
+            RED,
+            Transition::new(Duration::from_millis(60)).space(ColourSpace::Hsv),
+        )
+        .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(
+            read_to_string(format!("{platform}/rgb_zones/zone00")).unwrap(),
+            "0f0000"
+        );
+    }
+
+    #[test]
+    fn transition_changes() {
+        let platform = setup_aw("transition_changes");
+        let aw = Alienware::test(platform.clone());
+        let changes = [
+            Change::Zone {
+                zone: Zone::Left,
+                from: Some(BLACK),
+                to: WHITE,
+            },
+            Change::Zone {
+                zone: Zone::Right,
+                from: None,
+                to: BLUE,
+            },
+            Change::HdmiSource {
+                from: HDMISource::Gpu,
+                to: HDMISource::Cable,
+            },
+        ];
+        let transition = Transition::new(Duration::from_millis(40)).space(ColourSpace::Oklab);
+        aw.transition_changes(&changes, transition).unwrap();
+        let zone = |zone: &str| read_to_string(format!("{platform}/rgb_zones/{zone}")).unwrap();
+        assert_eq!(zone("zone01"), "0f0f0f");
+        assert_eq!(zone("zone02"), "00000f");
+        assert_eq!(
+            read_to_string(format!("{platform}/hdmi/source")).unwrap(),
+            "cable"
+        );
+
+        // a fade that is stopped returns straight away, leaving the zones where they had got to
+        let start = Instant::now();
+        let stopped = AtomicBool::new(false);
+        let fade = [Change::Zone {
+            zone: Zone::Left,
+            from: Some(WHITE),
+            to: RED,
+        }];
+        aw.transition_changes_until(&fade, Transition::new(Duration::from_secs(10)), &stopped)
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(zone("zone01"), "0f0f0f");
+    }
+}
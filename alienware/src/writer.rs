@@ -0,0 +1,164 @@
+use crate::{Alienware, Colour, Zone};
+use std::collections::HashMap;
+use std::fs::File;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Writes the colours of the LED zones for effects that change them many times a second.  The sysfs files are kept
+/// open between writes, a colour that is already showing is not written again, and no zone is written more often than
+/// the maximum rate, with any colours set in between coalesced into the latest one.
+pub struct ZoneWriter<'a> {
+    aw: &'a Alienware,
+    min_interval: Duration,
+    zones: HashMap<Zone, ZoneHandle>,
+}
+
+/// The open file and write history of a single zone
+#[derive(Default)]
+struct ZoneHandle {
+    file: Option<File>,
+    written: Option<Colour>,
+    pending: Option<Colour>,
+    last_write: Option<Instant>,
+}
+
+impl<'a> ZoneWriter<'a> {
+    /// Construct a writer for the zones of a machine, with no limit on the write rate
+    pub fn new(aw: &'a Alienware) -> ZoneWriter<'a> {
+        ZoneWriter {
+            aw,
+            min_interval: Duration::ZERO,
+            zones: HashMap::new(),
+        }
+    }
+
+    /// Limit the number of writes a second to each zone, where zero removes the limit
+    pub fn max_rate(mut self, writes_per_second: u32) -> ZoneWriter<'a> {
+        self.min_interval = if writes_per_second == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(1) / writes_per_second
+        };
+        self
+    }
+
+    /// Set the colour of a zone, writing it straight away unless the zone was written too recently, in which case it
+    /// is held until [`ZoneWriter::poll`] or [`ZoneWriter::flush`] is called
+    pub fn set(&mut self, zone: Zone, colour: Colour) -> std::io::Result<()> {
+        let handle = self.zones.entry(zone).or_default();
+        handle.pending = if handle.written == Some(colour) {
+            None
+        } else {
+            Some(colour)
+        };
+        self.write_due(zone, Instant::now()).map(|_| ())
+    }
+
+    /// Write every held colour that is due, returning how long it is until the next held colour will be due
+    pub fn poll(&mut self) -> std::io::Result<Option<Duration>> {
+        let now = Instant::now();
+        let zones: Vec<Zone> = self.zones.keys().copied().collect();
+        let mut next = None;
+        for zone in zones {
+            if let Some(wait) = self.write_due(zone, now)? {
+                next = Some(next.map_or(wait, |next: Duration| next.min(wait)));
+            }
+        }
+        Ok(next)
+    }
+
+    /// Write every held colour, waiting until each one is due, and keep the colours in the brightness file if there is
+    /// one
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        while let Some(wait) = self.poll()? {
+            sleep(wait);
+        }
+        self.aw.save_brightness_or_warn();
+        Ok(())
+    }
+
+    /// Whether there are colours held back by the rate limit
+    pub fn is_pending(&self) -> bool {
+        self.zones.values().any(|handle| handle.pending.is_some())
+    }
+
+    /// Write the held colour of a zone if it is due, otherwise returning how long it is until it will be
+    fn write_due(&mut self, zone: Zone, now: Instant) -> std::io::Result<Option<Duration>> {
+        let handle = match self.zones.get_mut(&zone) {
+            Some(handle) => handle,
+            None => return Ok(None),
+        };
+        let colour = match handle.pending {
+            Some(colour) => colour,
+            None => return Ok(None),
+        };
+        if let Some(last_write) = handle.last_write {
+            let due = last_write + self.min_interval;
+            if due > now {
+                return Ok(Some(due - now));
+            }
+        }
+        self.aw
+            .write_rgb_zone_with(zone, colour, &mut handle.file)?;
+        handle.written = Some(colour);
+        handle.pending = None;
+        handle.last_write = Some(now);
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZoneWriter;
+    use crate::tests::setup_aw;
+    use crate::{Alienware, Colour, Zone};
+    use std::fs::{read_to_string, write};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn skip_unchanged() {
+        let platform = setup_aw("zone_writer_skip_unchanged");
+        let aw = Alienware::test(platform.clone());
+        let zone00 = format!("{platform}/rgb_zones/zone00");
+        let mut writer = ZoneWriter::new(&aw);
+        writer.set(Zone::Head, Colour::new(15, 0, 0)).unwrap();
+        assert_eq!(read_to_string(&zone00).unwrap(), "0f0000");
+        write(&zone00, "000000").unwrap();
+        writer.set(Zone::Head, Colour::new(15, 0, 0)).unwrap();
+        assert_eq!(read_to_string(&zone00).unwrap(), "000000");
+        writer.set(Zone::Head, Colour::new(0, 15, 0)).unwrap();
+        assert_eq!(read_to_string(&zone00).unwrap(), "000f00");
+    }
+
+    #[test]
+    fn coalesce() {
+        let platform = setup_aw("zone_writer_coalesce");
+        let aw = Alienware::test(platform.clone());
+        let zone01 = format!("{platform}/rgb_zones/zone01");
+        let mut writer = ZoneWriter::new(&aw).max_rate(20);
+        let start = Instant::now();
+        writer.set(Zone::Left, Colour::new(1, 1, 1)).unwrap();
+        writer.set(Zone::Left, Colour::new(2, 2, 2)).unwrap();
+        writer.set(Zone::Left, Colour::new(3, 3, 3)).unwrap();
+        assert_eq!(read_to_string(&zone01).unwrap(), "010101");
+        assert!(writer.is_pending());
+        assert!(writer.poll().unwrap().is_some());
+        writer.flush().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(!writer.is_pending());
+        assert_eq!(read_to_string(&zone01).unwrap(), "030303");
+    }
+
+    #[test]
+    fn dry_run() {
+        let writes = Arc::new(Mutex::new(0));
+        let counted = writes.clone();
+        let aw = Alienware::test(setup_aw("zone_writer_dry_run"))
+            .dry_run(move |_| *counted.lock().unwrap() += 1);
+        let mut writer = ZoneWriter::new(&aw);
+        writer.set(Zone::Right, Colour::new(15, 0, 0)).unwrap();
+        writer.set(Zone::Right, Colour::new(15, 0, 0)).unwrap();
+        assert_eq!(*writes.lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Runs every getter against a corpus of sysfs files in the formats produced by different kernels and models, see
+//! `tests/fixtures/README.md`
+
+use alienware::{Alienware, Colour, HDMICableState, HDMISource, Zone, HDMI};
+
+/// What the getters should return for a fixture
+struct Expected {
+    fixture: &'static str,
+    is_alienware: bool,
+    hdmi: HDMI,
+    has_hdmi_source: bool,
+    zones: &'static [(Zone, Colour)],
+}
+
+const UNKNOWN_HDMI: HDMI = HDMI {
+    source: HDMISource::Unknown,
+    cable_state: HDMICableState::Unknown,
+    exists: true,
+};
+
+const FIXTURES: &[Expected] = &[
+    Expected {
+        fixture: "original",
+        is_alienware: true,
+        hdmi: HDMI {
+            source: HDMISource::Gpu,
+            cable_state: HDMICableState::Connected,
+            exists: true,
+        },
+        has_hdmi_source: true,
+        zones: &[
+            (Zone::Head, Colour::new(0, 0, 15)),
+            (Zone::Left, Colour::new(0, 15, 0)),
+            (Zone::Right, Colour::new(15, 0, 0)),
+        ],
+    },
+    Expected {
+        fixture: "alpha-r2-newline",
+        is_alienware: true,
+        hdmi: HDMI {
+            source: HDMISource::Cable,
+            cable_state: HDMICableState::Unconnected,
+            exists: true,
+        },
+        has_hdmi_source: true,
+        zones: &[
+            (Zone::Head, Colour::new(15, 0, 15)),
+            (Zone::Left, Colour::new(0, 15, 15)),
+        ],
+    },
+    Expected {
+        fixture: "hdmi-unknown",
+        is_alienware: true,
+        hdmi: UNKNOWN_HDMI,
+        has_hdmi_source: true,
+        zones: &[(Zone::Head, Colour::new(255, 128, 0))],
+    },
+    Expected {
+        fixture: "whitespace",
+        is_alienware: true,
+        hdmi: HDMI {
+            source: HDMISource::Gpu,
+            cable_state: HDMICableState::Connected,
+            exists: true,
+        },
+        has_hdmi_source: true,
+        zones: &[
+            (Zone::Head, Colour::new(1, 2, 3)),
+            (Zone::Left, Colour::new(4, 5, 6)),
+            (Zone::Right, Colour::new(7, 8, 9)),
+        ],
+    },
+    Expected {
+        fixture: "hex-zones",
+        is_alienware: true,
+        hdmi: UNKNOWN_HDMI,
+        has_hdmi_source: false,
+        zones: &[
+            (Zone::Head, Colour::new(15, 7, 0)),
+            (Zone::Left, Colour::new(0, 255, 10)),
+            (Zone::Right, Colour::new(10, 11, 12)),
+        ],
+    },
+    Expected {
+        fixture: "no-hdmi",
+        is_alienware: true,
+        hdmi: UNKNOWN_HDMI,
+        has_hdmi_source: false,
+        zones: &[
+            (Zone::Head, Colour::new(0, 0, 0)),
+            (Zone::Left, Colour::new(0, 0, 0)),
+        ],
+    },
+    Expected {
+        fixture: "empty-platform",
+        is_alienware: true,
+        hdmi: UNKNOWN_HDMI,
+        has_hdmi_source: false,
+        zones: &[],
+    },
+    Expected {
+        fixture: "not-alienware",
+        is_alienware: false,
+        hdmi: HDMI {
+            source: HDMISource::Unknown,
+            cable_state: HDMICableState::Unknown,
+            exists: false,
+        },
+        has_hdmi_source: false,
+        zones: &[],
+    },
+];
+
+fn alienware(fixture: &str) -> Alienware {
+    Alienware::with_platform(format!(
+        "{}/tests/fixtures/{fixture}/alienware-wmi",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+}
+
+#[test]
+fn is_alienware() {
+    for expected in FIXTURES {
+        assert_eq!(
+            alienware(expected.fixture).is_alienware(),
+            expected.is_alienware,
+            "{}",
+            expected.fixture
+        );
+    }
+}
+
+#[test]
+fn get_hdmi() {
+    for expected in FIXTURES {
+        assert_eq!(
+            alienware(expected.fixture).get_hdmi().unwrap(),
+            expected.hdmi,
+            "{}",
+            expected.fixture
+        );
+    }
+}
+
+#[test]
+fn has_hdmi() {
+    for expected in FIXTURES {
+        assert_eq!(
+            alienware(expected.fixture).has_hdmi(),
+            expected.hdmi.exists,
+            "{}",
+            expected.fixture
+        );
+    }
+}
+
+#[test]
+fn get_rgb_zones() {
+    for expected in FIXTURES {
+        let rgb_zones = alienware(expected.fixture).get_rgb_zones().unwrap();
+        assert_eq!(
+            rgb_zones.exists, expected.is_alienware,
+            "{}",
+            expected.fixture
+        );
+        assert_eq!(
+            rgb_zones.zones.len(),
+            expected.zones.len(),
+            "{}",
+            expected.fixture
+        );
+        for (zone, colour) in expected.zones {
+            let rgb_zone = rgb_zones.zones.get(zone).unwrap();
+            assert_eq!(rgb_zone.zone, *zone, "{}", expected.fixture);
+            assert_eq!(rgb_zone.colour(), *colour, "{} {zone}", expected.fixture);
+        }
+    }
+}
+
+#[test]
+fn has_rgb_zones() {
+    for expected in FIXTURES {
+        assert_eq!(
+            alienware(expected.fixture).has_rgb_zones(),
+            expected.is_alienware,
+            "{}",
+            expected.fixture
+        );
+    }
+}
+
+#[test]
+fn get_state() {
+    for expected in FIXTURES {
+        let state = alienware(expected.fixture).get_state().unwrap();
+        assert_eq!(
+            state.hdmi_source,
+            if expected.has_hdmi_source {
+                Some(expected.hdmi.source)
+            } else {
+                None
+            },
+            "{}",
+            expected.fixture
+        );
+        assert_eq!(
+            state.zones.len(),
+            expected.zones.len(),
+            "{}",
+            expected.fixture
+        );
+        for (zone, colour) in expected.zones {
+            assert_eq!(
+                state.zones.get(zone),
+                Some(colour),
+                "{} {zone}",
+                expected.fixture
+            );
+        }
+    }
+}
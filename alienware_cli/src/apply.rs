@@ -0,0 +1,286 @@
+use crate::cli::Format;
+use crate::print_error;
+use crate::restore::stop_on_signal;
+use crate::set::apply_changes;
+use crate::zones::parse_zone;
+use alienware::document::{self, colour_from_json};
+use alienware::{
+    Alienware, HDMISource, ReconcileEvent, Reconciler, SharedAlienware, State, Transition,
+};
+use jzon::JsonValue;
+use std::fs::read_to_string;
+use std::io::{stdin, Read};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// Apply a JSON or TOML state document to the machine, returning the exit code
+pub fn apply(aw: &Alienware, file: &str, format: Option<Format>, fade: Option<Transition>) -> i32 {
+    match load_state(file, format) {
+        Some(state) => apply_state(aw, &state, fade, &AtomicBool::new(true)),
+        None => 1,
+    }
+}
+
+/// Put back the settings of the machine that differ from a JSON or TOML state document, logging each one, either once
+/// or at an interval until stopped by a signal, returning the exit code
+pub fn reconcile(
+    aw: &SharedAlienware,
+    file: &str,
+    format: Option<Format>,
+    interval: Option<Duration>,
+) -> i32 {
+    let state = match load_state(file, format) {
+        Some(state) => state,
+        None => return 1,
+    };
+    if !check_state(aw.unlocked(), &state) {
+        return 1;
+    }
+    let reconciler = Reconciler::new(state);
+    let interval = match interval {
+        Some(interval) if interval.is_zero() => {
+            println!("The interval must be longer than zero");
+            return 1;
+        }
+        Some(interval) => interval,
+        None => {
+            return match aw.lock().and_then(|aw| reconciler.reconcile(&aw)) {
+                Ok(changes) => {
+                    if changes.is_empty() {
+                        println!("No drift from the state document");
+                    }
+                    for change in changes {
+                        println!("Drift: {change}");
+                    }
+                    0
+                }
+                Err(x) => {
+                    print_error("Problem reconciling the state", &x);
+                    1
+                }
+            };
+        }
+    };
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    println!("Reconciling every {interval:?}, press Ctrl-C to stop");
+    reconciler
+        .interval(interval)
+        .run(aw, &running, |event| match event {
+            ReconcileEvent::Drift(drift) => println!("Drift: {}", drift.change),
+            ReconcileEvent::Error { error, .. } => {
+                println!("Problem reconciling the state, trying again in {interval:?}: {error}")
+            }
+        });
+    0
+}
+
+/// Read and parse a state document, printing the reason if it cannot be
+fn load_state(file: &str, format: Option<Format>) -> Option<State> {
+    let contents = match read_document(file) {
+        Ok(contents) => contents,
+        Err(x) if file == "-" => {
+            println!("Problem reading the state document from stdin: {x}");
+            return None;
+        }
+        Err(x) => {
+            println!("Problem reading the state document {file}: {x}");
+            return None;
+        }
+    };
+    let format = format
+        .map(Format::into)
+        .or_else(|| document::Format::from_name(file));
+    match document::parse(contents.as_str(), format)
+        .and_then(|value| Ok((state_from_json(&value)?, has_brightness(&value))))
+    {
+        Ok((state, brightness)) => {
+            if brightness {
+                println!("The brightness and effective colours of the LEDs in the state document are ignored, the colours are set at the current brightness");
+            }
+            Some(state)
+        }
+        Err(x) => {
+            println!("Invalid state document: {x}");
+            None
+        }
+    }
+}
+
+/// Check that a state can be applied to the machine, printing the reasons if it cannot
+fn check_state(aw: &Alienware, state: &State) -> bool {
+    match aw.validate_state(state) {
+        Ok(errors) if errors.is_empty() => true,
+        Ok(errors) => {
+            println!("The state document cannot be applied to this machine:");
+            for error in errors {
+                println!("    {error}");
+            }
+            false
+        }
+        Err(x) => {
+            print_error("Problem getting the current state", &x);
+            false
+        }
+    }
+}
+
+/// Apply a state to the machine, printing the changes that it makes and returning the exit code.  A fade to the new
+/// colours stops part way through if `running` is cleared.
+pub fn apply_state(
+    aw: &Alienware,
+    state: &State,
+    fade: Option<Transition>,
+    running: &AtomicBool,
+) -> i32 {
+    if !check_state(aw, state) {
+        return 1;
+    }
+
+    let changes = match aw.diff_state(state) {
+        Ok(changes) => changes,
+        Err(x) => {
+            print_error("Problem getting the current state", &x);
+            return 1;
+        }
+    };
+    if changes.is_empty() {
+        println!("No changes to apply");
+        return 0;
+    }
+    println!("Changes:");
+    for change in changes.iter() {
+        println!("    {change}");
+    }
+    match apply_changes(aw, &changes, fade, running) {
+        Ok(()) => 0,
+        Err(x) => {
+            print_error("Problem applying the state", &x);
+            1
+        }
+    }
+}
+
+/// Read a document from a file, or from stdin if the file is `-`
+pub fn read_document(file: &str) -> std::io::Result<String> {
+    if file == "-" {
+        let mut contents = String::new();
+        stdin().read_to_string(&mut contents)?;
+        Ok(contents)
+    } else {
+        read_to_string(file)
+    }
+}
+
+/// Whether the LEDs of a state document have the brightness or effective colour written by `alienware-cli -jl`, which
+/// are only reported and are not set by applying it
+fn has_brightness(value: &JsonValue) -> bool {
+    value["leds"]
+        .entries()
+        .any(|(_, zone)| zone.has_key("brightness") || zone.has_key("effective"))
+}
+
+/// Read a state document in the layout produced by `alienware-cli -jlc`.  The brightness and effective colour of each
+/// LED are ignored, as the colours are set at the current brightness.
+pub fn state_from_json(value: &JsonValue) -> Result<State, String> {
+    if !value.is_object() {
+        return Err("the document must be an object".to_string());
+    }
+    let mut state = State::default();
+    for (key, value) in value.entries() {
+        match key {
+            "leds" => {
+                if !value.is_object() {
+                    return Err("leds must be an object".to_string());
+                }
+                for (zone, value) in value.entries() {
+                    if zone == "exists" {
+                        continue;
+                    }
+                    let zone = parse_zone(zone).ok_or(format!("unknown LED zone '{zone}'"))?;
+                    state.zones.insert(
+                        zone,
+                        colour_from_json(value).map_err(|x| format!("{x} for {zone}"))?,
+                    );
+                }
+            }
+            "hdmi" => {
+                // `-jc` nests the HDMI state in a second hdmi object
+                let hdmi = if value.has_key("hdmi") {
+                    &value["hdmi"]
+                } else {
+                    value
+                };
+                if !hdmi.is_object() {
+                    return Err("hdmi must be an object".to_string());
+                }
+                for (key, value) in hdmi.entries() {
+                    match key {
+                        "output" | "source" => {
+                            state.hdmi_source = Some(match value.as_str() {
+                                Some("cable") => HDMISource::Cable,
+                                Some("gpu") => HDMISource::Gpu,
+                                Some("unknown") => HDMISource::Unknown,
+                                _ => return Err(format!("invalid HDMI source {value}")),
+                            });
+                        }
+                        // the input cable state and existence are read only
+                        "input" | "exists" => {}
+                        _ => return Err(format!("unknown HDMI setting '{key}'")),
+                    }
+                }
+            }
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{has_brightness, state_from_json};
+    use alienware::document::{self, Format};
+    use alienware::{Colour, HDMISource, State, Zone};
+
+    fn parse_state(contents: &str, format: Option<Format>) -> Result<State, String> {
+        state_from_json(&document::parse(contents, format)?)
+    }
+
+    #[test]
+    fn parse_cli_json_output() {
+        let json = r#"{"hdmi":{"hdmi":{"exists":true,"input":"unconnected","output":"gpu"}},"leds":{"exists":true,"left":{"red":0,"green":15,"blue":15,"brightness":50,"effective":{"red":0,"green":8,"blue":8}},"head":{"red":15,"green":0,"blue":15}}}"#;
+        assert!(has_brightness(&document::parse(json, None).unwrap()));
+        let state = parse_state(json, None).unwrap();
+        assert_eq!(state.hdmi_source, Some(HDMISource::Gpu));
+        assert_eq!(state.zones.len(), 2);
+        assert_eq!(state.zones[&Zone::Left], Colour::new(0, 15, 15));
+        assert_eq!(state.zones[&Zone::Head], Colour::new(15, 0, 15));
+    }
+
+    #[test]
+    fn parse_toml() {
+        let state = parse_state(
+            "[leds]\nhead = \"cyan\"\nright = { red = 1, green = 2, blue = 3 }\n\n[hdmi]\nsource = \"cable\"\n",
+            Some(Format::Toml),
+        )
+        .unwrap();
+        assert_eq!(state.hdmi_source, Some(HDMISource::Cable));
+        assert_eq!(state.zones[&Zone::Head], Colour::new(0, 15, 15));
+        assert_eq!(state.zones[&Zone::Right], Colour::new(1, 2, 3));
+        let toml = "[leds]\nhead = \"cyan\"\n";
+        assert!(!has_brightness(&document::parse(toml, None).unwrap()));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse_state(r#"{"leds":{"middle":"red"}}"#, None).is_err());
+        assert!(parse_state(r#"{"leds":{"head":{"red":300,"green":0,"blue":0}}}"#, None).is_err());
+        assert!(parse_state(r#"{"hdmi":{"output":"hdmi"}}"#, None).is_err());
+        assert!(parse_state("not a document", None).is_err());
+    }
+}
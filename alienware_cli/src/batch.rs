@@ -0,0 +1,83 @@
+use crate::command::{parse_command, run_command, BatchCommand};
+use crate::config::Config;
+use crate::restore::{restore_if_stopped, sleep_while_running, stop_on_signal, SavedColours};
+use alienware::{SharedAlienware, Transition};
+use std::fs::File;
+use std::io::{stdin, BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Run the newline separated commands from a file, or stdin for `-`, stopping at the first problem and returning the
+/// exit code.  If it is stopped by a signal the colours from before it are put back, unless they are to be kept.
+pub fn batch(
+    aw: &SharedAlienware,
+    config: &Config,
+    fade: Option<Transition>,
+    file: &str,
+    keep: bool,
+) -> i32 {
+    let reader: Box<dyn BufRead> = if file == "-" {
+        Box::new(stdin().lock())
+    } else {
+        match File::open(file) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(x) => {
+                println!("Problem reading the batch file {file}: {x}");
+                return 1;
+            }
+        }
+    };
+    // a machine without LEDs can still run hdmi commands, so there may be nothing to put back
+    let saved = SavedColours::capture(aw.unlocked()).unwrap_or_default();
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    let code = run_lines(aw, config, fade, reader, &running);
+    restore_if_stopped(aw, &saved, &running, keep, code)
+}
+
+/// Run the commands from a reader until they run out, one fails or `running` is cleared, returning the exit code
+fn run_lines(
+    aw: &SharedAlienware,
+    config: &Config,
+    fade: Option<Transition>,
+    reader: Box<dyn BufRead>,
+    running: &AtomicBool,
+) -> i32 {
+    for (number, line) in reader.lines().enumerate() {
+        if !running.load(Ordering::SeqCst) {
+            println!("Stopped before line {}", number + 1);
+            return 1;
+        }
+        let line = match line {
+            Ok(line) => line,
+            Err(x) => {
+                println!("Problem reading line {}: {x}", number + 1);
+                return 1;
+            }
+        };
+        match parse_command(line.as_str()) {
+            Ok(Some(BatchCommand::Sleep(duration))) => {
+                if !sleep_while_running(duration, running) {
+                    println!("Stopped at line {}: {}", number + 1, line.trim());
+                    return 1;
+                }
+            }
+            Ok(Some(command)) => {
+                if !run_command(aw, config, fade, &command, running) {
+                    println!("Stopped at line {}: {}", number + 1, line.trim());
+                    return 1;
+                }
+            }
+            Ok(None) => {}
+            Err(x) => {
+                println!("Line {}: {x}", number + 1);
+                return 1;
+            }
+        }
+    }
+    0
+}
@@ -0,0 +1,62 @@
+use crate::config::Config;
+use crate::print_error;
+use crate::zones::resolve_zones;
+use alienware::{Alienware, Zone};
+
+/// Print the brightness, or set it globally or for some zones, returning the exit code
+pub fn brightness(
+    aw: &Alienware,
+    config: &Config,
+    percent: Option<u8>,
+    zones: Option<&str>,
+) -> i32 {
+    let percent = match percent {
+        Some(percent) => percent,
+        None => {
+            return match aw.get_brightness() {
+                Ok(brightness) => {
+                    println!("Brightness: {brightness}");
+                    0
+                }
+                Err(x) => {
+                    print_error("Problem getting the brightness", &x);
+                    1
+                }
+            };
+        }
+    };
+    let leds = match aw.get_rgb_zones() {
+        Ok(leds) => leds,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    if !leds.exists {
+        println!("There is no alienware LED unit on this machine");
+        return 1;
+    }
+    let result = match zones {
+        None => aw.set_brightness(percent),
+        Some(zones) => {
+            let discovered: Vec<Zone> = leds.zones.keys().copied().collect();
+            let zones = match resolve_zones(zones, config, &discovered) {
+                Ok(zones) => zones,
+                Err(x) => {
+                    println!("{x}");
+                    return 1;
+                }
+            };
+            zones
+                .iter()
+                .try_for_each(|zone| aw.set_zone_brightness(*zone, percent))
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(x) => {
+            print_error("Problem setting the brightness", &x);
+            1
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::config::{calibration_to_toml, config_path, save_calibration, SYSTEM_CONFIG};
+use crate::print_error;
+use crate::restore::SavedColours;
+use alienware::effects::MAX_LEVEL;
+use alienware::{Calibration, Colour, SharedAlienware, Zone};
+use std::collections::HashMap;
+use std::io::{stdin, stdout, BufRead, Write};
+
+/// The number of steps that a gain is raised or lowered in, from 0 to 1
+const GAIN_STEPS: f32 = 20.0;
+
+/// The colours that the zones are compared at
+const WHITE: Colour = Colour::new(MAX_LEVEL, MAX_LEVEL, MAX_LEVEL);
+const GREY: Colour = Colour::new(MAX_LEVEL / 2 + 1, MAX_LEVEL / 2 + 1, MAX_LEVEL / 2 + 1);
+
+/// The commands that can be given while matching a zone
+const HELP: &str = "  r+ r- g+ g- b+ b-  raise or lower the red, green or blue of this zone
+  gamma <value>      set the gamma of this zone, best compared in grey
+  white, grey        compare the zones in white or in mid grey
+  reset              take the calibration off this zone
+  next               keep this calibration and move on
+  quit               stop without saving";
+
+/// Walk through matching the white of every zone to the white of a reference zone, then offer to save the calibration
+/// to the user's configuration file, or the system wide one, returning the exit code
+pub fn calibrate(aw: &SharedAlienware, reference: Zone, system: bool) -> i32 {
+    let path = match config_path(system) {
+        Some(path) => path,
+        None => {
+            println!(
+                "The user's config file cannot be found, use --system to save to {SYSTEM_CONFIG}"
+            );
+            return 1;
+        }
+    };
+    let leds = match aw.get_rgb_zones() {
+        Ok(leds) => leds,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    if !leds.exists {
+        println!("There is no alienware LED unit on this machine");
+        return 1;
+    }
+    let zones: Vec<Zone> = [Zone::Head, Zone::Left, Zone::Right]
+        .into_iter()
+        .filter(|zone| leds.zones.contains_key(zone))
+        .collect();
+    if !zones.contains(&reference) {
+        println!("There is no {reference} zone to match the others to");
+        return 1;
+    }
+    let saved = match SavedColours::capture(aw.unlocked()) {
+        Ok(saved) => saved,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+
+    let before: Vec<(Zone, Calibration)> = zones
+        .iter()
+        .map(|zone| (*zone, aw.get_calibration(*zone)))
+        .collect();
+
+    let result = match_zones(aw, &zones, reference, &mut stdin().lock(), &mut stdout());
+    // put back the colours from before, which show the new calibration if there is one
+    let restored = aw.lock().and_then(|aw| {
+        if !matches!(result, Ok(Some(_))) {
+            for (zone, calibration) in before {
+                aw.set_calibration(zone, calibration);
+            }
+        }
+        saved.restore(&aw)
+    });
+    if let Err(x) = restored {
+        print_error("Problem restoring the LEDs", &x);
+        return 1;
+    }
+    let calibration = match result {
+        Ok(Some(calibration)) => calibration,
+        Ok(None) => {
+            println!("Stopped without saving");
+            return 0;
+        }
+        Err(x) => {
+            print_error("Problem setting RGB value", &x);
+            return 1;
+        }
+    };
+
+    println!(
+        "\nThe calibration is:\n\n{}",
+        calibration_to_toml(&calibration)
+    );
+    print!("Save it to {}? [y/N] ", path.display());
+    let _ = stdout().flush();
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+        println!("Not saved, the calibration can be added to the config file by hand");
+        return 0;
+    }
+    match save_calibration(&path, &calibration) {
+        Ok(()) => {
+            println!("Saved to {}", path.display());
+            0
+        }
+        Err(x) => {
+            println!("Problem saving the calibration: {x}");
+            1
+        }
+    }
+}
+
+/// Show every zone in the same colour and let the user change the calibration of each zone in turn until it matches the
+/// reference zone, returning the calibration of every zone, or `None` if they stop part way through.  The lock is only
+/// held while the zones are drawn, not while waiting for the user.
+pub fn match_zones(
+    aw: &SharedAlienware,
+    zones: &[Zone],
+    reference: Zone,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> std::io::Result<Option<HashMap<Zone, Calibration>>> {
+    let mut calibration: HashMap<Zone, Calibration> = zones
+        .iter()
+        .map(|zone| (*zone, aw.get_calibration(*zone)))
+        .collect();
+    let mut colour = WHITE;
+    writeln!(
+        output,
+        "Each zone is matched in turn to the {reference}, which is left as it is.  The commands are:\n{HELP}"
+    )?;
+    for zone in zones.iter().copied().filter(|zone| *zone != reference) {
+        writeln!(output, "\nMatching the {zone} to the {reference}")?;
+        loop {
+            {
+                let aw = aw.lock()?;
+                aw.set_calibration(zone, calibration[&zone]);
+                for zone in zones {
+                    aw.set_rgb_zone(*zone, colour.red, colour.green, colour.blue)?;
+                }
+            }
+            write!(output, "{zone}> ")?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            let current = calibration.entry(zone).or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["next"] => break,
+                ["quit"] => return Ok(None),
+                ["white"] => colour = WHITE,
+                ["grey"] | ["gray"] => colour = GREY,
+                ["reset"] => *current = Calibration::default(),
+                ["gamma", gamma] => match gamma.parse::<f32>() {
+                    Ok(gamma) if gamma > 0.0 && gamma <= 10.0 => current.gamma = gamma,
+                    _ => writeln!(output, "The gamma must be a number from 0 to 10")?,
+                },
+                [step] if step.len() == 2 && step.is_char_boundary(1) => {
+                    let channel = match &step[..1] {
+                        "r" => Some(0),
+                        "g" => Some(1),
+                        "b" => Some(2),
+                        _ => None,
+                    };
+                    let direction = match &step[1..] {
+                        "+" => Some(1.0),
+                        "-" => Some(-1.0),
+                        _ => None,
+                    };
+                    match (channel, direction) {
+                        (Some(channel), Some(direction)) => {
+                            let gain = &mut current.gain[channel];
+                            let steps = (*gain * GAIN_STEPS).round() + direction;
+                            *gain = (steps / GAIN_STEPS).clamp(0.0, 1.0);
+                            writeln!(
+                                output,
+                                "gain {:.2} {:.2} {:.2}",
+                                current.gain[0], current.gain[1], current.gain[2]
+                            )?;
+                        }
+                        _ => writeln!(output, "{HELP}")?,
+                    }
+                }
+                _ => writeln!(output, "{HELP}")?,
+            }
+        }
+    }
+    Ok(Some(calibration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::match_zones;
+    use alienware::{Alienware, Calibration, Colour, SharedAlienware, Zone};
+
+    #[test]
+    fn match_to_reference() {
+        let aw = SharedAlienware::new(Alienware::in_memory());
+        let zones = [Zone::Head, Zone::Left, Zone::Right];
+        let mut output = Vec::new();
+        let script = "b-\nb-\nfoo\ngrey\ngamma 2\nnext\ng-\nreset\nnext\n";
+        let calibration = match_zones(&aw, &zones, Zone::Left, &mut script.as_bytes(), &mut output)
+            .unwrap()
+            .unwrap();
+        let head = Calibration {
+            gain: [1.0, 1.0, 0.9],
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(calibration[&Zone::Head], head);
+        assert!(calibration[&Zone::Left].is_identity());
+        assert!(calibration[&Zone::Right].is_identity());
+        assert_eq!(aw.get_calibration(Zone::Head), head);
+        // the zones were last shown in grey
+        let leds = aw.get_rgb_zones().unwrap().zones;
+        assert_eq!(leds[&Zone::Left].colour(), Colour::new(8, 8, 8));
+        assert_eq!(leds[&Zone::Head].colour(), Colour::new(4, 4, 4));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("gain 1.00 1.00 0.90"), "{output}");
+        assert!(output.contains("raise or lower"), "{output}");
+
+        // stopping part way through gives nothing to save
+        let mut output = Vec::new();
+        for script in ["quit\n", "next\n"] {
+            let result = match_zones(&aw, &zones, Zone::Left, &mut script.as_bytes(), &mut output);
+            assert_eq!(result.unwrap(), None);
+        }
+    }
+}
@@ -1,4 +1,14 @@
-use clap::Parser;
+use crate::zones::parse_zone;
+use alienware::document;
+use alienware::{
+    parse_duration, BlendMode, Colour, ColourSpace, Easing, ParseError, Zone, MAX_BRIGHTNESS,
+};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCompleter, Shell};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// Struct containing the parsed command line arguments
 #[derive(Parser)]
@@ -26,11 +36,339 @@ pub struct Options {
     #[arg(short = 'R', long, value_parser)]
     pub right: Option<String>,
 
-    /// Output in JSON format for machine readability (combined with -c or -l)
+    /// Output in JSON format for machine readability (combined with -c, -l or selftest)
     #[arg(short, long, value_parser, default_value_t = false)]
     pub json: bool,
 
+    /// Print the sysfs writes that would be made instead of making them
+    #[arg(long, value_parser, default_value_t = false, global = true)]
+    pub dry_run: bool,
+
+    /// Fade to new colours over a time instead of changing them straight away, in milliseconds or with a unit, e.g.
+    /// 500 or 2s
+    #[arg(long, value_name = "MS", global = true, value_parser = fade_arg)]
+    pub fade: Option<Duration>,
+
+    /// The curve that a fade follows
+    #[arg(long, global = true, default_value_t = Easing::default(), value_parser = PossibleValuesParser::new(EASINGS).map(|x| x.parse::<Easing>().unwrap()))]
+    pub easing: Easing,
+
+    /// The colour space that a fade moves through
+    #[arg(long, global = true, default_value_t = ColourSpace::default(), value_parser = PossibleValuesParser::new(COLOUR_SPACES).map(|x| x.parse::<ColourSpace>().unwrap()))]
+    pub space: ColourSpace,
+
+    /// Leave the last colours shown when an effect, show, notification, batch or command at the prompt is stopped by
+    /// Ctrl-C, SIGTERM or SIGHUP, instead of putting back the colours from before it started
+    #[arg(long, value_parser, default_value_t = false, global = true)]
+    pub keep: bool,
+
+    /// Read the settings from an archive written by `dump` instead of this machine, without changing anything
+    #[arg(long, value_name = "ARCHIVE", global = true)]
+    pub replay: Option<PathBuf>,
+
     /// Print version information
     #[arg(short = 'V', long, value_parser, display_order(9))]
     pub version: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands that can be run instead of, or as well as, the options
+#[derive(Subcommand)]
+pub enum Command {
+    /// Apply a JSON or TOML state document, such as the output of `-jlc`
+    ///
+    /// The colours are set at the current brightness, so the brightness and effective colour that `-jl` gives for each
+    /// LED are ignored, use the `brightness` command to set the brightness.
+    ///
+    /// With `--reconcile` the settings that differ from the document are put back and each one is logged, such as
+    /// colours that were reset after a suspend, and with `--interval` this is repeated until Ctrl-C is pressed.  There
+    /// is no daemon, so `--interval` is how the settings are kept continuously, such as from a systemd service.  A pass
+    /// that cannot read or write the device is logged and tried again at the next interval.
+    Apply {
+        /// The state document to apply, `-` reads it from stdin
+        #[arg(default_value = "-")]
+        file: String,
+
+        /// The format of the state document, guessed from the file name or contents if not set
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+
+        /// Put back the settings that have drifted from the document, logging each one
+        #[arg(long)]
+        reconcile: bool,
+
+        /// Keep reconciling at this interval until stopped by Ctrl-C, SIGTERM or SIGHUP, e.g. 5s
+        #[arg(long, requires = "reconcile", value_parser = duration_arg)]
+        interval: Option<Duration>,
+    },
+
+    /// Set the colour of zones, e.g. `set head=red sides=#00ff00` or `set all blue`
+    ///
+    /// Zones can be head, left or right, the built-in groups all and sides, or a group defined in the config file.
+    /// Colours can be a name, an RGB value such as "0 15 15", a hex value such as #00ff00 or a colour temperature such
+    /// as 2700K.
+    Set {
+        /// Assignments of a colour to a zone or group, either `zone=colour` or `zone colour`
+        #[arg(required = true, add = ArgValueCompleter::new(crate::completions::complete_assignment))]
+        assignments: Vec<String>,
+    },
+
+    /// Run newline separated commands, such as `set head red`, `sleep 500ms` or `hdmi gpu`, from a file or stdin
+    Batch {
+        /// The file of commands to run, `-` reads them from stdin
+        #[arg(default_value = "-")]
+        file: String,
+    },
+
+    /// Run commands from an interactive prompt with history and tab completion
+    Repl,
+
+    /// Print the brightness, or set it from 0 to 100% for every zone or for some zones, e.g. `brightness 50%`
+    ///
+    /// Every colour is scaled by the brightness when it is written, so colours do not need to be worked out again to
+    /// dim the LEDs.  The brightness of a zone is combined with the global brightness.
+    Brightness {
+        /// The brightness to set, from 0 to 100 with or without a %
+        #[arg(value_parser = percent_arg)]
+        percent: Option<u8>,
+
+        /// Set the brightness of a zone or group rather than the global brightness
+        #[arg(short, long, requires = "percent")]
+        zones: Option<String>,
+    },
+
+    /// List, show, apply, save or delete named profiles of colours, brightness, HDMI source and an effect
+    ///
+    /// Profiles are kept in `~/.config/alienware/config.toml` and `/etc/alienware/config.toml` as `[profiles.<name>]`
+    /// tables, in the layout of a state document with a brightness and an effect added.
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+
+    /// Show an animated effect until Ctrl-C is pressed, e.g. `effect breathe colour=red period=2s`
+    ///
+    /// Parameters are `name=value`.  Every effect takes zones (a zone or group, default all) and fps (default 30), use
+    /// `--list` to see the effects and the other parameters that each of them takes.  When it is stopped the colours
+    /// from before it are put back, unless `--keep` is given.
+    Effect {
+        /// List the effects and their parameters
+        #[arg(long)]
+        list: bool,
+
+        /// The effect to show
+        #[arg(required_unless_present = "list", add = ArgValueCompleter::new(|current: &OsStr| crate::completions::complete_effect(crate::effect::registry(), current)))]
+        name: Option<String>,
+
+        /// Parameters of the effect as `name=value`
+        params: Vec<String>,
+    },
+
+    /// Show a colour over some zones for a time and then put back the colours they had, e.g. `notify red --for 2s`
+    ///
+    /// The colour is shown on a layer over the colours that the zones have now, mixed with them by the blend mode and
+    /// opacity.  If Ctrl-C is pressed the colours are put back straight away.
+    Notify {
+        /// The colour to show
+        #[arg(value_parser = colour_arg)]
+        colour: Colour,
+
+        /// The zone or group to show it on
+        #[arg(short, long, default_value = "all")]
+        zones: String,
+
+        /// How long to show it for, e.g. 500ms or 2s
+        #[arg(long = "for", value_name = "DURATION", default_value = "2s", value_parser = duration_arg)]
+        duration: Duration,
+
+        /// How the colour is combined with the colours that the zones have now
+        #[arg(long, default_value = "normal", value_parser = PossibleValuesParser::new(BLEND_MODES).map(|x| x.parse::<BlendMode>().unwrap()))]
+        blend: BlendMode,
+
+        /// How much of the colour is shown over the colours that the zones have now, from 0 to 1
+        #[arg(long, default_value_t = 1.0, value_parser = opacity_arg)]
+        opacity: f32,
+    },
+
+    /// Play a light show of keyframes for each zone from a JSON or TOML document until it ends or Ctrl-C is pressed
+    ///
+    /// A show is a list of tracks, each with the zones it lights and a list of keyframes.  Each keyframe has a colour,
+    /// a duration and an easing of step (the default), linear, ease-in, ease-out or ease-in-out, and tracks and shows
+    /// can repeat.  A fade between two colour temperatures, such as 6500K and 2700K, moves through the temperatures in
+    /// between.  See scripts/alienware-example.toml for an example.
+    Play {
+        /// The show to play, `-` reads it from stdin
+        #[arg(default_value = "-")]
+        file: String,
+
+        /// The format of the show, guessed from the file name or contents if not set
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+
+        /// How many times a second to update the colours
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=i64::from(crate::effect::MAX_FPS)))]
+        fps: u32,
+    },
+
+    /// Match the colours of the zones by eye, such as when the same white looks different on the head and the sides
+    ///
+    /// Every zone is shown in white and each one in turn is adjusted to match the reference zone, by raising or
+    /// lowering its red, green and blue and setting its gamma.  The calibration can then be saved to the user's config
+    /// file, or the system wide one, and is applied to every colour written from then on.
+    Calibrate {
+        /// The zone that the other zones are matched to
+        #[arg(short, long, default_value = "left", value_parser = zone_arg)]
+        reference: Zone,
+
+        /// Save to the system wide config file rather than the user's
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Cycle each LED zone through the primary colours, checking that each colour reads back, check that the HDMI
+    /// state can be read, then restore the original colours and report the results
+    Selftest {
+        /// How long to show each colour for, e.g. 250ms or 1s
+        #[arg(short, long, default_value = "250ms", value_parser = duration_arg)]
+        delay: Duration,
+    },
+
+    /// Check the machine, kernel module, sysfs files, permissions and snap setup, and suggest how to fix any problems
+    Doctor,
+
+    /// Write an archive of the alienware sysfs files, DMI ids and kernel module parameters to attach to a bug report
+    Dump {
+        /// The archive to write, `-` writes it to stdout
+        #[arg(default_value = "alienware-dump.tar.gz")]
+        file: String,
+    },
+
+    /// Print the script that sets up tab completion for a shell, e.g. `source <(alienware-cli completions bash)`
+    Completions {
+        /// The shell to complete in
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the man page, or write a man page for every command into a directory
+    Manpage {
+        /// The directory to write the man pages into
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+/// What to do with profiles
+#[derive(Subcommand)]
+pub enum ProfileCommand {
+    /// List the profiles in the config files
+    List,
+
+    /// Print the settings of a profile
+    Show {
+        /// The profile to print
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_profile))]
+        name: String,
+    },
+
+    /// Set the brightness, colours and HDMI source of a profile, then show its effect, if it has one, until Ctrl-C is
+    /// pressed
+    Apply {
+        /// The profile to apply
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_profile))]
+        name: String,
+    },
+
+    /// Save the colours, brightness and HDMI source that the machine has now as a profile, replacing any profile of
+    /// the same name
+    Save {
+        /// The name of the profile
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_profile))]
+        name: String,
+
+        /// Save to the system wide config file rather than the user's
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Delete a profile
+    Delete {
+        /// The profile to delete
+        #[arg(add = ArgValueCompleter::new(crate::completions::complete_profile))]
+        name: String,
+
+        /// Delete from the system wide config file rather than the user's
+        #[arg(long)]
+        system: bool,
+    },
+}
+
+/// The easing curves that can be chosen for a fade
+const EASINGS: [&str; 5] = ["step", "linear", "ease-in", "ease-out", "ease-in-out"];
+
+/// The colour spaces that a fade can move through
+const COLOUR_SPACES: [&str; 3] = ["rgb", "hsv", "oklab"];
+
+/// The ways that a notification can be combined with the colours below it
+const BLEND_MODES: [&str; 6] = ["normal", "add", "multiply", "screen", "lighten", "darken"];
+
+/// Parse a colour argument such as `red`, `#ff0000`, `"15 0 0"` or `2700K`
+fn colour_arg(input: &str) -> Result<Colour, String> {
+    input.parse().map_err(|x| match x {
+        ParseError::KelvinOutOfRange(_) => x.to_string(),
+        _ => format!("'{input}' is not a colour, e.g. red, #ff0000, \"15 0 0\" or 2700K"),
+    })
+}
+
+/// Parse the name of a zone
+fn zone_arg(input: &str) -> Result<Zone, String> {
+    parse_zone(input).ok_or(format!("'{input}' is not a zone, try head, left or right"))
+}
+
+/// Parse a brightness from 0 to 100%
+fn percent_arg(input: &str) -> Result<u8, String> {
+    let trimmed = input.trim();
+    match trimmed.strip_suffix('%').unwrap_or(trimmed).parse::<u8>() {
+        Ok(percent) if percent <= MAX_BRIGHTNESS => Ok(percent),
+        _ => Err(format!("'{input}' is not a brightness from 0 to 100%")),
+    }
+}
+
+/// Parse an opacity from 0 to 1
+fn opacity_arg(input: &str) -> Result<f32, String> {
+    match input.trim().parse::<f32>() {
+        Ok(opacity) if (0.0..=1.0).contains(&opacity) => Ok(opacity),
+        _ => Err(format!("'{input}' is not an opacity from 0 to 1")),
+    }
+}
+
+/// Parse the length of a fade, where a number without a unit is in milliseconds
+fn fade_arg(input: &str) -> Result<Duration, String> {
+    match input.trim().parse::<u64>() {
+        Ok(millis) => Ok(Duration::from_millis(millis)),
+        Err(_) => duration_arg(input),
+    }
+}
+
+/// Parse a duration argument such as `500ms` or `2s`
+fn duration_arg(input: &str) -> Result<Duration, String> {
+    parse_duration(input).ok_or(format!("'{input}' is not a duration, e.g. 500ms or 2s"))
+}
+
+/// Formats of a state document or show
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl From<Format> for document::Format {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Json => document::Format::Json,
+            Format::Toml => document::Format::Toml,
+        }
+    }
 }
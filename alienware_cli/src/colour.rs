@@ -0,0 +1,37 @@
+use alienware::Colour;
+
+/// The colours that can be given by name
+pub const COLOUR_NAMES: [&str; 8] = [
+    "black", "white", "red", "yellow", "green", "cyan", "blue", "magenta",
+];
+
+/// Parse the RGB value of the input string, either a named colour, an rgb value or a colour temperature
+pub fn parse_colour(input: &str) -> Option<Colour> {
+    input.parse().ok()
+}
+
+/// Parse the RGB value of the input string, falling back to blue if the input is not recognised
+pub fn parse_rgb_string(input: &str) -> (u8, u8, u8) {
+    // setting blue as the default
+    let colour = parse_colour(input).unwrap_or(Colour::new(0u8, 0u8, 15u8));
+    (colour.red, colour.green, colour.blue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_colour, parse_rgb_string};
+    use alienware::Colour;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_colour("Cyan"), Some(Colour::new(0, 15, 15)));
+        assert_eq!(parse_colour("0 15 15"), Some(Colour::new(0, 15, 15)));
+        assert_eq!(parse_colour("#00ff0a"), Some(Colour::new(0, 15, 1)));
+        assert_eq!(parse_colour("#00ff0"), None);
+        assert_eq!(parse_colour("256 0 0"), None);
+        assert_eq!(parse_colour("1 2"), None);
+        assert_eq!(parse_colour("2700K"), Some(Colour::new(15, 10, 5)));
+        assert_eq!(parse_colour("100K"), None);
+        assert_eq!(parse_rgb_string("purple"), (0, 0, 15));
+    }
+}
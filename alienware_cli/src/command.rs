@@ -0,0 +1,186 @@
+use crate::config::Config;
+use crate::print_error;
+use crate::restore::sleep_while_running;
+use crate::set::{parse_assignments, set_assignments, Assignment};
+use alienware::{parse_duration, AlienwareGuard, HDMISource, SharedAlienware, State, Transition};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// The commands understood by `batch` and `repl`
+pub const COMMANDS: [&str; 3] = ["set", "sleep", "hdmi"];
+
+/// A single command from a batch file or the interactive prompt
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchCommand {
+    /// `set head red`, `set all=#00ff00`
+    Set(Vec<Assignment>),
+    /// `sleep 500ms`, `sleep 2s`, `sleep .5`
+    Sleep(Duration),
+    /// `hdmi gpu`, `hdmi cable`
+    Hdmi(HDMISource),
+}
+
+/// Split a line into words on whitespace, keeping anything in double quotes together, e.g. `set head "0 15 15"`
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut in_quotes = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quote".to_string());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Parse a line into a command, returning `None` for blank lines and `#` comments
+pub fn parse_command(line: &str) -> Result<Option<BatchCommand>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let words = split_words(line)?;
+    let (command, args) = words.split_first().ok_or("No command")?;
+    match command.to_lowercase().as_str() {
+        "set" => Ok(Some(BatchCommand::Set(parse_assignments(args)?))),
+        "sleep" => match args {
+            [duration] => parse_duration(duration)
+                .map(|duration| Some(BatchCommand::Sleep(duration)))
+                .ok_or(format!("'{duration}' is not a duration")),
+            _ => Err("Usage: sleep <duration>, e.g. sleep 500ms".to_string()),
+        },
+        "hdmi" => match args {
+            [source] => match source.to_lowercase().as_str() {
+                "cable" => Ok(Some(BatchCommand::Hdmi(HDMISource::Cable))),
+                "gpu" => Ok(Some(BatchCommand::Hdmi(HDMISource::Gpu))),
+                _ => Err(format!("'{source}' is not an HDMI source (cable or gpu)")),
+            },
+            _ => Err("Usage: hdmi <cable|gpu>".to_string()),
+        },
+        _ => Err(format!("Unknown command '{command}'")),
+    }
+}
+
+/// Run a command, taking the lock for as long as it writes to the device, printing any problem and returning whether
+/// it succeeded.  A fade or sleep stops part way through if `running` is cleared.
+pub fn run_command(
+    aw: &SharedAlienware,
+    config: &Config,
+    fade: Option<Transition>,
+    command: &BatchCommand,
+    running: &AtomicBool,
+) -> bool {
+    match command {
+        BatchCommand::Set(assignments) => match lock(aw) {
+            Some(aw) => set_assignments(&aw, config, fade, assignments, running),
+            None => false,
+        },
+        BatchCommand::Sleep(duration) => sleep_while_running(*duration, running),
+        BatchCommand::Hdmi(source) => {
+            let aw = match lock(aw) {
+                Some(aw) => aw,
+                None => return false,
+            };
+            let state = State {
+                hdmi_source: Some(*source),
+                ..Default::default()
+            };
+            match aw.validate_state(&state) {
+                Ok(errors) if errors.is_empty() => {}
+                Ok(errors) => {
+                    for error in errors {
+                        println!("{error}");
+                    }
+                    return false;
+                }
+                Err(x) => {
+                    print_error("Problem getting HDMI state", &x);
+                    return false;
+                }
+            }
+            match aw.apply_state(&state) {
+                Ok(_) => true,
+                Err(x) => {
+                    print_error("Problem setting HDMI source", &x);
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Wait for exclusive access to write to the device, printing the problem if the lock cannot be taken
+fn lock(aw: &SharedAlienware) -> Option<AlienwareGuard<'_>> {
+    match aw.lock() {
+        Ok(guard) => Some(guard),
+        Err(x) => {
+            print_error("Problem locking the device", &x);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_command, split_words, BatchCommand};
+    use crate::set::Assignment;
+    use alienware::{Colour, HDMISource};
+    use std::time::Duration;
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_words(r#"set  head "0 15 15" left=red"#),
+            Ok(vec![
+                "set".to_string(),
+                "head".to_string(),
+                "0 15 15".to_string(),
+                "left=red".to_string()
+            ])
+        );
+        assert!(split_words(r#"set head "0 15"#).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("  # a comment"), Ok(None));
+        assert_eq!(parse_command(""), Ok(None));
+        assert_eq!(
+            parse_command("set head red"),
+            Ok(Some(BatchCommand::Set(vec![Assignment {
+                target: "head".to_string(),
+                colour: Colour::new(15, 0, 0)
+            }])))
+        );
+        assert_eq!(
+            parse_command("sleep 500ms"),
+            Ok(Some(BatchCommand::Sleep(Duration::from_millis(500))))
+        );
+        assert_eq!(
+            parse_command("HDMI gpu"),
+            Ok(Some(BatchCommand::Hdmi(HDMISource::Gpu)))
+        );
+        assert!(parse_command("hdmi unknown").is_err());
+        assert!(parse_command("dance").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::cli::Options;
+use crate::colour::COLOUR_NAMES;
+use crate::config::Config;
+use crate::zones::{zone_names, ZONES};
+use alienware::effects::Registry;
+use alienware::{Alienware, Zone};
+use clap::CommandFactory;
+use clap_complete::env::Shells;
+use clap_complete::{CompletionCandidate, Shell};
+use clap_mangen::Man;
+use std::ffi::OsStr;
+use std::io::stdout;
+use std::path::Path;
+
+/// The environment variable that the shell scripts set when asking for completions
+const COMPLETE_VAR: &str = "COMPLETE";
+
+/// The name of the binary in the completion scripts and man pages
+const BIN_NAME: &str = "alienware-cli";
+
+/// Print the script that registers completions with the shell, returning the exit code
+pub fn completions(shell: Shell) -> i32 {
+    let shells = Shells::builtins();
+    let completer = match shells.completer(shell.to_string().as_str()) {
+        Some(completer) => completer,
+        None => {
+            println!("Completions are not available for {shell}");
+            return 1;
+        }
+    };
+    match completer.write_registration(COMPLETE_VAR, BIN_NAME, BIN_NAME, BIN_NAME, &mut stdout()) {
+        Ok(()) => 0,
+        Err(x) => {
+            eprintln!("Problem writing the completion script: {x}");
+            1
+        }
+    }
+}
+
+/// Print the man page, or write a man page for each command into a directory, returning the exit code
+pub fn manpage(dir: Option<&Path>) -> i32 {
+    let cmd = Options::command();
+    let result = match dir {
+        Some(dir) => clap_mangen::generate_to(cmd, dir),
+        None => Man::new(cmd).render(&mut stdout()),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(x) => {
+            eprintln!("Problem writing the man page: {x}");
+            1
+        }
+    }
+}
+
+/// Complete a `set` assignment: a zone or group name, or a colour name after `zone=`
+pub fn complete_assignment(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    match current.split_once('=') {
+        Some((target, _)) => COLOUR_NAMES
+            .iter()
+            .map(|colour| format!("{target}={colour}"))
+            .filter(|candidate| candidate.starts_with(current.as_ref()))
+            .map(CompletionCandidate::new)
+            .collect(),
+        None => {
+            let names = zone_names(&Config::load(), &discovered_zones());
+            names
+                .into_iter()
+                .chain(COLOUR_NAMES.iter().map(|colour| colour.to_string()))
+                .filter(|candidate| candidate.starts_with(current.as_ref()))
+                .map(CompletionCandidate::new)
+                .collect()
+        }
+    }
+}
+
+/// Complete the name of an effect in a registry
+pub fn complete_effect(registry: &Registry, current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    registry
+        .effects()
+        .map(|spec| {
+            CompletionCandidate::new(spec.name.as_str()).help(Some(spec.description.clone().into()))
+        })
+        .filter(|candidate| {
+            candidate
+                .get_value()
+                .to_string_lossy()
+                .starts_with(current.as_ref())
+        })
+        .collect()
+}
+
+/// Complete the name of a profile in the config files
+pub fn complete_profile(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy().to_lowercase();
+    Config::load()
+        .profiles
+        .into_keys()
+        .filter(|name| name.starts_with(current.as_str()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// The zones of this machine, or all zones if they cannot be read so that completion still works when writing scripts
+/// on another machine
+fn discovered_zones() -> Vec<Zone> {
+    match Alienware::new().get_rgb_zones() {
+        Ok(leds) if leds.exists => leds.zones.keys().copied().collect(),
+        _ => ZONES.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::complete_assignment;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn complete_colour() {
+        let candidates: Vec<String> = complete_assignment(OsStr::new("sides=b"))
+            .iter()
+            .map(|candidate| candidate.get_value().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(candidates, vec!["sides=black", "sides=blue"]);
+    }
+}
@@ -0,0 +1,648 @@
+use crate::apply::state_from_json;
+use crate::colour::parse_colour;
+use crate::command::split_words;
+use crate::zones::parse_zone;
+use alienware::document::toml_to_json;
+use alienware::{Brightness, Calibration, HDMISource, State, Zone, MAX_BRIGHTNESS};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+
+/// The system wide configuration file
+pub const SYSTEM_CONFIG: &str = "/etc/alienware/config.toml";
+
+/// The zones in the order they are written to a configuration file
+const ZONES: [Zone; 3] = [Zone::Head, Zone::Left, Zone::Right];
+
+/// Settings read from the configuration files, where the user's file overrides the system wide file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub groups: BTreeMap<String, Vec<Zone>>,
+    /// The calibration of each zone that has one
+    pub calibration: HashMap<Zone, Calibration>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A named lighting setup, where anything that is left out is not changed when it is applied
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    /// The colours of the zones and the HDMI source, in the layout of a state document
+    pub state: State,
+    pub brightness: Option<Brightness>,
+    /// An effect to show once the rest of the profile is set, given as to the `effect` command, e.g.
+    /// `breathe colour=red period=2s`
+    pub effect: Option<String>,
+}
+
+impl Config {
+    /// Load the system wide configuration and then the user's configuration, reporting but otherwise ignoring any
+    /// file that cannot be read
+    pub fn load() -> Config {
+        let mut config = Config::default();
+        let mut paths = vec![PathBuf::from(SYSTEM_CONFIG)];
+        if let Some(path) = user_config_path() {
+            paths.push(path);
+        }
+        for path in paths {
+            if let Ok(contents) = read_to_string(&path) {
+                if let Err(x) = config.merge(contents.as_str()) {
+                    eprintln!("Ignoring config file {}: {x}", path.display());
+                }
+            }
+        }
+        config
+    }
+
+    /// Merge the contents of a configuration file into these settings
+    pub fn merge(&mut self, contents: &str) -> Result<(), String> {
+        let table = contents
+            .parse::<toml::Table>()
+            .map_err(|x| x.message().to_string())?;
+        if let Some(groups) = table.get("groups") {
+            let groups = groups.as_table().ok_or("groups must be a table")?;
+            for (name, zones) in groups {
+                let zones = zones
+                    .as_array()
+                    .ok_or(format!("group '{name}' must be a list of zones"))?
+                    .iter()
+                    .map(|zone| {
+                        zone.as_str()
+                            .and_then(parse_zone)
+                            .ok_or(format!("group '{name}' has an unknown zone {zone}"))
+                    })
+                    .collect::<Result<Vec<Zone>, String>>()?;
+                self.groups.insert(name.to_lowercase(), zones);
+            }
+        }
+        if let Some(calibration) = table.get("calibration") {
+            let calibration = calibration
+                .as_table()
+                .ok_or("calibration must be a table of zones")?;
+            for (name, value) in calibration {
+                let zone = parse_zone(name).ok_or(format!("unknown calibration zone '{name}'"))?;
+                self.calibration
+                    .insert(zone, calibration_from_toml(zone, value)?);
+            }
+        }
+        if let Some(profiles) = table.get("profiles") {
+            let profiles = profiles.as_table().ok_or("profiles must be a table")?;
+            for (name, value) in profiles {
+                self.profiles
+                    .insert(name.to_lowercase(), profile_from_toml(name, value)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the calibrations of the zones on a machine
+    pub fn calibrate(&self, aw: &alienware::Alienware) {
+        for (zone, calibration) in self.calibration.iter() {
+            aw.set_calibration(*zone, *calibration);
+        }
+    }
+}
+
+/// Read the calibration of a zone, e.g. `{ gain = [1, 0.9, 0.8], gamma = 2.2, white = "15 14 12" }`, where anything
+/// that is left out is not corrected
+fn calibration_from_toml(zone: Zone, value: &toml::Value) -> Result<Calibration, String> {
+    let table = value
+        .as_table()
+        .ok_or(format!("the calibration of {zone} must be a table"))?;
+    let number = |value: &toml::Value| {
+        value
+            .as_float()
+            .or_else(|| value.as_integer().map(|x| x as f64))
+    };
+    let mut calibration = Calibration::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "gain" => {
+                let gain = match value.as_array() {
+                    Some(gains) => gains.iter().map(number).collect::<Option<Vec<f64>>>(),
+                    None => number(value).map(|gain| vec![gain; 3]),
+                };
+                calibration.gain = match gain.as_deref() {
+                    Some(&[red, green, blue]) if [red, green, blue].iter().all(|x| (0.0..=1.0).contains(x)) => {
+                        [red as f32, green as f32, blue as f32]
+                    }
+                    _ => {
+                        return Err(format!(
+                            "the gain of {zone} must be a number from 0 to 1, or a list of one for each of red, green and blue"
+                        ))
+                    }
+                };
+            }
+            "gamma" => {
+                calibration.gamma = match number(value) {
+                    Some(gamma) if gamma > 0.0 && gamma <= 10.0 => gamma as f32,
+                    _ => return Err(format!("the gamma of {zone} must be a number from 0 to 10")),
+                };
+            }
+            "white" => {
+                calibration.white = value.as_str().and_then(parse_colour).ok_or(format!(
+                    "the white point of {zone} must be a colour, e.g. \"15 14 12\""
+                ))?;
+            }
+            _ => return Err(format!("unknown calibration setting '{key}' for {zone}")),
+        }
+    }
+    Ok(calibration)
+}
+
+/// Write the calibration of some zones as a `calibration` table in the layout that the configuration file reads
+pub fn calibration_to_toml(calibration: &HashMap<Zone, Calibration>) -> toml::Table {
+    // round away the noise of single precision
+    let round = |x: f32| (f64::from(x) * 1000.0).round() / 1000.0;
+    let mut zones = toml::Table::new();
+    for zone in ZONES {
+        let calibration = match calibration.get(&zone) {
+            Some(calibration) if !calibration.is_identity() => calibration,
+            _ => continue,
+        };
+        let mut table = toml::Table::new();
+        table.insert(
+            "gain".to_string(),
+            toml::Value::Array(
+                calibration
+                    .gain
+                    .iter()
+                    .map(|gain| toml::Value::Float(round(*gain)))
+                    .collect(),
+            ),
+        );
+        table.insert(
+            "gamma".to_string(),
+            toml::Value::Float(round(calibration.gamma)),
+        );
+        table.insert(
+            "white".to_string(),
+            toml::Value::String(calibration.white.to_string()),
+        );
+        zones.insert(zone.to_string(), toml::Value::Table(table));
+    }
+    let mut table = toml::Table::new();
+    table.insert("calibration".to_string(), toml::Value::Table(zones));
+    table
+}
+
+/// Read a profile, e.g. `{ brightness = 40, effect = "breathe", leds = { head = "2700K" }, hdmi = { source = "gpu" } }`
+fn profile_from_toml(name: &str, value: &toml::Value) -> Result<Profile, String> {
+    let table = value
+        .as_table()
+        .ok_or(format!("profile '{name}' must be a table"))?;
+    let mut profile = Profile::default();
+    let mut state = toml::Table::new();
+    for (key, value) in table {
+        match key.as_str() {
+            "leds" | "hdmi" => {
+                state.insert(key.clone(), value.clone());
+            }
+            "brightness" => profile.brightness = Some(brightness_from_toml(name, value)?),
+            "effect" => {
+                let effect = value
+                    .as_str()
+                    .filter(|effect| !split_words(effect).unwrap_or_default().is_empty())
+                    .ok_or(format!(
+                        "the effect of profile '{name}' must be an effect and its parameters, e.g. \"breathe period=2s\""
+                    ))?;
+                profile.effect = Some(effect.to_string());
+            }
+            _ => return Err(format!("unknown setting '{key}' in profile '{name}'")),
+        }
+    }
+    profile.state = state_from_json(&toml_to_json(&toml::Value::Table(state)))
+        .map_err(|x| format!("profile '{name}': {x}"))?;
+    Ok(profile)
+}
+
+/// Read a brightness, either a percentage for every zone or a table of `global` and zone percentages
+fn brightness_from_toml(name: &str, value: &toml::Value) -> Result<Brightness, String> {
+    let percent = |value: &toml::Value| {
+        value
+            .as_integer()
+            .and_then(|percent| u8::try_from(percent).ok())
+            .filter(|percent| *percent <= MAX_BRIGHTNESS)
+            .ok_or(format!(
+                "the brightness of profile '{name}' must be from 0 to {MAX_BRIGHTNESS}"
+            ))
+    };
+    let mut brightness = Brightness::default();
+    match value.as_table() {
+        Some(table) => {
+            for (key, value) in table {
+                match key.as_str() {
+                    "global" => brightness.global = percent(value)?,
+                    zone => {
+                        let zone = parse_zone(zone).ok_or(format!(
+                            "unknown brightness zone '{zone}' in profile '{name}'"
+                        ))?;
+                        let percent = percent(value)?;
+                        if percent != MAX_BRIGHTNESS {
+                            brightness.zones.insert(zone, percent);
+                        }
+                    }
+                }
+            }
+        }
+        None => brightness.global = percent(value)?,
+    }
+    Ok(brightness)
+}
+
+/// Write a profile in the layout that the configuration file reads
+pub fn profile_to_toml(profile: &Profile) -> toml::Table {
+    let mut table = toml::Table::new();
+    if let Some(brightness) = &profile.brightness {
+        let value = if brightness.zones.is_empty() {
+            toml::Value::Integer(brightness.global.into())
+        } else {
+            let mut zones = toml::Table::new();
+            zones.insert(
+                "global".to_string(),
+                toml::Value::Integer(brightness.global.into()),
+            );
+            for zone in ZONES {
+                if let Some(percent) = brightness.zones.get(&zone) {
+                    zones.insert(zone.to_string(), toml::Value::Integer((*percent).into()));
+                }
+            }
+            toml::Value::Table(zones)
+        };
+        table.insert("brightness".to_string(), value);
+    }
+    if let Some(effect) = &profile.effect {
+        table.insert("effect".to_string(), toml::Value::String(effect.clone()));
+    }
+    let mut leds = toml::Table::new();
+    for zone in ZONES {
+        if let Some(colour) = profile.state.zones.get(&zone) {
+            leds.insert(zone.to_string(), toml::Value::String(colour.to_string()));
+        }
+    }
+    if !leds.is_empty() {
+        table.insert("leds".to_string(), toml::Value::Table(leds));
+    }
+    if let Some(source) = profile.state.hdmi_source {
+        if source != HDMISource::Unknown {
+            let mut hdmi = toml::Table::new();
+            hdmi.insert(
+                "source".to_string(),
+                toml::Value::String(source.to_string()),
+            );
+            table.insert("hdmi".to_string(), toml::Value::Table(hdmi));
+        }
+    }
+    table
+}
+
+/// Replace the calibration in a configuration file, keeping its other settings, comments and layout
+pub fn save_calibration(
+    path: &Path,
+    calibration: &HashMap<Zone, Calibration>,
+) -> Result<(), String> {
+    let mut document = read_config_document(path)?.unwrap_or_default();
+    let section = config_section(&mut document, "calibration", path)?;
+    let zones = calibration_to_toml(calibration);
+    let zones = zones
+        .get("calibration")
+        .and_then(toml::Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+    for zone in ZONES {
+        let zone = zone.to_string();
+        match zones.get(&zone).and_then(toml::Value::as_table) {
+            Some(settings) => set_table(section, zone.as_str(), settings),
+            None => {
+                section.remove(zone.as_str());
+            }
+        }
+    }
+    if section.is_empty() {
+        document.remove("calibration");
+    }
+    write_config_document(path, &document)
+}
+
+/// Add a profile to a configuration file, replacing any profile of the same name and keeping the rest of the file's
+/// settings, comments and layout
+pub fn save_profile(path: &Path, name: &str, profile: &Profile) -> Result<(), String> {
+    let mut document = read_config_document(path)?.unwrap_or_default();
+    let profiles = config_section(&mut document, "profiles", path)?;
+    let name = name.to_lowercase();
+    let key = profiles
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .find(|key| key.to_lowercase() == name)
+        .unwrap_or(name);
+    set_table(profiles, key.as_str(), &profile_to_toml(profile));
+    write_config_document(path, &document)
+}
+
+/// Remove a profile from a configuration file, returning whether it was there
+pub fn delete_profile(path: &Path, name: &str) -> Result<bool, String> {
+    let mut document = match read_config_document(path)? {
+        Some(document) => document,
+        None => return Ok(false),
+    };
+    let profiles = match document
+        .get_mut("profiles")
+        .and_then(|profiles| profiles.as_table_like_mut())
+    {
+        Some(profiles) => profiles,
+        None => return Ok(false),
+    };
+    let name = name.to_lowercase();
+    let key = match profiles
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .find(|key| key.to_lowercase() == name)
+    {
+        Some(key) => key,
+        None => return Ok(false),
+    };
+    profiles.remove(key.as_str());
+    if profiles.is_empty() {
+        document.remove("profiles");
+    }
+    write_config_document(path, &document)?;
+    Ok(true)
+}
+
+/// Read a configuration file to edit in place, or `None` if there is no file.  A file that is there but cannot be read
+/// is an error, so that it is not replaced by one holding only the setting being saved.
+fn read_config_document(path: &Path) -> Result<Option<DocumentMut>, String> {
+    match read_to_string(path) {
+        Ok(contents) => contents
+            .parse::<DocumentMut>()
+            .map(Some)
+            .map_err(|x| format!("{} cannot be read: {}", path.display(), x.message())),
+        Err(x) if x.kind() == ErrorKind::NotFound => Ok(None),
+        Err(x) => Err(format!("{} cannot be read: {x}", path.display())),
+    }
+}
+
+/// Write a configuration file that has been edited in place
+fn write_config_document(path: &Path, document: &DocumentMut) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(|x| format!("{}: {x}", parent.display()))?;
+    }
+    write(path, document.to_string()).map_err(|x| format!("{}: {x}", path.display()))
+}
+
+/// A section of a configuration file, such as `profiles`, adding it if it is not there yet
+fn config_section<'a>(
+    document: &'a mut DocumentMut,
+    name: &str,
+    path: &Path,
+) -> Result<&'a mut toml_edit::Table, String> {
+    document
+        .entry(name)
+        .or_insert_with(|| {
+            let mut section = toml_edit::Table::new();
+            section.set_implicit(true);
+            Item::Table(section)
+        })
+        .as_table_mut()
+        .ok_or(format!("{name} in {} is not a table", path.display()))
+}
+
+/// Set a table in a section of a configuration file to some settings, keeping the comments on the table and on the
+/// settings that it already had
+fn set_table(section: &mut toml_edit::Table, key: &str, settings: &toml::Table) {
+    match section.get_mut(key).and_then(Item::as_table_mut) {
+        Some(table) => {
+            table.retain(|key, _| settings.contains_key(key));
+            for (key, value) in settings {
+                table[key.as_str()] = toml_edit::value(toml_to_edit(value));
+            }
+        }
+        None => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in settings {
+                table.insert(key, toml_edit::value(toml_to_edit(value)));
+            }
+            section.insert(key, Item::Table(table));
+        }
+    }
+}
+
+/// Convert a TOML value into one that can be written to a configuration file that is edited in place, where tables
+/// are written inline
+fn toml_to_edit(value: &toml::Value) -> toml_edit::Value {
+    match value {
+        toml::Value::String(s) => s.as_str().into(),
+        toml::Value::Integer(i) => (*i).into(),
+        toml::Value::Float(f) => (*f).into(),
+        toml::Value::Boolean(b) => (*b).into(),
+        toml::Value::Datetime(d) => d.to_string().into(),
+        toml::Value::Array(a) => toml_edit::Value::Array(a.iter().map(toml_to_edit).collect()),
+        toml::Value::Table(t) => toml_edit::Value::InlineTable(
+            t.iter()
+                .map(|(key, value)| (key.as_str(), toml_to_edit(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// The configuration file that settings are saved to or deleted from, the system wide one or the user's
+pub fn config_path(system: bool) -> Option<PathBuf> {
+    if system {
+        Some(PathBuf::from(SYSTEM_CONFIG))
+    } else {
+        user_config_path()
+    }
+}
+
+/// The user's configuration file, in the XDG config directory
+pub fn user_config_path() -> Option<PathBuf> {
+    let mut path = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let mut path = PathBuf::from(env::var_os("HOME")?);
+            path.push(".config");
+            path
+        }
+    };
+    path.push("alienware");
+    path.push("config.toml");
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calibration_to_toml, delete_profile, profile_to_toml, save_calibration, save_profile,
+        Config, Profile,
+    };
+    use alienware::{Calibration, Colour, HDMISource, Zone};
+    use std::collections::HashMap;
+    use std::fs::{read_to_string, write};
+
+    #[test]
+    fn merge_groups() {
+        let mut config = Config::default();
+        config
+            .merge("[groups]\nfront = [\"head\"]\nEdges = [\"left\", \"right\"]\n")
+            .unwrap();
+        config.merge("[groups]\nfront = [\"left\"]\n").unwrap();
+        assert_eq!(config.groups["front"], vec![Zone::Left]);
+        assert_eq!(config.groups["edges"], vec![Zone::Left, Zone::Right]);
+        assert!(config.merge("[groups]\nfront = [\"middle\"]\n").is_err());
+    }
+
+    #[test]
+    fn merge_calibration() {
+        let mut config = Config::default();
+        config
+            .merge("[calibration.head]\ngain = [1, 0.9, 0.75]\ngamma = 2.2\n\n[calibration.left]\nwhite = \"15 14 12\"\n")
+            .unwrap();
+        let head = Calibration {
+            gain: [1.0, 0.9, 0.75],
+            gamma: 2.2,
+            ..Default::default()
+        };
+        assert_eq!(config.calibration[&Zone::Head], head);
+        assert_eq!(
+            config.calibration[&Zone::Left].white,
+            Colour::new(15, 14, 12)
+        );
+        for invalid in [
+            "[calibration.middle]\ngamma = 2\n",
+            "[calibration.head]\ngain = [1, 2, 1]\n",
+            "[calibration.head]\ngain = [1, 1]\n",
+            "[calibration.head]\ngamma = 0\n",
+            "[calibration.head]\nwhite = \"grey\"\n",
+            "[calibration.head]\ncontrast = 1\n",
+        ] {
+            assert!(config.merge(invalid).is_err(), "{invalid}");
+        }
+
+        // what is saved is read back the same
+        let mut saved = Config::default();
+        saved
+            .merge(
+                calibration_to_toml(&config.calibration)
+                    .to_string()
+                    .as_str(),
+            )
+            .unwrap();
+        assert_eq!(saved.calibration, config.calibration);
+    }
+
+    #[test]
+    fn merge_profiles() {
+        let mut config = Config::default();
+        config
+            .merge(
+                r#"
+[profiles.Evening]
+brightness = 40
+effect = "breathe colour=2700K period=4s"
+leds = { head = "2700K", left = { red = 15, green = 10, blue = 5 } }
+hdmi = { source = "gpu" }
+
+[profiles.dim]
+brightness = { global = 50, head = 20, left = 100 }
+"#,
+            )
+            .unwrap();
+        let evening = &config.profiles["evening"];
+        assert_eq!(evening.state.zones[&Zone::Head], Colour::new(15, 10, 5));
+        assert_eq!(evening.state.zones[&Zone::Left], Colour::new(15, 10, 5));
+        assert_eq!(evening.state.hdmi_source, Some(HDMISource::Gpu));
+        assert_eq!(evening.brightness.as_ref().unwrap().global, 40);
+        assert_eq!(
+            evening.effect.as_deref(),
+            Some("breathe colour=2700K period=4s")
+        );
+        let dim = config.profiles["dim"].brightness.as_ref().unwrap();
+        assert_eq!(dim.global, 50);
+        assert_eq!(dim.zones.len(), 1);
+        assert_eq!(dim.zones[&Zone::Head], 20);
+        for invalid in [
+            "[profiles.bad]\nbrightness = 101\n",
+            "[profiles.bad]\nbrightness = { middle = 20 }\n",
+            "[profiles.bad]\nleds = { middle = \"red\" }\n",
+            "[profiles.bad]\neffect = \"\"\n",
+            "[profiles.bad]\nspeed = 2\n",
+            "profiles = 1\n",
+        ] {
+            assert!(config.merge(invalid).is_err(), "{invalid}");
+        }
+
+        // what is saved is read back the same
+        let mut saved = Config::default();
+        let mut profiles = toml::Table::new();
+        for (name, profile) in config.profiles.iter() {
+            profiles.insert(name.clone(), toml::Value::Table(profile_to_toml(profile)));
+        }
+        let mut table = toml::Table::new();
+        table.insert("profiles".to_string(), toml::Value::Table(profiles));
+        saved.merge(table.to_string().as_str()).unwrap();
+        assert_eq!(saved.profiles, config.profiles);
+    }
+
+    #[test]
+    fn edit_in_place() {
+        let dir = std::env::temp_dir().join(format!("alienware-config-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        write(
+            &path,
+            "# my groups\n[groups]\nfront = [\"head\"] # the head\n\n# for the evenings\n[profiles.Evening]\nbrightness = 40\n",
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config
+            .merge("[profiles.work]\nleds = { head = \"white\" }\n")
+            .unwrap();
+        save_profile(&path, "work", &config.profiles["work"]).unwrap();
+        save_profile(&path, "evening", &config.profiles["work"]).unwrap();
+        let mut calibration = HashMap::new();
+        let head = Calibration {
+            gamma: 2.0,
+            ..Calibration::default()
+        };
+        calibration.insert(Zone::Head, head);
+        save_calibration(&path, &calibration).unwrap();
+        let contents = read_to_string(&path).unwrap();
+        for kept in [
+            "# my groups\n",
+            "front = [\"head\"] # the head\n",
+            "# for the evenings\n[profiles.Evening]\n",
+        ] {
+            assert!(contents.contains(kept), "{contents}");
+        }
+        assert!(!contents.contains("brightness"), "{contents}");
+        let mut saved = Config::default();
+        saved.merge(contents.as_str()).unwrap();
+        assert_eq!(saved.profiles["evening"], config.profiles["work"]);
+        assert_eq!(saved.profiles["work"], config.profiles["work"]);
+        assert_eq!(saved.calibration, calibration);
+
+        assert_eq!(delete_profile(&path, "EVENING"), Ok(true));
+        assert_eq!(delete_profile(&path, "evening"), Ok(false));
+        let contents = read_to_string(&path).unwrap();
+        assert!(contents.contains("# my groups\n"), "{contents}");
+        assert!(!contents.contains("Evening"), "{contents}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_file() {
+        let dir = std::env::temp_dir().join(format!("alienware-unreadable-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        let contents = b"# not utf-8 \xff\n[groups]\nfront = [\"head\"]\n";
+        write(&path, contents).unwrap();
+        let mut calibration = HashMap::new();
+        calibration.insert(Zone::Head, Calibration::default());
+        assert!(save_calibration(&path, &calibration).is_err());
+        assert!(save_profile(&path, "work", &Profile::default()).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use crate::snap::{connect_command, snap_connected};
+use alienware::Alienware;
+use snapcraft::in_snap;
+use std::fs::{metadata, read_to_string, File, OpenOptions};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// The sysfs attributes of the alienware platform device, and whether they can be written
+const ATTRIBUTES: [(&str, bool); 5] = [
+    ("hdmi/cable", false),
+    ("hdmi/source", true),
+    ("rgb_zones/zone00", true),
+    ("rgb_zones/zone01", true),
+    ("rgb_zones/zone02", true),
+];
+
+/// How serious a finding is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Info,
+    Warn,
+    Fail,
+}
+
+/// Something that was found, and how to fix it if it is a problem
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub status: Status,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn new(status: Status, message: String) -> Finding {
+        Finding {
+            status,
+            message,
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: String) -> Finding {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Read the first line of a file, if it can be read
+fn read_line(path: &str) -> Option<String> {
+    read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+        .filter(|contents| !contents.is_empty())
+}
+
+/// Check the machine, the kernel module, the sysfs attributes and the snap setup
+pub fn diagnose(aw: &Alienware) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let kernel = read_line("/proc/sys/kernel/osrelease").unwrap_or("unknown".to_string());
+    findings.push(Finding::new(
+        Status::Info,
+        format!("Kernel version {kernel}"),
+    ));
+
+    let vendor = read_line("/sys/class/dmi/id/sys_vendor");
+    let product = read_line("/sys/class/dmi/id/product_name");
+    match (vendor, product) {
+        (Some(vendor), product) => {
+            let product = product.unwrap_or("unknown".to_string());
+            if vendor.contains("Alienware") || vendor.contains("Dell") {
+                findings.push(Finding::new(
+                    Status::Ok,
+                    format!("Machine is {vendor} {product}"),
+                ));
+            } else {
+                findings.push(
+                    Finding::new(
+                        Status::Warn,
+                        format!("Machine is {vendor} {product}, which does not look like an Alienware machine"),
+                    )
+                    .fix("alienware-cli only works on Alienware machines supported by the alienware-wmi kernel module".to_string()),
+                );
+            }
+        }
+        (None, _) => findings.push(Finding::new(
+            Status::Info,
+            "Machine vendor and product could not be read".to_string(),
+        )),
+    }
+
+    let uid = metadata("/proc/self").map(|m| m.uid()).ok();
+    findings.push(Finding::new(
+        Status::Info,
+        match uid {
+            Some(0) => "Running as root".to_string(),
+            Some(uid) => format!("Running as user {uid}, not root"),
+            None => "Running as an unknown user".to_string(),
+        },
+    ));
+
+    if Path::new("/sys/module/alienware_wmi").exists() {
+        findings.push(Finding::new(
+            Status::Ok,
+            "The alienware_wmi kernel module is loaded".to_string(),
+        ));
+    } else {
+        findings.push(
+            Finding::new(
+                Status::Fail,
+                "The alienware_wmi kernel module is not loaded".to_string(),
+            )
+            .fix("Load the module with: sudo modprobe alienware-wmi".to_string()),
+        );
+    }
+
+    let platform = aw.platform();
+    if aw.is_alienware() {
+        findings.push(Finding::new(
+            Status::Ok,
+            format!("The platform device {} exists", platform.display()),
+        ));
+        for (attribute, writable) in ATTRIBUTES {
+            findings.push(check_attribute(
+                platform,
+                attribute,
+                writable,
+                uid == Some(0),
+            ));
+        }
+    } else {
+        findings.push(
+            Finding::new(
+                Status::Fail,
+                format!("The platform device {} does not exist", platform.display()),
+            )
+            .fix("The alienware-wmi module did not find a supported device, check `sudo dmesg | grep -i alienware`".to_string()),
+        );
+    }
+
+    if in_snap() {
+        match snap_connected() {
+            Some(true) => findings.push(Finding::new(
+                Status::Ok,
+                "Running in a snap that is connected to the alienware device".to_string(),
+            )),
+            Some(false) => findings.push(
+                Finding::new(
+                    Status::Fail,
+                    "Running in a snap that is not connected to the alienware device".to_string(),
+                )
+                .fix(format!("Connect the snap with: {}", connect_command())),
+            ),
+            None => findings.push(
+                Finding::new(
+                    Status::Warn,
+                    "Running in a snap, but the connection to the alienware device could not be checked"
+                        .to_string(),
+                )
+                .fix(format!("Check the connection with `snap connections alienware-cli`, and connect it with: {}", connect_command())),
+            ),
+        }
+    }
+    findings
+}
+
+/// Check that an attribute exists and can be read, and written if it is a setting
+fn check_attribute(platform: &Path, attribute: &str, writable: bool, root: bool) -> Finding {
+    let path = platform.join(attribute);
+    if !path.exists() {
+        return Finding::new(
+            Status::Info,
+            format!("{attribute} does not exist on this machine"),
+        );
+    }
+    if let Err(x) = File::open(&path) {
+        return Finding::new(Status::Fail, format!("{attribute} cannot be read: {x}"))
+            .fix(permission_fix(root));
+    }
+    if writable {
+        if let Err(x) = OpenOptions::new().write(true).open(&path) {
+            return Finding::new(
+                Status::Fail,
+                format!("{attribute} can be read but not written: {x}"),
+            )
+            .fix(permission_fix(root));
+        }
+        Finding::new(Status::Ok, format!("{attribute} can be read and written"))
+    } else {
+        Finding::new(Status::Ok, format!("{attribute} can be read"))
+    }
+}
+
+/// How to fix a permissions problem on an attribute
+fn permission_fix(root: bool) -> String {
+    if in_snap() {
+        format!("Connect the snap with: {}", connect_command())
+    } else if root {
+        "Check for a security module (e.g. AppArmor or SELinux) blocking access to the device"
+            .to_string()
+    } else {
+        "Run alienware-cli with sudo, or add a udev rule giving your user access to the device"
+            .to_string()
+    }
+}
+
+/// Print the diagnosis and the steps to fix any problems, returning the exit code
+pub fn doctor(aw: &Alienware) -> i32 {
+    let findings = diagnose(aw);
+    for finding in findings.iter() {
+        let status = match finding.status {
+            Status::Ok => "[ OK ]",
+            Status::Info => "[INFO]",
+            Status::Warn => "[WARN]",
+            Status::Fail => "[FAIL]",
+        };
+        println!("{status} {}", finding.message);
+        if let Some(fix) = &finding.fix {
+            println!("       {fix}");
+        }
+    }
+    if findings
+        .iter()
+        .any(|finding| finding.status == Status::Fail)
+    {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_attribute, Status};
+    use std::fs::{create_dir_all, write};
+    use std::path::Path;
+
+    #[test]
+    fn attributes() {
+        let platform = Path::new("/tmp/alienware_cli_test/doctor/alienware-wmi");
+        create_dir_all(platform.join("hdmi")).unwrap();
+        write(platform.join("hdmi/source"), "cable [gpu] unknown").unwrap();
+        let finding = check_attribute(platform, "hdmi/source", true, true);
+        assert_eq!(finding.status, Status::Ok);
+        assert_eq!(finding.message, "hdmi/source can be read and written");
+        let finding = check_attribute(platform, "rgb_zones/zone02", true, true);
+        assert_eq!(finding.status, Status::Info);
+    }
+}
@@ -0,0 +1,28 @@
+use alienware::{dump as dump_archive, Alienware};
+use std::fs::File;
+use std::io::{stdout, Write};
+
+/// Write a bug report archive of the sysfs files, DMI ids and module parameters, returning the exit code
+pub fn dump(aw: &Alienware, file: &str) -> i32 {
+    let result = if file == "-" {
+        dump_archive(aw, stdout().lock()).and_then(|mut out| out.flush())
+    } else {
+        File::create(file)
+            .and_then(|out| dump_archive(aw, out))
+            .and_then(|out| out.sync_all())
+    };
+    match result {
+        Ok(()) => {
+            if file != "-" {
+                println!(
+                    "Wrote {file}, which can be read back with: alienware-cli --replay {file} -lc"
+                );
+            }
+            0
+        }
+        Err(x) => {
+            eprintln!("Problem writing the archive {file}: {x}");
+            1
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::config::Config;
+use crate::print_error;
+use crate::restore::{restore_if_stopped, stop_on_signal, SavedColours};
+use crate::zones::resolve_zones;
+use alienware::effects::{EffectSpec, ParamValue, Registry};
+use alienware::{FrameScheduler, MonotonicClock, SharedAlienware, Zone};
+use std::sync::atomic::AtomicBool;
+use std::sync::OnceLock;
+
+/// The most frames a second that an effect can be shown at
+pub const MAX_FPS: u32 = 100;
+
+/// The values given for the parameters of an effect, with the zones to show it on and how often to update them
+#[derive(Clone, Debug, PartialEq)]
+pub struct EffectOptions {
+    pub values: Vec<(String, ParamValue)>,
+    pub zones: String,
+    pub fps: u32,
+}
+
+/// The effects that can be shown, which are built once and passed to everything that looks effects up by name
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+/// Parse the `name=value` parameters of an effect.  As well as the effect's own parameters, which are parsed by the
+/// kind of value each one takes, every effect takes `zones` and `fps`.
+pub fn parse_params<S: AsRef<str>>(
+    spec: &EffectSpec,
+    params: &[S],
+) -> Result<EffectOptions, String> {
+    let mut options = EffectOptions {
+        values: Vec::new(),
+        zones: "all".to_string(),
+        fps: 30,
+    };
+    let mut own = Vec::new();
+    for param in params {
+        let param = param.as_ref();
+        let (key, value) = match param.split_once('=') {
+            Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+            None => {
+                own.push(param);
+                continue;
+            }
+        };
+        match key.as_str() {
+            "zones" => options.zones = value.to_string(),
+            "fps" => {
+                options.fps = match value.parse::<u32>() {
+                    Ok(fps) if (1..=MAX_FPS).contains(&fps) => fps,
+                    _ => return Err(format!("fps must be a number from 1 to {MAX_FPS}")),
+                }
+            }
+            _ => own.push(param),
+        }
+    }
+    options.values = spec.parse_params(&own).map_err(|x| x.to_string())?;
+    Ok(options)
+}
+
+/// Print the effects and the parameters they take, returning the exit code
+pub fn list_effects(registry: &Registry) -> i32 {
+    for spec in registry.effects() {
+        println!("{}: {}", spec.name, spec.description);
+        for param in spec.params.iter() {
+            println!(
+                "    {}={}  {} ({})",
+                param.name, param.default, param.description, param.kind
+            );
+        }
+    }
+    println!("Every effect also takes zones=<zone or group> (default all) and fps=<1-{MAX_FPS}> (default 30)");
+    0
+}
+
+/// Show an effect until Ctrl-C is pressed, then put back the colours from before it unless they are to be kept,
+/// returning the exit code
+pub fn effect<S: AsRef<str>>(
+    aw: &SharedAlienware,
+    config: &Config,
+    keep: bool,
+    registry: &Registry,
+    name: &str,
+    params: &[S],
+) -> i32 {
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    show_effect(aw, config, keep, registry, name, params, &running)
+}
+
+/// Show an effect until `running` is cleared, then put back the colours from before it unless they are to be kept,
+/// returning the exit code
+pub fn show_effect<S: AsRef<str>>(
+    aw: &SharedAlienware,
+    config: &Config,
+    keep: bool,
+    registry: &Registry,
+    name: &str,
+    params: &[S],
+    running: &AtomicBool,
+) -> i32 {
+    let spec = match registry.get(name) {
+        Some(spec) => spec,
+        None => {
+            let names: Vec<&str> = registry.effects().map(|spec| spec.name.as_str()).collect();
+            println!("'{name}' is not an effect, try one of {}", names.join(", "));
+            return 1;
+        }
+    };
+    let options = match parse_params(spec, params) {
+        Ok(options) => options,
+        Err(x) => {
+            println!("{x}");
+            return 1;
+        }
+    };
+    let mut effect = match spec.build(options.values) {
+        Ok(effect) => effect,
+        Err(x) => {
+            println!("{x}");
+            return 1;
+        }
+    };
+    let leds = match aw.get_rgb_zones() {
+        Ok(leds) => leds,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    if !leds.exists {
+        println!("There is no alienware LED unit on this machine");
+        return 1;
+    }
+    let discovered: Vec<Zone> = leds.zones.keys().copied().collect();
+    let zones = match resolve_zones(options.zones.as_str(), config, &discovered) {
+        Ok(zones) => zones,
+        Err(x) => {
+            println!("{x}");
+            return 1;
+        }
+    };
+
+    let saved = match SavedColours::capture(aw.unlocked()) {
+        Ok(saved) => saved,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    println!("Showing {}, press Ctrl-C to stop", spec.name);
+    let clock = MonotonicClock::new();
+    let mut scheduler = FrameScheduler::new(&clock, options.fps);
+    let result = aw.run_effect(effect.as_mut(), &zones, &mut scheduler, running, None);
+    if scheduler.dropped() > 0 {
+        println!(
+            "Dropped {} frames that could not be shown in time, try a lower fps",
+            scheduler.dropped()
+        );
+    }
+    match result {
+        Ok(()) => restore_if_stopped(aw, &saved, running, keep, 0),
+        Err(x) => {
+            print_error("Problem setting RGB value", &x);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_params, EffectOptions};
+    use alienware::effects::{ParamValue, Registry};
+    use alienware::Colour;
+    use std::time::Duration;
+
+    #[test]
+    fn parse() {
+        let registry = Registry::default();
+        let breathe = registry.get("Breathe").unwrap();
+        assert_eq!(
+            parse_params(breathe, &["color=red", "period=2s", "zones=sides"]),
+            Ok(EffectOptions {
+                values: vec![
+                    (
+                        "colour".to_string(),
+                        ParamValue::Colour(Colour::new(15, 0, 0))
+                    ),
+                    (
+                        "period".to_string(),
+                        ParamValue::Duration(Duration::from_secs(2))
+                    ),
+                ],
+                zones: "sides".to_string(),
+                fps: 30,
+            })
+        );
+        let cycle = registry.get("cycle").unwrap();
+        assert_eq!(
+            parse_params(cycle, &["palette=red,#0000ff", "fps=10"]),
+            Ok(EffectOptions {
+                values: vec![(
+                    "palette".to_string(),
+                    ParamValue::Palette(vec![Colour::new(15, 0, 0), Colour::new(0, 0, 15)])
+                )],
+                zones: "all".to_string(),
+                fps: 10,
+            })
+        );
+        assert!(parse_params(registry.get("rainbow").unwrap(), &["colour=red"]).is_err());
+        let strobe = registry.get("strobe").unwrap();
+        for params in [["duty=2"], ["duty=half"], ["period=0s"], ["2s"], ["fps=0"]] {
+            assert!(parse_params(strobe, &params).is_err(), "{params:?}");
+        }
+    }
+}
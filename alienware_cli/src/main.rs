@@ -1,12 +1,37 @@
+mod apply;
+mod batch;
+mod brightness;
+mod calibrate;
 mod cli;
+mod colour;
+mod command;
+mod completions;
+mod config;
+mod doctor;
+mod dump;
+mod effect;
+mod notify;
+mod play;
+mod profile;
+mod repl;
+mod restore;
+mod selftest;
+mod set;
+mod snap;
+mod zones;
 
-use alienware::{Alienware, Zone};
-use clap::Parser;
+use alienware::{
+    Alienware, AlienwareGuard, Change, SharedAlienware, Transition, Zone, MAX_BRIGHTNESS,
+};
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
+use cli::{Command, ProfileCommand};
+use colour::parse_rgb_string;
 use jzon::object;
-use regex::Regex;
-use snapcraft::in_snap;
+use snap::check_snap;
+use std::fs::File;
 use std::io::ErrorKind;
-use std::process::{exit, Command};
+use std::process::exit;
 use std::sync::OnceLock;
 
 fn main() {
@@ -18,6 +43,8 @@ fn main() {
         )
     });
 
+    CompleteEnv::with_factory(cli::Options::command).complete();
+
     let options = cli::Options::parse();
 
     if options.version {
@@ -25,7 +52,28 @@ fn main() {
         exit(0);
     }
 
-    let aw = Alienware::new();
+    let aw = match &options.replay {
+        Some(archive) => match File::open(archive).and_then(Alienware::from_archive) {
+            Ok(aw) => aw,
+            Err(x) => {
+                println!("Problem reading the archive {}: {x}", archive.display());
+                exit(1);
+            }
+        },
+        None => Alienware::new()
+            .brightness_file(snap::brightness_file())
+            .brightness_warnings(|x| println!("Problem with the brightness file: {x}")),
+    };
+    let aw = if options.dry_run {
+        aw.dry_run(|write| println!("Would write {write}"))
+    } else {
+        aw
+    };
+    let config = config::Config::load();
+    config.calibrate(&aw);
+    // every write goes through the lock so that it is not interleaved with those of other processes
+    let aw = SharedAlienware::with_lock_file(aw, Some(snap::lock_file()));
+    let registry = effect::registry();
     let mut json_data = object! {};
 
     if options.connector {
@@ -52,30 +100,30 @@ fn main() {
                 println!();
             }
         } else if let Err(x) = hdmi {
-            match x.kind() {
-                ErrorKind::PermissionDenied => {
-                    println!("You do not have permission to run this command (do you need sudo?)");
-                    check_snap();
-                }
-                _ => {
-                    println!("Problem getting HDMI state {:?} ", x.kind());
-                }
-            }
+            print_error("Problem getting HDMI state", &x);
         }
     }
 
     if options.led_state {
         let leds = aw.get_rgb_zones();
         if let Ok(leds) = leds {
+            let brightness = aw.get_brightness().unwrap_or_default();
             if options.json {
                 let mut leds_data = object! {
                     "exists": leds.exists,
                 };
                 for zone in leds.zones.values() {
+                    let colour = aw.zone_colour(zone);
                     let zone_data = object! {
-                        "red": zone.red,
-                        "green": zone.green,
-                        "blue": zone.blue,
+                        "red": colour.red,
+                        "green": colour.green,
+                        "blue": colour.blue,
+                        "brightness": brightness.of_zone(zone.zone),
+                        "effective": {
+                            "red": zone.red,
+                            "green": zone.green,
+                            "blue": zone.blue,
+                        },
                     };
                     leds_data
                         .insert(format!("{}", zone.zone).as_str(), zone_data)
@@ -87,10 +135,15 @@ fn main() {
                 if leds.exists {
                     println!("present");
                     for zone in leds.zones.values() {
+                        let colour = aw.zone_colour(zone);
                         println!("    {}:", zone.zone);
-                        println!("        red: {}", zone.red);
-                        println!("        green: {}", zone.green);
-                        println!("        blue: {}", zone.blue);
+                        println!("        red: {}", colour.red);
+                        println!("        green: {}", colour.green);
+                        println!("        blue: {}", colour.blue);
+                        let percent = brightness.of_zone(zone.zone);
+                        if percent != MAX_BRIGHTNESS {
+                            println!("        brightness: {percent}%, lit at {}", zone.colour());
+                        }
                     }
                 } else {
                     println!("not present");
@@ -98,54 +151,137 @@ fn main() {
                 println!();
             }
         } else if let Err(x) = leds {
-            match x.kind() {
-                ErrorKind::PermissionDenied => {
-                    println!("You do not have permission to run this command (do you need sudo?)");
-                    check_snap();
-                }
-                _ => {
-                    println!("Problem getting LED state {:?} ", x.kind());
-                }
-            }
+            print_error("Problem getting LED state", &x);
         }
     }
 
-    if options.json {
+    if options.json && (options.connector || options.led_state) {
         println!("{}", json_data.dump());
     }
 
+    let fade = options.fade.map(|duration| {
+        Transition::new(duration)
+            .easing(options.easing)
+            .space(options.space)
+    });
+
     if let Some(head) = options.head {
-        set_led_zone_rgb(&aw, Zone::Head, head);
+        set_led_zone_rgb(&lock(&aw), Zone::Head, head, fade);
     }
 
     if let Some(left) = options.left {
-        set_led_zone_rgb(&aw, Zone::Left, left);
+        set_led_zone_rgb(&lock(&aw), Zone::Left, left, fade);
     }
 
     if let Some(right) = options.right {
-        set_led_zone_rgb(&aw, Zone::Right, right);
+        set_led_zone_rgb(&lock(&aw), Zone::Right, right, fade);
+    }
+
+    if let Some(command) = options.command {
+        let code = match command {
+            Command::Apply {
+                file,
+                format,
+                reconcile,
+                interval,
+            } => {
+                if reconcile {
+                    apply::reconcile(&aw, file.as_str(), format, interval)
+                } else {
+                    apply::apply(&lock(&aw), file.as_str(), format, fade)
+                }
+            }
+            Command::Set { assignments } => set::set(&lock(&aw), &config, fade, &assignments),
+            Command::Batch { file } => {
+                batch::batch(&aw, &config, fade, file.as_str(), options.keep)
+            }
+            Command::Repl => repl::repl(&aw, &config, fade, options.keep),
+            Command::Brightness { percent, zones } => {
+                brightness::brightness(&lock(&aw), &config, percent, zones.as_deref())
+            }
+            Command::Profile { action } => match action {
+                ProfileCommand::List => profile::list_profiles(&config),
+                ProfileCommand::Show { name } => profile::show_profile(&config, name.as_str()),
+                ProfileCommand::Apply { name } => profile::apply_profile(
+                    &aw,
+                    &config,
+                    registry,
+                    name.as_str(),
+                    fade,
+                    options.keep,
+                ),
+                ProfileCommand::Save { name, system } => {
+                    profile::save(aw.unlocked(), name.as_str(), system)
+                }
+                ProfileCommand::Delete { name, system } => {
+                    profile::delete(&config, name.as_str(), system)
+                }
+            },
+            Command::Effect { list, name, params } => match name {
+                Some(name) if !list => {
+                    effect::effect(&aw, &config, options.keep, registry, name.as_str(), &params)
+                }
+                _ => effect::list_effects(registry),
+            },
+            Command::Notify {
+                colour,
+                zones,
+                duration,
+                blend,
+                opacity,
+            } => notify::notify(
+                &aw,
+                &config,
+                zones.as_str(),
+                notify::Notification {
+                    colour,
+                    duration,
+                    blend,
+                    opacity,
+                },
+                options.keep,
+            ),
+            Command::Play { file, format, fps } => {
+                play::play(&aw, file.as_str(), format, fps, options.keep)
+            }
+            Command::Calibrate { reference, system } => {
+                calibrate::calibrate(&aw, reference, system)
+            }
+            Command::Selftest { delay } => selftest::selftest(&lock(&aw), delay, options.json),
+            Command::Doctor => doctor::doctor(aw.unlocked()),
+            Command::Dump { file } => dump::dump(aw.unlocked(), file.as_str()),
+            Command::Completions { shell } => completions::completions(shell),
+            Command::Manpage { dir } => completions::manpage(dir.as_deref()),
+        };
+        // exit does not run destructors, so the colours written are saved to the brightness file first
+        drop(aw);
+        exit(code);
     }
 }
 
 /// Set the chosen Zone to the specified RGB
-fn set_led_zone_rgb(aw: &Alienware, zone: Zone, input: String) {
+fn set_led_zone_rgb(aw: &Alienware, zone: Zone, input: String, fade: Option<Transition>) {
     let leds = aw.get_rgb_zones();
     if let Ok(leds) = leds {
         if leds.exists {
-            if leds.zones.contains_key(&zone) {
-                let (r, g, b) = parse_rgb_string(input.as_str());
-                match aw.set_rgb_zone(zone, r, g, b) {
-                    Ok(_) => {}
-                    Err(x) => match x.kind() {
-                        ErrorKind::PermissionDenied => {
-                            println!("You do not have permission to run this command (do you need sudo?)");
-                            check_snap();
-                        }
-                        _ => {
-                            println!("Problem setting RGB value {:?} ", x.kind());
-                        }
-                    },
+            if let Some(current) = leds.zones.get(&zone) {
+                let result = match fade {
+                    Some(transition) => {
+                        let change = Change::Zone {
+                            zone,
+                            from: Some(aw.zone_colour(current)),
+                            to: parse_rgb_string(input.as_str()).into(),
+                        };
+                        aw.transition_changes(&[change], transition)
+                    }
+                    None => {
+                        let (r, g, b) = parse_rgb_string(input.as_str());
+                        aw.set_rgb_zone(zone, r, g, b)
+                    }
                 };
+                if let Err(x) = result {
+                    print_error("Problem setting RGB value", &x);
+                }
             } else {
                 println!("There are no {zone} LEDs");
             }
@@ -153,73 +289,30 @@ fn set_led_zone_rgb(aw: &Alienware, zone: Zone, input: String) {
             println!("There is no alienware LED unit on this machine");
         }
     } else if let Err(x) = leds {
-        match x.kind() {
-            ErrorKind::PermissionDenied => {
-                println!("You do not have permission to run this command (do you need sudo?)");
-                check_snap();
-            }
-            _ => {
-                println!("Problem setting RGB value {:?} ", x.kind());
-            }
-        }
+        print_error("Problem setting RGB value", &x);
     }
 }
 
-/// Parse the RGB value of the input string, either a named colour, or an rgb value
-fn parse_rgb_string(input: &str) -> (u8, u8, u8) {
-    let input = input.to_lowercase();
-    match input.as_str() {
-        "black" => (0u8, 0u8, 0u8),
-        "white" => (15u8, 15u8, 15u8),
-        "red" => (15u8, 0u8, 0u8),
-        "yellow" => (15u8, 15u8, 0u8),
-        "green" => (0u8, 15u8, 0u8),
-        "cyan" => (0u8, 15u8, 15u8),
-        "blue" => (0u8, 0u8, 15u8),
-        "magenta" => (15u8, 0u8, 15u8),
-        _ => {
-            static RE: OnceLock<Regex> = OnceLock::new();
-            let re = RE.get_or_init(|| Regex::new(r"(\d+) (\d+) (\d+)").unwrap());
-            match re.captures(input.as_str()) {
-                Some(caps) => {
-                    if caps.len() == 4 {
-                        let red = &caps[1];
-                        let green = &caps[2];
-                        let blue = &caps[3];
-                        (
-                            red.parse::<u8>().unwrap(),
-                            green.parse::<u8>().unwrap(),
-                            blue.parse::<u8>().unwrap(),
-                        )
-                    } else {
-                        (0u8, 0u8, 15u8) // setting blue as the default
-                    }
-                }
-                _ => (0u8, 0u8, 15u8), // setting blue as the default
-            }
+/// Wait for exclusive access to write to the device, exiting if the lock cannot be taken
+fn lock(aw: &SharedAlienware) -> AlienwareGuard<'_> {
+    match aw.lock() {
+        Ok(guard) => guard,
+        Err(x) => {
+            print_error("Problem locking the device", &x);
+            exit(1);
         }
     }
 }
 
-fn check_snap() {
-    if in_snap() {
-        let is_snap_connected = {
-            let snap_connected_status = Command::new("snapctl")
-                .arg("is-connected")
-                .arg("alienware")
-                .status()
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to check whether snap is able to read /sys/devices/platform/alienware-wmi"
-                    )
-                });
-            snap_connected_status.success()
-        };
-        if is_snap_connected {
-            println!("This may be because you have installed alienware-cli from snap, which prevents automatic setup.\n");
-            print!("The snap container initially blocks access to the alienware device setup that is needed to carry out this action.  ");
-            println!("The following command can be run to unblock access to the alienware device and then you can try again:\n");
-            println!("    sudo snap connect alienware-wmi:alienware\n \n",);
+/// Print an error from the alienware API, with advice if it was a permissions problem
+fn print_error(message: &str, x: &std::io::Error) {
+    match x.kind() {
+        ErrorKind::PermissionDenied => {
+            println!("You do not have permission to run this command (do you need sudo?)");
+            check_snap();
+        }
+        _ => {
+            println!("{message} {:?} ", x.kind());
         }
     }
 }
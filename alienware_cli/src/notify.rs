@@ -0,0 +1,146 @@
+use crate::config::Config;
+use crate::print_error;
+use crate::restore::stop_on_signal;
+use crate::zones::resolve_zones;
+use alienware::effects::Effect;
+use alienware::{
+    BlendMode, Colour, Compositor, FrameScheduler, Layer, MonotonicClock, SharedAlienware, Zone,
+    ZoneWriter,
+};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// How many times a second the zones are updated during a notification
+const NOTIFY_FPS: u32 = 30;
+
+/// The name of the layer that a notification is shown on
+const NOTIFICATION: &str = "notification";
+
+/// How a notification is shown over the colours that the zones already have
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Notification {
+    pub colour: Colour,
+    pub duration: Duration,
+    pub blend: BlendMode,
+    pub opacity: f32,
+}
+
+/// Show a colour over some zones for a time, then put back the colours they had before, returning the exit code.  If
+/// it is stopped by a signal the colours are put back straight away, unless the colours shown then are to be kept.
+pub fn notify(
+    aw: &SharedAlienware,
+    config: &Config,
+    zones: &str,
+    notification: Notification,
+    keep: bool,
+) -> i32 {
+    let leds = match aw.get_rgb_zones() {
+        Ok(leds) => leds,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    if !leds.exists {
+        println!("There is no alienware LED unit on this machine");
+        return 1;
+    }
+    let discovered: Vec<Zone> = leds.zones.keys().copied().collect();
+    let zones = match resolve_zones(zones, config, &discovered) {
+        Ok(zones) => zones,
+        Err(x) => {
+            println!("{x}");
+            return 1;
+        }
+    };
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    match show_notification(aw, &zones, notification, &running, keep) {
+        Ok(()) => 0,
+        Err(x) => {
+            print_error("Problem setting RGB value", &x);
+            1
+        }
+    }
+}
+
+/// Show a notification over the colours that some zones have now until it expires or `running` is cleared, and then
+/// restore them unless they are to be kept when it is stopped early
+pub fn show_notification(
+    aw: &SharedAlienware,
+    zones: &[Zone],
+    notification: Notification,
+    running: &AtomicBool,
+    keep: bool,
+) -> std::io::Result<()> {
+    let mut compositor = Compositor::new();
+    compositor.add(Layer::colours("base", Layer::BASE, aw.get_zone_colours()?));
+    compositor.add(
+        Layer::colours(
+            NOTIFICATION,
+            Layer::NOTIFICATION,
+            zones
+                .iter()
+                .map(|zone| (*zone, notification.colour))
+                .collect(),
+        )
+        .blend(notification.blend)
+        .opacity(notification.opacity)
+        .expire_after(notification.duration),
+    );
+    let clock = MonotonicClock::new();
+    aw.run_effect(
+        &mut compositor,
+        zones,
+        &mut FrameScheduler::new(&clock, NOTIFY_FPS),
+        running,
+        Some(notification.duration),
+    )?;
+    // stopped before the notification expired, so the base has not been shown again yet
+    if compositor.remove(NOTIFICATION) && !keep {
+        let aw = aw.lock()?;
+        let mut writer = ZoneWriter::new(&aw);
+        for (zone, colour) in compositor.frame(notification.duration, zones) {
+            writer.set(zone, colour)?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{show_notification, Notification};
+    use alienware::{Alienware, BlendMode, Colour, SharedAlienware, Zone};
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    #[test]
+    fn restore_after_notification() {
+        let aw = SharedAlienware::new(Alienware::in_memory());
+        let before = aw.get_rgb_zones().unwrap();
+        let notification = Notification {
+            colour: Colour::new(15, 0, 0),
+            duration: Duration::from_millis(50),
+            blend: BlendMode::Normal,
+            opacity: 1.0,
+        };
+        // it is put back when it ends even if the colours are kept when stopped early
+        for (running, keep) in [(true, false), (false, false), (true, true)] {
+            show_notification(
+                &aw,
+                &[Zone::Head, Zone::Left],
+                notification,
+                &AtomicBool::new(running),
+                keep,
+            )
+            .unwrap();
+            assert_eq!(aw.get_rgb_zones().unwrap(), before);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::apply::read_document;
+use crate::cli::Format;
+use crate::print_error;
+use crate::restore::{restore_if_stopped, stop_on_signal, SavedColours};
+use alienware::document;
+use alienware::show::Show;
+use alienware::SharedAlienware;
+
+/// Play a light show from a JSON or TOML document until it ends or Ctrl-C is pressed, when the colours from before it
+/// are put back unless they are to be kept, returning the exit code
+pub fn play(aw: &SharedAlienware, file: &str, format: Option<Format>, fps: u32, keep: bool) -> i32 {
+    let contents = match read_document(file) {
+        Ok(contents) => contents,
+        Err(x) if file == "-" => {
+            println!("Problem reading the show from stdin: {x}");
+            return 1;
+        }
+        Err(x) => {
+            println!("Problem reading the show {file}: {x}");
+            return 1;
+        }
+    };
+    let format = format
+        .map(Format::into)
+        .or_else(|| document::Format::from_name(file));
+    let show = match Show::from_document(contents.as_str(), format) {
+        Ok(show) => show,
+        Err(x) => {
+            println!("Invalid show: {x}");
+            return 1;
+        }
+    };
+    match aw.unlocked().validate_show(&show) {
+        Ok(errors) if errors.is_empty() => {}
+        Ok(errors) => {
+            println!("The show cannot be played on this machine:");
+            for error in errors {
+                println!("    {error}");
+            }
+            return 1;
+        }
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    }
+
+    let saved = match SavedColours::capture(aw.unlocked()) {
+        Ok(saved) => saved,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    let name = show.name.as_deref().unwrap_or(file);
+    match show.length() {
+        Some(length) => println!(
+            "Playing {name} for {:.1}s, press Ctrl-C to stop",
+            length.as_secs_f64()
+        ),
+        None => println!("Playing {name}, press Ctrl-C to stop"),
+    }
+    match aw.play(&show, fps, &running) {
+        Ok(()) => restore_if_stopped(aw, &saved, &running, keep, 0),
+        Err(x) => {
+            print_error("Problem setting RGB value", &x);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alienware::show::Show;
+    use alienware::Zone;
+    use std::time::Duration;
+
+    #[test]
+    fn example_show() {
+        let show = Show::from_toml(include_str!("../../scripts/alienware-example.toml")).unwrap();
+        assert!(show.validate().is_empty());
+        assert_eq!(show.zones(), vec![Zone::Head, Zone::Left]);
+        assert_eq!(show.length(), Some(Duration::from_secs(31)));
+    }
+}
@@ -0,0 +1,347 @@
+use crate::apply::apply_state;
+use crate::command::split_words;
+use crate::config::{
+    config_path, delete_profile, profile_to_toml, save_profile, Config, Profile, SYSTEM_CONFIG,
+};
+use crate::effect::show_effect;
+use crate::print_error;
+use crate::restore::{restore_if_stopped, stop_on_signal, SavedColours};
+use alienware::effects::Registry;
+use alienware::{Alienware, Brightness, SharedAlienware, Transition, MAX_BRIGHTNESS};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Print the names of the profiles in the config files, returning the exit code
+pub fn list_profiles(config: &Config) -> i32 {
+    if config.profiles.is_empty() {
+        println!("There are no profiles, save the current lighting as one with `alienware-cli profile save <name>`");
+    }
+    for name in config.profiles.keys() {
+        println!("{name}");
+    }
+    0
+}
+
+/// Print the settings of a profile as they are written in the config file, returning the exit code
+pub fn show_profile(config: &Config, name: &str) -> i32 {
+    match find_profile(config, name) {
+        Some(profile) => {
+            print!("{}", profile_to_toml(profile));
+            0
+        }
+        None => 1,
+    }
+}
+
+/// Set the brightness, colours and HDMI source of a profile as one update, and then show its effect if it has one until
+/// it is stopped by Ctrl-C, returning the exit code
+pub fn apply_profile(
+    shared: &SharedAlienware,
+    config: &Config,
+    registry: &Registry,
+    name: &str,
+    fade: Option<Transition>,
+    keep: bool,
+) -> i32 {
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    apply_profile_with(shared, config, registry, name, fade, keep, &running)
+}
+
+/// Apply a profile as [`apply_profile`] does until `running` is cleared.  If it is cleared part way through the fade to
+/// the colours of the profile, the brightness and colours from before it are put back unless they are to be kept.
+pub fn apply_profile_with(
+    shared: &SharedAlienware,
+    config: &Config,
+    registry: &Registry,
+    name: &str,
+    fade: Option<Transition>,
+    keep: bool,
+    running: &AtomicBool,
+) -> i32 {
+    let profile = match find_profile(config, name) {
+        Some(profile) => profile,
+        None => return 1,
+    };
+    let saved = match SavedColours::capture(shared.unlocked()) {
+        Ok(saved) => saved,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    let before = match shared.get_brightness() {
+        Ok(brightness) => brightness,
+        Err(x) => {
+            print_error("Problem getting the brightness", &x);
+            return 1;
+        }
+    };
+    let aw = match shared.lock() {
+        Ok(aw) => aw,
+        Err(x) => {
+            print_error("Problem locking the device", &x);
+            return 1;
+        }
+    };
+    // check the whole profile before the brightness is changed
+    match aw.validate_state(&profile.state) {
+        Ok(errors) if errors.is_empty() => {}
+        Ok(errors) => {
+            println!("The profile {name} cannot be applied to this machine:");
+            for error in errors {
+                println!("    {error}");
+            }
+            return 1;
+        }
+        Err(x) => {
+            print_error("Problem getting the current state", &x);
+            return 1;
+        }
+    }
+    if let Some(brightness) = profile.brightness.as_ref().filter(|b| **b != before) {
+        if let Err(x) = set_brightness(&aw, brightness) {
+            print_error("Problem setting the brightness", &x);
+            return 1;
+        }
+        println!("Brightness: {brightness}");
+    }
+    if !profile.state.zones.is_empty() || profile.state.hdmi_source.is_some() {
+        let code = apply_state(&aw, &profile.state, fade, running);
+        if code != 0 {
+            return code;
+        }
+    }
+    if !running.load(Ordering::SeqCst) {
+        // the brightness goes back first, as the colours put back are scaled by it
+        if !keep {
+            if let Err(x) = set_brightness(&aw, &before) {
+                print_error("Problem restoring the brightness", &x);
+                return 1;
+            }
+        }
+        drop(aw);
+        return restore_if_stopped(shared, &saved, running, keep, 0);
+    }
+    // the effect takes the lock for each frame
+    drop(aw);
+    let words = match profile.effect.as_deref().map(split_words) {
+        Some(Ok(words)) => words,
+        Some(Err(x)) => {
+            println!("The effect of profile {name} cannot be read: {x}");
+            return 1;
+        }
+        None => return 0,
+    };
+    match words.split_first() {
+        Some((effect, params)) => show_effect(
+            shared,
+            config,
+            keep,
+            registry,
+            effect.as_str(),
+            params,
+            running,
+        ),
+        None => 0,
+    }
+}
+
+/// Save the colours, brightness and HDMI source that the machine has now as a profile in the user's config file, or the
+/// system wide one, returning the exit code
+pub fn save(aw: &Alienware, name: &str, system: bool) -> i32 {
+    let leds = match aw.get_rgb_zones() {
+        Ok(leds) => leds,
+        Err(x) => {
+            print_error("Problem getting LED state", &x);
+            return 1;
+        }
+    };
+    if !leds.exists {
+        println!("There is no alienware LED unit on this machine");
+        return 1;
+    }
+    let path = match config_path(system) {
+        Some(path) => path,
+        None => {
+            println!(
+                "The user's config file cannot be found, use --system to save to {SYSTEM_CONFIG}"
+            );
+            return 1;
+        }
+    };
+    let profile = match capture_profile(aw) {
+        Ok(profile) => profile,
+        Err(x) => {
+            print_error("Problem getting the current state", &x);
+            return 1;
+        }
+    };
+    match save_profile(&path, name, &profile) {
+        Ok(()) => {
+            println!("Saved profile {name} to {}", path.display());
+            0
+        }
+        Err(x) => {
+            println!("Problem saving the profile: {x}");
+            1
+        }
+    }
+}
+
+/// Delete a profile from the user's config file, or the system wide one, returning the exit code
+pub fn delete(config: &Config, name: &str, system: bool) -> i32 {
+    let path = match config_path(system) {
+        Some(path) => path,
+        None => {
+            println!("The user's config file cannot be found, use --system to delete from {SYSTEM_CONFIG}");
+            return 1;
+        }
+    };
+    match delete_profile(&path, name) {
+        Ok(true) => {
+            println!("Deleted profile {name} from {}", path.display());
+            0
+        }
+        Ok(false) if config.profiles.contains_key(&name.to_lowercase()) => {
+            if system {
+                println!(
+                    "The profile {name} is in the user's config file, delete it without --system"
+                );
+            } else {
+                println!("The profile {name} is in {SYSTEM_CONFIG}, delete it with --system");
+            }
+            1
+        }
+        Ok(false) => {
+            println!("There is no profile {name} in {}", path.display());
+            1
+        }
+        Err(x) => {
+            println!("Problem deleting the profile: {x}");
+            1
+        }
+    }
+}
+
+/// The colours, brightness and HDMI source that the machine has now
+pub fn capture_profile(aw: &Alienware) -> std::io::Result<Profile> {
+    Ok(Profile {
+        state: aw.get_state()?,
+        brightness: Some(aw.get_brightness()?),
+        effect: None,
+    })
+}
+
+/// Find a profile by name, printing the profiles that there are if it is not one of them
+fn find_profile<'a>(config: &'a Config, name: &str) -> Option<&'a Profile> {
+    let profile = config.profiles.get(&name.to_lowercase());
+    if profile.is_none() {
+        if config.profiles.is_empty() {
+            println!("There is no profile {name}, there are no profiles in the config files");
+        } else {
+            let names: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+            println!(
+                "There is no profile {name}, try one of {}",
+                names.join(", ")
+            );
+        }
+    }
+    profile
+}
+
+/// Set the global brightness and that of every zone, where a zone that is not given is set to full brightness
+fn set_brightness(aw: &Alienware, brightness: &Brightness) -> std::io::Result<()> {
+    if aw.get_brightness()? == *brightness {
+        return Ok(());
+    }
+    aw.set_brightness(brightness.global)?;
+    for zone in aw.get_rgb_zones()?.zones.keys() {
+        let percent = brightness
+            .zones
+            .get(zone)
+            .copied()
+            .unwrap_or(MAX_BRIGHTNESS);
+        aw.set_zone_brightness(*zone, percent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_profile_with, capture_profile};
+    use crate::config::Config;
+    use alienware::effects::Registry;
+    use alienware::{Alienware, Brightness, Colour, SharedAlienware, Zone};
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn apply_and_capture() {
+        let shared = SharedAlienware::new(Alienware::in_memory());
+        let aw = shared.unlocked();
+        let registry = Registry::default();
+        let mut config = Config::default();
+        config
+            .merge("[profiles.Evening]\nbrightness = { global = 50, right = 20 }\nleds = { head = \"2700K\", left = \"red\" }\n")
+            .unwrap();
+        assert_eq!(
+            apply_profile_with(
+                &shared,
+                &config,
+                &registry,
+                "evening",
+                None,
+                false,
+                &AtomicBool::new(true)
+            ),
+            0
+        );
+        let zones = aw.get_rgb_zones().unwrap().zones;
+        assert_eq!(aw.zone_colour(&zones[&Zone::Head]), Colour::new(15, 10, 5));
+        assert_eq!(zones[&Zone::Head].colour(), Colour::new(8, 5, 3));
+        assert_eq!(aw.zone_colour(&zones[&Zone::Left]), Colour::new(15, 0, 0));
+        let brightness = aw.get_brightness().unwrap();
+        assert_eq!(brightness.of_zone(Zone::Right), 10);
+
+        let profile = capture_profile(aw).unwrap();
+        assert_eq!(profile.brightness, Some(aw.get_brightness().unwrap()));
+        assert_eq!(profile.state.zones[&Zone::Right], Colour::new(15, 0, 0));
+        assert_eq!(profile.effect, None);
+
+        assert_eq!(
+            apply_profile_with(
+                &shared,
+                &config,
+                &registry,
+                "morning",
+                None,
+                false,
+                &AtomicBool::new(true)
+            ),
+            1
+        );
+
+        // stopped part way through, the brightness and colours from before are put back
+        let shared = SharedAlienware::new(Alienware::in_memory());
+        let aw = shared.unlocked();
+        let before = aw.get_rgb_zones().unwrap();
+        assert_eq!(
+            apply_profile_with(
+                &shared,
+                &config,
+                &registry,
+                "evening",
+                None,
+                false,
+                &AtomicBool::new(false)
+            ),
+            0
+        );
+        assert_eq!(aw.get_brightness().unwrap(), Brightness::default());
+        assert_eq!(aw.get_rgb_zones().unwrap(), before);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::colour::COLOUR_NAMES;
+use crate::command::{parse_command, run_command, COMMANDS};
+use crate::config::Config;
+use crate::restore::{restore_if_stopped, stop_on_signal, SavedColours};
+use crate::zones::zone_names;
+use alienware::{SharedAlienware, Transition, Zone};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::env;
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+/// Commands that are only available at the interactive prompt
+const REPL_COMMANDS: [&str; 3] = ["help", "exit", "quit"];
+
+/// Tab completion of commands, zones, groups, colours and HDMI sources
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct CommandHelper {
+    zone_names: Vec<String>,
+}
+
+impl Completer for CommandHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, candidates) = complete(&line[..pos], &self.zone_names);
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .map(|candidate| Pair {
+                    display: candidate.clone(),
+                    replacement: candidate,
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Find the completions of the last word of a partial line, returning where the completed text starts
+pub fn complete(line: &str, zone_names: &[String]) -> (usize, Vec<String>) {
+    let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+    let word = &line[start..];
+    let previous: Vec<&str> = line[..start].split_whitespace().collect();
+    let (start, word, options): (usize, &str, Vec<&str>) = match previous.first() {
+        None => (
+            start,
+            word,
+            COMMANDS
+                .iter()
+                .chain(REPL_COMMANDS.iter())
+                .copied()
+                .collect(),
+        ),
+        Some(&"set") => match word.split_once('=') {
+            Some((target, colour)) => (start + target.len() + 1, colour, COLOUR_NAMES.to_vec()),
+            None => {
+                if previous.len() % 2 == 0 {
+                    (start, word, COLOUR_NAMES.to_vec())
+                } else {
+                    (start, word, zone_names.iter().map(|s| s.as_str()).collect())
+                }
+            }
+        },
+        Some(&"hdmi") if previous.len() == 1 => (start, word, vec!["cable", "gpu"]),
+        _ => (start, word, Vec::new()),
+    };
+    (
+        start,
+        options
+            .into_iter()
+            .filter(|option| option.starts_with(word))
+            .map(|option| option.to_string())
+            .collect(),
+    )
+}
+
+/// The file that the prompt history is kept in, in the XDG state directory
+fn history_path() -> Option<PathBuf> {
+    let mut path = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let mut path = PathBuf::from(env::var_os("HOME")?);
+            path.push(".local");
+            path.push("state");
+            path
+        }
+    };
+    path.push("alienware");
+    path.push("history");
+    Some(path)
+}
+
+/// Run commands typed at an interactive prompt until `exit` or end of input, returning the exit code.  A command that is
+/// stopped by a signal puts back the colours from before it, unless they are to be kept, and the prompt carries on.
+pub fn repl(aw: &SharedAlienware, config: &Config, fade: Option<Transition>, keep: bool) -> i32 {
+    let discovered: Vec<Zone> = aw
+        .get_rgb_zones()
+        .map(|leds| leds.zones.keys().copied().collect())
+        .unwrap_or_default();
+    let mut editor: Editor<CommandHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(x) => {
+            println!("Problem starting the interactive prompt: {x}");
+            return 1;
+        }
+    };
+    editor.set_helper(Some(CommandHelper {
+        zone_names: zone_names(config, &discovered),
+    }));
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+    // Ctrl-C at the prompt is read as a key, so the handler only sees it while a command runs
+    let running = match stop_on_signal() {
+        Ok(running) => running,
+        Err(x) => {
+            println!("Problem setting up Ctrl-C: {x}");
+            return 1;
+        }
+    };
+    println!("Type help for a list of commands, exit or Ctrl-D to leave");
+    loop {
+        let line = editor.readline("alienware> ");
+        // SIGTERM or SIGHUP while waiting at the prompt
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        match line {
+            Ok(line) => {
+                let _ = editor.add_history_entry(line.as_str());
+                match line.trim() {
+                    "exit" | "quit" => break,
+                    "help" => print_help(),
+                    _ => match parse_command(line.as_str()) {
+                        Ok(Some(command)) => {
+                            // a machine without LEDs can still run hdmi commands, so there may be nothing to put back
+                            let saved = SavedColours::capture(aw.unlocked()).unwrap_or_default();
+                            run_command(aw, config, fade, &command, &running);
+                            restore_if_stopped(aw, &saved, &running, keep, 0);
+                            running.store(true, Ordering::SeqCst);
+                        }
+                        Ok(None) => {}
+                        Err(x) => println!("{x}"),
+                    },
+                }
+            }
+            Err(ReadlineError::Interrupted) => {}
+            Err(ReadlineError::Eof) => break,
+            Err(x) => {
+                println!("Problem reading the command: {x}");
+                break;
+            }
+        }
+    }
+    if let Some(history) = &history {
+        if let Some(dir) = history.parent() {
+            let _ = create_dir_all(dir);
+        }
+        let _ = editor.save_history(history);
+    }
+    0
+}
+
+/// Print the commands that can be used at the prompt
+fn print_help() {
+    println!(
+        "    set <zone> <colour>...  set zones or groups, e.g. set head red or set sides=#00ff00"
+    );
+    println!("    sleep <duration>        pause, e.g. sleep 500ms or sleep 2s");
+    println!("    hdmi <cable|gpu>        set the source of the HDMI output");
+    println!("    help                    show this list");
+    println!("    exit                    leave the prompt");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::complete;
+
+    #[test]
+    fn completions() {
+        let zones = vec!["head".to_string(), "left".to_string(), "all".to_string()];
+        assert_eq!(
+            complete("s", &zones),
+            (0, vec!["set".to_string(), "sleep".to_string()])
+        );
+        assert_eq!(complete("set ", &zones).1, zones);
+        assert_eq!(complete("set he", &zones), (4, vec!["head".to_string()]));
+        assert_eq!(complete("set head r", &zones), (9, vec!["red".to_string()]));
+        assert_eq!(
+            complete("set head=bl", &zones),
+            (9, vec!["black".to_string(), "blue".to_string()])
+        );
+        assert_eq!(
+            complete("set head red l", &zones),
+            (13, vec!["left".to_string()])
+        );
+        assert_eq!(complete("hdmi g", &zones), (5, vec!["gpu".to_string()]));
+        assert_eq!(complete("sleep 1", &zones), (6, vec![]));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::print_error;
+use alienware::{Alienware, Colour, SharedAlienware, Zone, ZoneWriter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// How often a sleep checks whether it has been stopped by a signal
+const SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+/// The colours of the zones when a long running command started, to put back if it is stopped by a signal
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SavedColours {
+    colours: Vec<(Zone, Colour)>,
+}
+
+impl SavedColours {
+    /// Read the colours that the zones have now
+    pub fn capture(aw: &Alienware) -> std::io::Result<SavedColours> {
+        let colours = aw.get_zone_colours()?.into_iter().collect();
+        Ok(SavedColours { colours })
+    }
+
+    /// Set the zones back to the colours they had when they were captured
+    pub fn restore(&self, aw: &Alienware) -> std::io::Result<()> {
+        let mut writer = ZoneWriter::new(aw);
+        for (zone, colour) in self.colours.iter() {
+            writer.set(*zone, *colour)?;
+        }
+        writer.flush()
+    }
+}
+
+/// A flag that is set while running, which is cleared by Ctrl-C, `SIGTERM` or `SIGHUP`
+pub fn stop_on_signal() -> Result<Arc<AtomicBool>, ctrlc::Error> {
+    let running = Arc::new(AtomicBool::new(true));
+    let handler = running.clone();
+    ctrlc::set_handler(move || handler.store(false, Ordering::SeqCst))?;
+    Ok(running)
+}
+
+/// Once a command has finished, put back the colours it started with if it was stopped by a signal, unless they are to
+/// be kept, returning the exit code of the command or 1 if they could not be put back
+pub fn restore_if_stopped(
+    aw: &SharedAlienware,
+    saved: &SavedColours,
+    running: &AtomicBool,
+    keep: bool,
+    code: i32,
+) -> i32 {
+    if keep || running.load(Ordering::SeqCst) {
+        return code;
+    }
+    match aw.lock().and_then(|aw| saved.restore(&aw)) {
+        Ok(()) => code,
+        Err(x) => {
+            print_error("Problem restoring the LEDs", &x);
+            1
+        }
+    }
+}
+
+/// Sleep for a duration or until `running` is cleared, returning whether it is still set
+pub fn sleep_while_running(duration: Duration, running: &AtomicBool) -> bool {
+    let end = Instant::now() + duration;
+    while running.load(Ordering::SeqCst) {
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        sleep(left.min(SLEEP_SLICE));
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{restore_if_stopped, sleep_while_running, SavedColours};
+    use alienware::{Alienware, SharedAlienware, Zone};
+    use std::sync::atomic::AtomicBool;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn restore() {
+        let aw = SharedAlienware::new(Alienware::in_memory());
+        let before = aw.get_rgb_zones().unwrap();
+        let saved = SavedColours::capture(aw.unlocked()).unwrap();
+        aw.set_rgb_zone(Zone::Head, 1, 2, 3).unwrap();
+        assert_eq!(
+            restore_if_stopped(&aw, &saved, &AtomicBool::new(true), false, 0),
+            0
+        );
+        assert_ne!(aw.get_rgb_zones().unwrap(), before);
+        assert_eq!(
+            restore_if_stopped(&aw, &saved, &AtomicBool::new(false), true, 0),
+            0
+        );
+        assert_ne!(aw.get_rgb_zones().unwrap(), before);
+        assert_eq!(
+            restore_if_stopped(&aw, &saved, &AtomicBool::new(false), false, 0),
+            0
+        );
+        assert_eq!(aw.get_rgb_zones().unwrap(), before);
+    }
+
+    #[test]
+    fn sleep() {
+        let start = Instant::now();
+        assert!(sleep_while_running(
+            Duration::from_millis(20),
+            &AtomicBool::new(true)
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        let start = Instant::now();
+        assert!(!sleep_while_running(
+            Duration::from_secs(10),
+            &AtomicBool::new(false)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
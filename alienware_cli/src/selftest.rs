@@ -0,0 +1,151 @@
+use alienware::{Alienware, Colour, Zone};
+use jzon::{array, object};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The colours that each zone is cycled through
+const PRIMARIES: [(&str, Colour); 3] = [
+    ("red", Colour::new(15, 0, 0)),
+    ("green", Colour::new(0, 15, 0)),
+    ("blue", Colour::new(0, 0, 15)),
+];
+
+/// The outcome of a single check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: String, passed: bool, detail: String) -> Check {
+        Check {
+            name,
+            passed,
+            detail,
+        }
+    }
+}
+
+/// Cycle every zone through the primary colours, reading each one back, check that the HDMI state can be read, and
+/// restore the original state
+pub fn run_checks(aw: &Alienware, delay: Duration) -> Vec<Check> {
+    let mut checks = Vec::new();
+    if !aw.is_alienware() {
+        checks.push(Check::new(
+            "read state".to_string(),
+            false,
+            "this machine has no alienware platform device".to_string(),
+        ));
+        return checks;
+    }
+    let original = match aw.get_state() {
+        Ok(original) => {
+            checks.push(Check::new(
+                "read state".to_string(),
+                true,
+                format!("{} LED zones", original.zones.len()),
+            ));
+            original
+        }
+        Err(x) => {
+            checks.push(Check::new("read state".to_string(), false, x.to_string()));
+            return checks;
+        }
+    };
+
+    for zone in [Zone::Head, Zone::Left, Zone::Right] {
+        if !original.zones.contains_key(&zone) {
+            continue;
+        }
+        for (name, colour) in PRIMARIES {
+            let name = format!("{zone} {name}");
+            if let Err(x) = aw.set_rgb_zone(zone, colour.red, colour.green, colour.blue) {
+                checks.push(Check::new(name, false, format!("write failed: {x}")));
+                continue;
+            }
+            sleep(delay);
+            if aw.is_dry_run() {
+                checks.push(Check::new(
+                    name,
+                    true,
+                    "read back skipped in dry run".to_string(),
+                ));
+                continue;
+            }
+            checks.push(match aw.get_rgb_zones() {
+                Ok(leds) => match leds.zones.get(&zone) {
+                    Some(read) if aw.zone_colour(read) == colour => {
+                        Check::new(name, true, format!("read back {colour}"))
+                    }
+                    Some(read) => Check::new(
+                        name,
+                        false,
+                        format!("wrote {colour} but read back {}", aw.zone_colour(read)),
+                    ),
+                    None => Check::new(name, false, "zone disappeared".to_string()),
+                },
+                Err(x) => Check::new(name, false, format!("read back failed: {x}")),
+            });
+        }
+    }
+
+    checks.push(match aw.get_hdmi() {
+        Ok(hdmi) if hdmi.exists => Check::new(
+            "hdmi".to_string(),
+            true,
+            format!("input {}, output {}", hdmi.cable_state, hdmi.source),
+        ),
+        Ok(_) => Check::new("hdmi".to_string(), true, "not present".to_string()),
+        Err(x) => Check::new("hdmi".to_string(), false, x.to_string()),
+    });
+
+    checks.push(match aw.apply_state(&original) {
+        Ok(_) => Check::new(
+            "restore".to_string(),
+            true,
+            "original colours restored".to_string(),
+        ),
+        Err(x) => Check::new("restore".to_string(), false, x.to_string()),
+    });
+    checks
+}
+
+/// Run the self test and print the report, returning the exit code
+pub fn selftest(aw: &Alienware, delay: Duration, json: bool) -> i32 {
+    let checks = run_checks(aw, delay);
+    let failed = checks.iter().filter(|check| !check.passed).count();
+    if json {
+        let mut checks_data = array![];
+        for check in checks.iter() {
+            checks_data
+                .push(object! {
+                    "name": check.name.as_str(),
+                    "passed": check.passed,
+                    "detail": check.detail.as_str(),
+                })
+                .unwrap();
+        }
+        let report = object! {
+            "passed": failed == 0,
+            "checks": checks_data,
+        };
+        println!("{}", report.dump());
+    } else {
+        for check in checks.iter() {
+            let result = if check.passed { "PASS" } else { "FAIL" };
+            println!("{result}  {}: {}", check.name, check.detail);
+        }
+        if failed == 0 {
+            println!("\nSelf test passed ({} checks)", checks.len());
+        } else {
+            println!("\nSelf test failed ({failed} of {} checks)", checks.len());
+        }
+    }
+    if failed == 0 {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,173 @@
+use crate::colour::parse_colour;
+use crate::config::Config;
+use crate::print_error;
+use crate::zones::resolve_zones;
+use alienware::{Alienware, Change, Colour, State, Transition, Zone};
+use std::sync::atomic::AtomicBool;
+
+/// A colour to be set on a zone or group of zones, before it is resolved against the zones of the machine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assignment {
+    pub target: String,
+    pub colour: Colour,
+}
+
+/// Parse a list of assignments, each either `zone=colour` or `zone colour`
+pub fn parse_assignments<S: AsRef<str>>(args: &[S]) -> Result<Vec<Assignment>, String> {
+    let mut assignments = Vec::new();
+    let mut args = args.iter().map(|arg| arg.as_ref());
+    while let Some(arg) = args.next() {
+        let (target, colour) = match arg.split_once('=') {
+            Some((target, colour)) => (target, colour),
+            None => (
+                arg,
+                args.next().ok_or(format!("No colour given for '{arg}'"))?,
+            ),
+        };
+        let colour = parse_colour(colour).ok_or(format!("'{colour}' is not a colour"))?;
+        assignments.push(Assignment {
+            target: target.trim().to_string(),
+            colour,
+        });
+    }
+    if assignments.is_empty() {
+        return Err("No zones to set".to_string());
+    }
+    Ok(assignments)
+}
+
+/// Resolve the assignments against the zones of this machine, later assignments overriding earlier ones
+pub fn resolve_assignments(
+    assignments: &[Assignment],
+    config: &Config,
+    discovered: &[Zone],
+) -> Result<State, String> {
+    let mut state = State::default();
+    for assignment in assignments {
+        for zone in resolve_zones(assignment.target.as_str(), config, discovered)? {
+            state.zones.insert(zone, assignment.colour);
+        }
+    }
+    Ok(state)
+}
+
+/// Set the colour of zones and groups of zones, returning the exit code
+pub fn set<S: AsRef<str>>(
+    aw: &Alienware,
+    config: &Config,
+    fade: Option<Transition>,
+    args: &[S],
+) -> i32 {
+    match parse_assignments(args) {
+        Ok(assignments) => {
+            if set_assignments(aw, config, fade, &assignments, &AtomicBool::new(true)) {
+                0
+            } else {
+                1
+            }
+        }
+        Err(x) => {
+            println!("{x}");
+            1
+        }
+    }
+}
+
+/// Set the colours of parsed assignments, fading to them until `running` is cleared if there is a fade, printing any
+/// problem and returning whether they were all set
+pub fn set_assignments(
+    aw: &Alienware,
+    config: &Config,
+    fade: Option<Transition>,
+    assignments: &[Assignment],
+    running: &AtomicBool,
+) -> bool {
+    let leds = match aw.get_rgb_zones() {
+        Ok(leds) => leds,
+        Err(x) => {
+            print_error("Problem setting RGB value", &x);
+            return false;
+        }
+    };
+    if !leds.exists {
+        println!("There is no alienware LED unit on this machine");
+        return false;
+    }
+    let discovered: Vec<Zone> = leds.zones.keys().copied().collect();
+    let state = match resolve_assignments(assignments, config, &discovered) {
+        Ok(state) => state,
+        Err(x) => {
+            println!("{x}");
+            return false;
+        }
+    };
+    match aw
+        .diff_state(&state)
+        .and_then(|changes| apply_changes(aw, &changes, fade, running))
+    {
+        Ok(()) => true,
+        Err(x) => {
+            print_error("Problem setting RGB value", &x);
+            false
+        }
+    }
+}
+
+/// Make a set of changes, fading the zones to their new colours if there is a fade, which stops part way through if
+/// `running` is cleared
+pub fn apply_changes(
+    aw: &Alienware,
+    changes: &[Change],
+    fade: Option<Transition>,
+    running: &AtomicBool,
+) -> std::io::Result<()> {
+    match fade {
+        Some(transition) => aw.transition_changes_until(changes, transition, running),
+        None => aw.apply_changes(changes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_assignments, resolve_assignments, Assignment};
+    use crate::config::Config;
+    use alienware::{Colour, Zone};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_assignments(&["head=red", "left=#00ff00", "right", "0 0 15"]),
+            Ok(vec![
+                Assignment {
+                    target: "head".to_string(),
+                    colour: Colour::new(15, 0, 0)
+                },
+                Assignment {
+                    target: "left".to_string(),
+                    colour: Colour::new(0, 15, 0)
+                },
+                Assignment {
+                    target: "right".to_string(),
+                    colour: Colour::new(0, 0, 15)
+                },
+            ])
+        );
+        assert!(parse_assignments(&["head"]).is_err());
+        assert!(parse_assignments(&["head=mauve"]).is_err());
+        assert!(parse_assignments::<&str>(&[]).is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let assignments = parse_assignments(&["all=red", "head=blue"]).unwrap();
+        let state = resolve_assignments(
+            &assignments,
+            &Config::default(),
+            &[Zone::Head, Zone::Left, Zone::Right],
+        )
+        .unwrap();
+        assert_eq!(state.zones[&Zone::Head], Colour::new(0, 0, 15));
+        assert_eq!(state.zones[&Zone::Left], Colour::new(15, 0, 0));
+        assert_eq!(state.zones[&Zone::Right], Colour::new(15, 0, 0));
+    }
+}
@@ -0,0 +1,57 @@
+use alienware::{DEFAULT_BRIGHTNESS_FILE, DEFAULT_LOCK_FILE};
+use snapcraft::{in_snap, snap_common, snap_name};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The plug that gives the snap access to the alienware platform device
+pub const SNAP_PLUG: &str = "sys-devices-platform-alienware-wmi";
+
+/// Whether the snap has been connected to the alienware platform device, or `None` if this is not running in a snap
+/// or the connection cannot be checked
+pub fn snap_connected() -> Option<bool> {
+    if !in_snap() {
+        return None;
+    }
+    Command::new("snapctl")
+        .arg("is-connected")
+        .arg(SNAP_PLUG)
+        .status()
+        .ok()
+        .map(|status| status.success())
+}
+
+/// The command that connects the snap to the alienware platform device
+pub fn connect_command() -> String {
+    format!(
+        "sudo snap connect {}:{SNAP_PLUG}",
+        snap_name().unwrap_or("alienware-cli".to_string())
+    )
+}
+
+/// The file that the brightness is kept in, which is in the data directory of the snap when running in one as the
+/// usual place cannot be written from there
+pub fn brightness_file() -> PathBuf {
+    match snap_common() {
+        Some(dir) if in_snap() => dir.join("brightness"),
+        _ => PathBuf::from(DEFAULT_BRIGHTNESS_FILE),
+    }
+}
+
+/// The file that is locked while writing to the device, which is in the data directory of the snap when running in one
+/// for the same reason as the brightness file
+pub fn lock_file() -> PathBuf {
+    match snap_common() {
+        Some(dir) if in_snap() => dir.join("alienware-wmi.lock"),
+        _ => PathBuf::from(DEFAULT_LOCK_FILE),
+    }
+}
+
+/// Explain how to unblock access to the alienware device if this is an unconnected snap
+pub fn check_snap() {
+    if snap_connected() == Some(false) {
+        println!("This may be because you have installed alienware-cli from snap, which prevents automatic setup.\n");
+        print!("The snap container initially blocks access to the alienware device setup that is needed to carry out this action.  ");
+        println!("The following command can be run to unblock access to the alienware device and then you can try again:\n");
+        println!("    {}\n \n", connect_command());
+    }
+}
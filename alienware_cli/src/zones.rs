@@ -0,0 +1,93 @@
+use crate::config::Config;
+use alienware::Zone;
+
+/// All of the zones, in the order that they are reported
+pub const ZONES: [Zone; 3] = [Zone::Head, Zone::Left, Zone::Right];
+
+/// Parse the name of an LED zone
+pub fn parse_zone(input: &str) -> Option<Zone> {
+    input.parse().ok()
+}
+
+/// Resolve a zone name, a built-in group (`all` or `sides`) or a group from the config file to the zones it covers
+/// on this machine
+pub fn resolve_zones(
+    name: &str,
+    config: &Config,
+    discovered: &[Zone],
+) -> Result<Vec<Zone>, String> {
+    let name = name.to_lowercase();
+    if let Some(zone) = parse_zone(name.as_str()) {
+        return if discovered.contains(&zone) {
+            Ok(vec![zone])
+        } else {
+            Err(format!("There are no {zone} LEDs"))
+        };
+    }
+    let members = match name.as_str() {
+        "all" => ZONES.to_vec(),
+        "sides" => vec![Zone::Left, Zone::Right],
+        _ => config
+            .groups
+            .get(name.as_str())
+            .cloned()
+            .ok_or(format!("'{name}' is not a zone or a group"))?,
+    };
+    let zones: Vec<Zone> = members
+        .into_iter()
+        .filter(|zone| discovered.contains(zone))
+        .collect();
+    if zones.is_empty() {
+        Err(format!("There are no LEDs in the {name} group"))
+    } else {
+        Ok(zones)
+    }
+}
+
+/// The names that can be used to select zones on this machine: the zones themselves, the built-in groups and the
+/// configured groups
+pub fn zone_names(config: &Config, discovered: &[Zone]) -> Vec<String> {
+    let mut names: Vec<String> = ZONES
+        .iter()
+        .filter(|zone| discovered.contains(zone))
+        .map(|zone| zone.to_string())
+        .collect();
+    names.push("all".to_string());
+    names.push("sides".to_string());
+    names.extend(config.groups.keys().cloned());
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_zones;
+    use crate::config::Config;
+    use alienware::Zone;
+
+    #[test]
+    fn resolve() {
+        let mut config = Config::default();
+        config
+            .groups
+            .insert("front".to_string(), vec![Zone::Head, Zone::Right]);
+        let discovered = [Zone::Head, Zone::Left];
+        assert_eq!(
+            resolve_zones("HEAD", &config, &discovered),
+            Ok(vec![Zone::Head])
+        );
+        assert!(resolve_zones("right", &config, &discovered).is_err());
+        assert_eq!(
+            resolve_zones("all", &config, &discovered),
+            Ok(vec![Zone::Head, Zone::Left])
+        );
+        assert_eq!(
+            resolve_zones("sides", &config, &discovered),
+            Ok(vec![Zone::Left])
+        );
+        assert_eq!(
+            resolve_zones("front", &config, &discovered),
+            Ok(vec![Zone::Head])
+        );
+        assert!(resolve_zones("back", &config, &discovered).is_err());
+    }
+}